@@ -0,0 +1,467 @@
+//! The file systems a `PatchSet` can be applied to
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+// The mode git gives regular files which aren't executable
+const DEFAULT_MODE: u32 = 0o100644;
+// The mode git gives symbolic links
+const SYMLINK_MODE: u32 = 0o120000;
+
+/// A file system which a `PatchSet` can be applied to with
+/// [`ApplyOptions::apply_patch_set`]
+///
+/// Paths are always relative and only made up of normal components, i.e.
+/// they never contain `..` or a root. File modes use the values found in git
+/// patches, such as `0o100644` for regular files, `0o100755` for executable
+/// files and `0o120000` for symbolic links.
+///
+/// [`ApplyOptions::apply_patch_set`]: crate::ApplyOptions::apply_patch_set
+pub trait FileSystem {
+    /// Returns the contents of the file at `path`
+    ///
+    /// Fails with an error of kind [`io::ErrorKind::NotFound`] if the file
+    /// doesn't exist.
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Writes `contents` to the file at `path`, creating the file and any
+    /// missing parent directories if needed
+    fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+    /// Deletes the file at `path`
+    fn delete(&mut self, path: &Path) -> io::Result<()>;
+
+    /// Moves the file at `from` to `to`, creating any missing parent
+    /// directories of `to`
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// Returns `true` if a file exists at `path`
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Returns the mode of the file at `path`, or `None` if the file system
+    /// doesn't keep track of modes
+    fn mode(&self, path: &Path) -> io::Result<Option<u32>>;
+
+    /// Sets the mode of the file at `path`
+    ///
+    /// File systems which don't keep track of modes can ignore this.
+    fn set_mode(&mut self, path: &Path, mode: u32) -> io::Result<()>;
+}
+
+/// A [`FileSystem`] held entirely in memory
+///
+/// ```
+/// use diffy::{apply_patch_set, MemoryFs, PatchSet};
+///
+/// let s = "\
+/// diff --git a/spren b/spren
+/// --- a/spren
+/// +++ b/spren
+/// @@ -1 +1 @@
+/// -Pattern
+/// +Wyndle
+/// ";
+/// let patch_set = PatchSet::from_bytes(s.as_bytes()).unwrap();
+///
+/// let mut fs = MemoryFs::new();
+/// fs.insert("spren", "Pattern\n");
+/// apply_patch_set(&mut fs, &patch_set).unwrap();
+/// assert_eq!(fs.get("spren"), Some(&b"Wyndle\n"[..]));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryFs {
+    files: BTreeMap<PathBuf, MemoryFile>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct MemoryFile {
+    contents: Vec<u8>,
+    mode: u32,
+}
+
+impl MemoryFs {
+    /// Construct an empty `MemoryFs`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a regular file to the file system, replacing any existing file at
+    /// `path`
+    pub fn insert<P: Into<PathBuf>, C: Into<Vec<u8>>>(
+        &mut self,
+        path: P,
+        contents: C,
+    ) -> &mut Self {
+        let file = MemoryFile {
+            contents: contents.into(),
+            mode: DEFAULT_MODE,
+        };
+        self.files.insert(path.into(), file);
+        self
+    }
+
+    /// Returns the contents of the file at `path`, if it exists
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<&[u8]> {
+        self.files
+            .get(path.as_ref())
+            .map(|file| file.contents.as_slice())
+    }
+
+    /// Returns an iterator over the paths and contents of the files, sorted
+    /// by path
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &[u8])> {
+        self.files
+            .iter()
+            .map(|(path, file)| (path.as_path(), file.contents.as_slice()))
+    }
+
+    fn file_mut(&mut self, path: &Path) -> io::Result<&mut MemoryFile> {
+        self.files.get_mut(path).ok_or_else(not_found)
+    }
+}
+
+impl FileSystem for MemoryFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.get(path).map(<[u8]>::to_vec).ok_or_else(not_found)
+    }
+
+    fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        match self.files.get_mut(path) {
+            Some(file) => file.contents = contents.to_vec(),
+            None => {
+                self.insert(path, contents);
+            }
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, path: &Path) -> io::Result<()> {
+        self.files.remove(path).map(drop).ok_or_else(not_found)
+    }
+
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        let file = self.files.remove(from).ok_or_else(not_found)?;
+        self.files.insert(to.to_owned(), file);
+        Ok(())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.files.contains_key(path)
+    }
+
+    fn mode(&self, path: &Path) -> io::Result<Option<u32>> {
+        self.files
+            .get(path)
+            .map(|file| Some(file.mode))
+            .ok_or_else(not_found)
+    }
+
+    fn set_mode(&mut self, path: &Path, mode: u32) -> io::Result<()> {
+        self.file_mut(path)?.mode = mode;
+        Ok(())
+    }
+}
+
+fn not_found() -> io::Error {
+    io::ErrorKind::NotFound.into()
+}
+
+/// A [`FileSystem`] backed by a directory on disk using `std::fs`
+///
+/// Modes are only tracked on unix, where a file is executable if any of its
+/// execute permission bits are set and symbolic links are read and written
+/// as the path they point to, like git does. Paths which lead through a
+/// symbolic link to a directory are refused so that a patch can't touch
+/// files outside of the root directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StdFs {
+    root: PathBuf,
+}
+
+impl StdFs {
+    /// Construct a `StdFs` for the files in the directory `root`
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Returns the directory holding the files
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // Returns the path of a file on disk, creating its parent directories if `create_parent` is set.
+    // Fails if any of the parent directories is a symbolic link.
+    fn path(&self, path: &Path, create_parent: bool) -> io::Result<PathBuf> {
+        for parent in path.ancestors().skip(1) {
+            if parent.as_os_str().is_empty() {
+                continue;
+            }
+            match fs::symlink_metadata(self.root.join(parent)) {
+                Ok(metadata) if metadata.file_type().is_symlink() => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("{} is beyond a symbolic link", path.display()),
+                    ))
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let path = self.root.join(path);
+        if create_parent {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        Ok(path)
+    }
+}
+
+impl FileSystem for StdFs {
+    #[cfg(unix)]
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = self.path(path, false)?;
+        if fs::symlink_metadata(&path)?.file_type().is_symlink() {
+            Ok(fs::read_link(&path)?.as_os_str().as_bytes().to_vec())
+        } else {
+            fs::read(&path)
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(self.path(path, false)?)
+    }
+
+    #[cfg(unix)]
+    fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let path = self.path(path, true)?;
+        // Writing to a symbolic link changes where it points rather than the file it points to
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                fs::remove_file(&path)?;
+                symlink(contents, &path)
+            }
+            _ => fs::write(&path, contents),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        fs::write(self.path(path, true)?, contents)
+    }
+
+    fn delete(&mut self, path: &Path) -> io::Result<()> {
+        fs::remove_file(self.path(path, false)?)
+    }
+
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(self.path(from, false)?, self.path(to, true)?)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.path(path, false)
+            .map_or(false, |path| fs::symlink_metadata(path).is_ok())
+    }
+
+    #[cfg(unix)]
+    fn mode(&self, path: &Path) -> io::Result<Option<u32>> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let metadata = fs::symlink_metadata(self.path(path, false)?)?;
+        let mode = if metadata.file_type().is_symlink() {
+            SYMLINK_MODE
+        } else if metadata.permissions().mode() & 0o111 != 0 {
+            0o100755
+        } else {
+            DEFAULT_MODE
+        };
+        Ok(Some(mode))
+    }
+
+    #[cfg(not(unix))]
+    fn mode(&self, _path: &Path) -> io::Result<Option<u32>> {
+        Ok(None)
+    }
+
+    #[cfg(unix)]
+    fn set_mode(&mut self, path: &Path, mode: u32) -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = self.path(path, false)?;
+        let is_symlink = fs::symlink_metadata(&path)?.file_type().is_symlink();
+
+        // Turn the file into a symbolic link pointing to its contents or back again
+        if mode == SYMLINK_MODE {
+            if !is_symlink {
+                let target = fs::read(&path)?;
+                fs::remove_file(&path)?;
+                symlink(&target, &path)?;
+            }
+            return Ok(());
+        }
+        if is_symlink {
+            use std::os::unix::ffi::OsStrExt;
+
+            let target = fs::read_link(&path)?;
+            fs::remove_file(&path)?;
+            fs::write(&path, target.as_os_str().as_bytes())?;
+        }
+
+        // Only the executable bit of regular files is tracked, the same as git
+        if mode & 0o170000 != 0o100000 {
+            return Ok(());
+        }
+        let mut permissions = fs::metadata(&path)?.permissions();
+        let current = permissions.mode();
+        let updated = if mode & 0o111 != 0 {
+            // Let whoever can read the file execute it
+            current | (current & 0o444) >> 2
+        } else {
+            current & !0o111
+        };
+        if updated != current {
+            permissions.set_mode(updated);
+            fs::set_permissions(&path, permissions)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn set_mode(&mut self, _path: &Path, mode: u32) -> io::Result<()> {
+        if mode == SYMLINK_MODE {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "symbolic links are not supported on this platform",
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Creates a symbolic link at `path` pointing to `target`
+#[cfg(unix)]
+fn symlink(target: &[u8], path: &Path) -> io::Result<()> {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    std::os::unix::fs::symlink(OsStr::from_bytes(target), path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs the same checks against each file system
+    fn exercise<F: FileSystem>(fs: &mut F) {
+        let alpha = Path::new("src/alpha");
+        let beta = Path::new("docs/beta");
+
+        assert!(!fs.exists(alpha));
+        assert_eq!(fs.read(alpha).unwrap_err().kind(), io::ErrorKind::NotFound);
+
+        fs.write(alpha, b"Kaladin\n").unwrap();
+        assert!(fs.exists(alpha));
+        assert_eq!(fs.read(alpha).unwrap(), b"Kaladin\n");
+
+        fs.rename(alpha, beta).unwrap();
+        assert!(!fs.exists(alpha));
+        assert_eq!(fs.read(beta).unwrap(), b"Kaladin\n");
+
+        if let Some(mode) = fs.mode(beta).unwrap() {
+            assert_eq!(mode, 0o100644);
+            fs.set_mode(beta, 0o100755).unwrap();
+            assert_eq!(fs.mode(beta).unwrap(), Some(0o100755));
+            fs.set_mode(beta, 0o100644).unwrap();
+            assert_eq!(fs.mode(beta).unwrap(), Some(0o100644));
+        }
+
+        fs.delete(beta).unwrap();
+        assert!(!fs.exists(beta));
+        fs.delete(beta).unwrap_err();
+    }
+
+    #[test]
+    fn memory_fs() {
+        let mut fs = MemoryFs::new();
+        exercise(&mut fs);
+        assert_eq!(fs.iter().count(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn std_fs_symlinks() {
+        let dir =
+            std::env::temp_dir().join(format!("diffy-std-fs-symlinks-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let root = dir.join("root");
+        let outside = dir.join("outside");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("secret"), b"Odium\n").unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        std::os::unix::fs::symlink(outside.join("secret"), root.join("escape")).unwrap();
+
+        let mut fs = StdFs::new(&root);
+        let through_link = Path::new("link/pwned");
+        let err = fs.write(through_link, b"Kaladin\n").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(!outside.join("pwned").exists());
+        let patch = "\
+diff --git a/link/pwned b/link/pwned
+new file mode 100644
+--- /dev/null
++++ b/link/pwned
+@@ -0,0 +1 @@
++Kaladin
+";
+        let patch = crate::PatchSet::from_bytes(patch.as_bytes()).unwrap();
+        crate::apply_patch_set(&mut fs, &patch).unwrap_err();
+        assert!(!outside.join("pwned").exists());
+        fs.read(Path::new("link/secret")).unwrap_err();
+        fs.delete(Path::new("link/secret")).unwrap_err();
+        assert!(!fs.exists(Path::new("link/secret")));
+
+        // Symbolic links are read and written as the path they point to
+        let escape = Path::new("escape");
+        assert_eq!(fs.mode(escape).unwrap(), Some(SYMLINK_MODE));
+        assert_eq!(
+            fs.read(escape).unwrap(),
+            outside.join("secret").to_str().unwrap().as_bytes()
+        );
+        fs.write(escape, b"elsewhere").unwrap();
+        assert_eq!(
+            fs::read_link(root.join("escape")).unwrap(),
+            Path::new("elsewhere")
+        );
+        assert_eq!(fs::read(outside.join("secret")).unwrap(), b"Odium\n");
+
+        let alpha = Path::new("alpha");
+        fs.write(alpha, b"beta").unwrap();
+        fs.set_mode(alpha, SYMLINK_MODE).unwrap();
+        assert_eq!(
+            fs::read_link(root.join("alpha")).unwrap(),
+            Path::new("beta")
+        );
+        assert_eq!(fs.mode(alpha).unwrap(), Some(SYMLINK_MODE));
+        fs.set_mode(alpha, DEFAULT_MODE).unwrap();
+        assert_eq!(fs.mode(alpha).unwrap(), Some(DEFAULT_MODE));
+        assert_eq!(fs.read(alpha).unwrap(), b"beta");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn std_fs() {
+        let root = std::env::temp_dir().join(format!("diffy-std-fs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        exercise(&mut StdFs::new(&root));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
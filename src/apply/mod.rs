@@ -0,0 +1,1776 @@
+mod filesystem;
+mod set;
+
+pub use filesystem::{FileSystem, MemoryFs, StdFs};
+pub use set::{apply_patch_set, AppliedFile, PatchSetError, PatchSetErrorKind};
+
+use crate::{
+    diff::Whitespace,
+    merge::MergeOptions,
+    patch::{Hunk, HunkRange, Line, Patch},
+    utils::{self, token::LineIter, Text},
+};
+use std::{borrow::Cow, cmp, fmt, iter};
+
+/// An error returned when [`apply`]ing a `Patch` fails
+///
+/// Along with which hunk failed to apply, the error describes why: the lines
+/// the hunk expected to find, whether it looks like the hunk has already been
+/// applied and the position in the image which came closest to matching.
+///
+/// [`apply`]: fn.apply.html
+pub struct ApplyError<T: ToOwned + ?Sized = str> {
+    hunk: usize,
+    old_range: Option<HunkRange>,
+    new_range: Option<HunkRange>,
+    pre_image: Vec<T::Owned>,
+    already_applied: bool,
+    closest_match: Option<ClosestMatch>,
+}
+
+impl<T: ToOwned + ?Sized> ApplyError<T> {
+    // A binary patch doesn't have hunks which could be partially matched
+    fn binary() -> Self {
+        Self {
+            hunk: 1,
+            old_range: None,
+            new_range: None,
+            pre_image: Vec::new(),
+            already_applied: false,
+            closest_match: None,
+        }
+    }
+
+    /// Returns the 1-based index of the hunk which failed to apply
+    pub fn hunk(&self) -> usize {
+        self.hunk
+    }
+
+    /// Returns the range of the old file covered by the failing hunk, or
+    /// `None` for binary patches
+    pub fn old_range(&self) -> Option<HunkRange> {
+        self.old_range
+    }
+
+    /// Returns the range of the new file covered by the failing hunk, or
+    /// `None` for binary patches
+    pub fn new_range(&self) -> Option<HunkRange> {
+        self.new_range
+    }
+
+    /// Returns the lines the failing hunk expected to find in the image,
+    /// i.e. its context and deleted lines
+    pub fn pre_image(&self) -> &[T::Owned] {
+        &self.pre_image
+    }
+
+    /// Returns `true` if the failing hunk's changes appear to already be
+    /// present in the image
+    pub fn is_already_applied(&self) -> bool {
+        self.already_applied
+    }
+
+    /// Returns the position in the image which came closest to matching the
+    /// failing hunk's pre-image, if any of its lines matched at all
+    pub fn closest_match(&self) -> Option<ClosestMatch> {
+        self.closest_match
+    }
+}
+
+impl<T: ?Sized, O> fmt::Debug for ApplyError<T>
+where
+    T: ToOwned<Owned = O>,
+    O: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApplyError")
+            .field("hunk", &self.hunk)
+            .field("old_range", &self.old_range)
+            .field("new_range", &self.new_range)
+            .field("pre_image", &self.pre_image)
+            .field("already_applied", &self.already_applied)
+            .field("closest_match", &self.closest_match)
+            .finish()
+    }
+}
+
+impl<T: ToOwned + ?Sized> fmt::Display for ApplyError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error applying hunk #{}", self.hunk)?;
+        if self.already_applied {
+            write!(f, " (it appears to already be applied)")?;
+        } else if let Some(closest) = self.closest_match {
+            write!(
+                f,
+                " (closest match at line {} with {} of {} lines matching)",
+                closest.line, closest.matching_lines, closest.len
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: ?Sized, O> std::error::Error for ApplyError<T>
+where
+    T: ToOwned<Owned = O>,
+    O: fmt::Debug,
+{
+}
+
+/// The position in an image which came closest to matching a hunk which
+/// failed to apply
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ClosestMatch {
+    line: usize,
+    matching_lines: usize,
+    len: usize,
+}
+
+impl ClosestMatch {
+    /// Returns the 1-based line in the image where the match starts
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the number of lines of the hunk's pre-image which matched
+    pub fn matching_lines(&self) -> usize {
+        self.matching_lines
+    }
+
+    /// Returns the fraction of the hunk's pre-image which matched, from 0 to 1
+    pub fn similarity(&self) -> f64 {
+        self.matching_lines as f64 / self.len as f64
+    }
+}
+
+#[derive(Debug)]
+enum ImageLine<'a, T: ?Sized> {
+    Unpatched(&'a T),
+    // A line of the image covered by a hunk
+    Patched(&'a T),
+    // A line inserted by a hunk
+    Inserted(&'a T),
+    // A line of the result of a 3-way merge, as an index into a separate list of merged lines as
+    // it isn't borrowed from the image or the patch
+    Merged(usize),
+}
+
+impl<'a, T: ?Sized> ImageLine<'a, T> {
+    fn unpatched(&self) -> Option<&'a T> {
+        match self {
+            ImageLine::Unpatched(inner) => Some(inner),
+            _ => None,
+        }
+    }
+}
+
+impl<T: ?Sized> Copy for ImageLine<'_, T> {}
+
+impl<T: ?Sized> Clone for ImageLine<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+/// A collection of options for modifying the way a patch is applied
+#[derive(Debug)]
+pub struct ApplyOptions {
+    fuzz: usize,
+    reverse: bool,
+    already_applied: AlreadyApplied,
+    whitespace: Whitespace,
+    ignore_line_endings: bool,
+    three_way: Option<MergeOptions>,
+    strip: usize,
+    dry_run: bool,
+}
+
+impl ApplyOptions {
+    /// Construct a new `ApplyOptions` with default settings
+    ///
+    /// ## Defaults
+    /// * fuzz = 0
+    /// * reverse = false
+    /// * already_applied = AlreadyApplied::Error
+    /// * whitespace = Whitespace::Exact
+    /// * ignore_line_endings = false
+    /// * three_way = None
+    /// * strip = 1
+    /// * dry_run = false
+    pub fn new() -> Self {
+        Self {
+            fuzz: 0,
+            reverse: false,
+            already_applied: AlreadyApplied::Error,
+            whitespace: Whitespace::Exact,
+            ignore_line_endings: false,
+            three_way: None,
+            strip: 1,
+            dry_run: false,
+        }
+    }
+
+    /// Set the maximum number of context lines which may be ignored at the
+    /// start and end of a hunk which doesn't match the base image exactly
+    ///
+    /// This mirrors GNU patch's `--fuzz`. A hunk is first matched with all of
+    /// its context, then with one line of context ignored at each end, then
+    /// two, and so on up to `fuzz` lines. The ignored context lines are left
+    /// untouched in the base image. At least one line of the hunk's pre-image
+    /// always needs to match.
+    pub fn set_fuzz(&mut self, fuzz: usize) -> &mut Self {
+        self.fuzz = fuzz;
+        self
+    }
+
+    /// Set whether the patch should be applied in reverse, undoing its
+    /// changes like GNU patch's `--reverse`
+    ///
+    /// This is the same as applying the patch returned by `Patch::reverse`.
+    pub fn set_reverse(&mut self, reverse: bool) -> &mut Self {
+        self.reverse = reverse;
+        self
+    }
+
+    /// Set how to handle a hunk which doesn't apply because its changes are
+    /// already present in the base image
+    ///
+    /// A hunk is considered to already be applied when its pre-image can't be
+    /// found but its post-image can, the same way GNU patch detects a
+    /// "Reversed (or previously applied) patch". Skipping such hunks makes
+    /// applying the same patch twice a no-op.
+    ///
+    /// ```
+    /// use diffy::{AlreadyApplied, ApplyOptions, Patch};
+    ///
+    /// let s = "\
+    /// --- a/orders
+    /// +++ b/orders
+    /// @@ -1,2 +1,3 @@
+    ///  Windrunners
+    /// +Skybreakers
+    ///  Dustbringers
+    /// ";
+    /// let patch = Patch::from_str(s).unwrap();
+    /// let patched = "Windrunners\nSkybreakers\nDustbringers\n";
+    ///
+    /// let err = ApplyOptions::new().apply(patched, &patch).unwrap_err();
+    /// assert!(err.is_already_applied());
+    ///
+    /// let applied = ApplyOptions::new()
+    ///     .set_already_applied(AlreadyApplied::Report)
+    ///     .apply(patched, &patch)
+    ///     .unwrap();
+    /// assert_eq!(applied.image(), patched);
+    /// assert!(applied.hunks()[0].is_already_applied());
+    /// ```
+    pub fn set_already_applied(&mut self, already_applied: AlreadyApplied) -> &mut Self {
+        self.already_applied = already_applied;
+        self
+    }
+
+    /// Set how whitespace is treated when matching a hunk's context and
+    /// deleted lines against the base image
+    ///
+    /// `Whitespace::IgnoreChange` is the equivalent of
+    /// `git apply --ignore-whitespace`. Context lines keep the whitespace they
+    /// have in the base image while inserted lines are taken from the patch
+    /// as they are.
+    pub fn set_whitespace(&mut self, whitespace: Whitespace) -> &mut Self {
+        self.whitespace = whitespace;
+        self
+    }
+
+    /// Set whether `\r\n` and `\n` line endings are considered the same
+    /// when matching a hunk against the base image
+    ///
+    /// This lets patches created against files with Windows line endings
+    /// apply to files with Unix line endings and the other way round. Lines
+    /// inserted by the patch are given the line ending used by most lines of
+    /// the base image, so the patched image keeps its own line endings.
+    ///
+    /// ```
+    /// use diffy::{ApplyOptions, Patch};
+    ///
+    /// let s = "--- a/file\n+++ b/file\n@@ -1,2 +1,2 @@\n one\n-two\n+2\n";
+    /// let patch = Patch::from_str(s).unwrap();
+    ///
+    /// let base_image = "one\r\ntwo\r\n";
+    /// ApplyOptions::new().apply(base_image, &patch).unwrap_err();
+    ///
+    /// let applied = ApplyOptions::new()
+    ///     .set_ignore_line_endings(true)
+    ///     .apply(base_image, &patch)
+    ///     .unwrap();
+    /// assert_eq!(applied.image(), "one\r\n2\r\n");
+    /// ```
+    pub fn set_ignore_line_endings(&mut self, ignore_line_endings: bool) -> &mut Self {
+        self.ignore_line_endings = ignore_line_endings;
+        self
+    }
+
+    /// Fall back to a 3-way merge for hunks which can't be applied, like
+    /// `git apply --3way`
+    ///
+    /// The hunk's pre-image is used as the common ancestor and its post-image
+    /// as their version, which is merged with our version of the lines in the
+    /// base image where the hunk came closest to matching. Instead of an
+    /// `ApplyError`, the patched image then contains the merged lines, with
+    /// conflict markers rendered according to `merge_options` if the hunk's
+    /// changes conflict with the base image.
+    ///
+    /// ```
+    /// use diffy::{ApplyOptions, ConflictStyle, MergeOptions, Patch};
+    ///
+    /// let s = "\
+    /// --- a/spren
+    /// +++ b/spren
+    /// @@ -1,3 +1,3 @@
+    ///  Syl
+    /// -Pattern
+    /// +Wyndle
+    ///  Ivory
+    /// ";
+    /// let patch = Patch::from_str(s).unwrap();
+    /// let base_image = "Syl\nGlys\nIvory\n";
+    ///
+    /// let mut merge_options = MergeOptions::new();
+    /// merge_options.set_conflict_style(ConflictStyle::Merge);
+    /// let applied = ApplyOptions::new()
+    ///     .set_three_way(merge_options)
+    ///     .apply(base_image, &patch)
+    ///     .unwrap();
+    ///
+    /// let expected = "\
+    /// Syl
+    /// <<<<<<< ours
+    /// Glys
+    /// =======
+    /// Wyndle
+    /// >>>>>>> theirs
+    /// Ivory
+    /// ";
+    /// assert_eq!(applied.image(), expected);
+    /// assert!(applied.hunks()[0].has_conflicts());
+    /// ```
+    pub fn set_three_way(&mut self, merge_options: MergeOptions) -> &mut Self {
+        self.three_way = Some(merge_options);
+        self
+    }
+
+    /// Set the number of leading path components to strip from the filenames
+    /// in a patch when applying a `PatchSet` to a `FileSystem`, like the `-p`
+    /// option of `git apply` and GNU patch
+    ///
+    /// The default of 1 strips the `a/` and `b/` prefixes used by git.
+    pub fn set_strip(&mut self, strip: usize) -> &mut Self {
+        self.strip = strip;
+        self
+    }
+
+    /// Set whether applying a `PatchSet` to a `FileSystem` should only check
+    /// that every patch applies, without writing any files
+    pub fn set_dry_run(&mut self, dry_run: bool) -> &mut Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Apply a `Patch` to a base image, returning the result along with a
+    /// report of how each hunk was applied
+    ///
+    /// ```
+    /// use diffy::{ApplyOptions, Patch};
+    ///
+    /// let s = "\
+    /// --- a/words
+    /// +++ b/words
+    /// @@ -1,3 +1,3 @@
+    ///  one
+    /// -two
+    /// +2
+    ///  three
+    /// ";
+    /// let patch = Patch::from_str(s).unwrap();
+    ///
+    /// // The trailing context doesn't match
+    /// let base_image = "one\ntwo\nTHREE\n";
+    /// ApplyOptions::new().apply(base_image, &patch).unwrap_err();
+    ///
+    /// let applied = ApplyOptions::new()
+    ///     .set_fuzz(1)
+    ///     .apply(base_image, &patch)
+    ///     .unwrap();
+    /// assert_eq!(applied.image(), "one\n2\nTHREE\n");
+    /// assert_eq!(applied.hunks()[0].fuzz(), 1);
+    /// ```
+    pub fn apply(
+        &self,
+        base_image: &str,
+        patch: &Patch<'_, str>,
+    ) -> Result<Applied<String>, ApplyError> {
+        let reversed;
+        let patch = if self.reverse {
+            reversed = patch.reverse();
+            &reversed
+        } else {
+            patch
+        };
+
+        if let Some(binary) = patch.binary() {
+            return binary
+                .apply(base_image.as_bytes())
+                .and_then(|image| String::from_utf8(image).ok())
+                .map(|image| Applied::new(image, Vec::new()))
+                .ok_or_else(ApplyError::binary);
+        }
+
+        let mut image: Vec<_> = LineIter::new(base_image)
+            .map(ImageLine::Unpatched)
+            .collect();
+
+        let mut merged = Vec::new();
+        let hunks = self.apply_hunks(&mut image, &mut merged, patch.hunks())?;
+
+        Ok(Applied::new(
+            String::from_utf8(self.join_image(base_image, image, &merged)).unwrap(),
+            hunks,
+        ))
+    }
+
+    /// Apply a non-utf8 `Patch` to a base image, returning the result along
+    /// with a report of how each hunk was applied
+    ///
+    /// This includes patches to binary files in git's `GIT binary patch` format.
+    pub fn apply_bytes(
+        &self,
+        base_image: &[u8],
+        patch: &Patch<'_, [u8]>,
+    ) -> Result<Applied<Vec<u8>>, ApplyError<[u8]>> {
+        let reversed;
+        let patch = if self.reverse {
+            reversed = patch.reverse();
+            &reversed
+        } else {
+            patch
+        };
+
+        if let Some(binary) = patch.binary() {
+            return binary
+                .apply(base_image)
+                .map(|image| Applied::new(image, Vec::new()))
+                .ok_or_else(ApplyError::binary);
+        }
+
+        let mut image: Vec<_> = LineIter::new(base_image)
+            .map(ImageLine::Unpatched)
+            .collect();
+
+        let mut merged = Vec::new();
+        let hunks = self.apply_hunks(&mut image, &mut merged, patch.hunks())?;
+
+        Ok(Applied::new(
+            self.join_image(base_image, image, &merged),
+            hunks,
+        ))
+    }
+
+    /// Apply as much of a `Patch` as possible to a base image, returning the
+    /// result along with the hunks which couldn't be applied
+    ///
+    /// Unlike [`apply`](ApplyOptions::apply), a hunk which fails to apply
+    /// doesn't stop the remaining hunks from being applied. Like GNU patch,
+    /// the rejected hunks are collected into a patch which can be written out
+    /// with `PatchFormatter` as a `.rej` file so that they can be fixed up by
+    /// hand.
+    ///
+    /// ```
+    /// use diffy::{ApplyOptions, Patch};
+    ///
+    /// let s = "\
+    /// --- a/heralds
+    /// +++ b/heralds
+    /// @@ -1,2 +1,2 @@
+    ///  Jezrien
+    /// -Nale
+    /// +Nalan
+    /// @@ -4,2 +4,2 @@
+    ///  Ishar
+    /// -Kelek
+    /// +Kalak
+    /// ";
+    /// let patch = Patch::from_str(s).unwrap();
+    ///
+    /// let base_image = "Jezrien\nNale\nChana\nIshi\nKelek\n";
+    /// let applied = ApplyOptions::new().apply_partial(base_image, &patch);
+    /// assert_eq!(applied.image(), "Jezrien\nNalan\nChana\nIshi\nKelek\n");
+    /// assert!(applied.hunks()[0].is_ok());
+    /// assert!(applied.hunks()[1].is_err());
+    ///
+    /// let rejects = "\
+    /// --- a/heralds
+    /// +++ b/heralds
+    /// @@ -4,2 +4,2 @@
+    ///  Ishar
+    /// -Kelek
+    /// +Kalak
+    /// ";
+    /// assert_eq!(applied.rejects().unwrap().to_string(), rejects);
+    /// ```
+    pub fn apply_partial<'a>(
+        &self,
+        base_image: &str,
+        patch: &Patch<'a, str>,
+    ) -> PartiallyApplied<'a, str> {
+        let reversed;
+        let patch = if self.reverse {
+            reversed = patch.reverse();
+            &reversed
+        } else {
+            patch
+        };
+
+        if let Some(binary) = patch.binary() {
+            return match binary
+                .apply(base_image.as_bytes())
+                .and_then(|image| String::from_utf8(image).ok())
+            {
+                Some(image) => PartiallyApplied::new(image, Vec::new(), None),
+                None => PartiallyApplied::new(
+                    base_image.to_owned(),
+                    vec![Err(ApplyError::binary())],
+                    Some(patch.clone()),
+                ),
+            };
+        }
+
+        let mut image: Vec<_> = LineIter::new(base_image)
+            .map(ImageLine::Unpatched)
+            .collect();
+
+        let mut merged = Vec::new();
+        let (hunks, rejects) = self.apply_hunks_partial(&mut image, &mut merged, patch);
+
+        PartiallyApplied::new(
+            String::from_utf8(self.join_image(base_image, image, &merged)).unwrap(),
+            hunks,
+            rejects,
+        )
+    }
+
+    /// Apply as much of a non-utf8 `Patch` as possible to a base image,
+    /// returning the result along with the hunks which couldn't be applied
+    ///
+    /// A binary patch is either applied in full or rejected as a whole.
+    pub fn apply_partial_bytes<'a>(
+        &self,
+        base_image: &[u8],
+        patch: &Patch<'a, [u8]>,
+    ) -> PartiallyApplied<'a, [u8]> {
+        let reversed;
+        let patch = if self.reverse {
+            reversed = patch.reverse();
+            &reversed
+        } else {
+            patch
+        };
+
+        if let Some(binary) = patch.binary() {
+            return match binary.apply(base_image) {
+                Some(image) => PartiallyApplied::new(image, Vec::new(), None),
+                None => PartiallyApplied::new(
+                    base_image.to_owned(),
+                    vec![Err(ApplyError::binary())],
+                    Some(patch.clone()),
+                ),
+            };
+        }
+
+        let mut image: Vec<_> = LineIter::new(base_image)
+            .map(ImageLine::Unpatched)
+            .collect();
+
+        let mut merged = Vec::new();
+        let (hunks, rejects) = self.apply_hunks_partial(&mut image, &mut merged, patch);
+
+        PartiallyApplied::new(self.join_image(base_image, image, &merged), hunks, rejects)
+    }
+
+    // Joins the lines of a patched image, giving inserted lines the line ending used by most lines
+    // of `base_image` when line endings are ignored. Only line endings are changed, so the result
+    // is valid utf8 if the image and the `merged` lines are.
+    fn join_image<T: Text + ?Sized>(
+        &self,
+        base_image: &T,
+        image: Vec<ImageLine<'_, T>>,
+        merged: &[Vec<u8>],
+    ) -> Vec<u8> {
+        let line_ending = if self.ignore_line_endings {
+            Some(line_ending(base_image.as_bytes()))
+        } else {
+            None
+        };
+
+        let mut joined = Vec::with_capacity(base_image.len());
+        for line in image {
+            match (line, line_ending) {
+                (ImageLine::Inserted(line), Some(line_ending)) => {
+                    let body = strip_line_ending(line.as_bytes());
+                    joined.extend_from_slice(body);
+                    if body.len() != line.len() {
+                        joined.extend_from_slice(line_ending);
+                    }
+                }
+                (ImageLine::Unpatched(line), _)
+                | (ImageLine::Patched(line), _)
+                | (ImageLine::Inserted(line), _) => joined.extend_from_slice(line.as_bytes()),
+                (ImageLine::Merged(i), _) => joined.extend_from_slice(&merged[i]),
+            }
+        }
+        joined
+    }
+
+    // Compares a line of a hunk with a line of the image
+    fn lines_eq<T: Text + ?Sized>(&self, a: &T, b: &T) -> bool {
+        let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+        if self.ignore_line_endings {
+            a = strip_line_ending(a);
+            b = strip_line_ending(b);
+        }
+        utils::whitespace_eq(a, b, self.whitespace)
+    }
+
+    fn apply_hunks<'a, T: MergeText + ?Sized>(
+        &self,
+        image: &mut Vec<ImageLine<'a, T>>,
+        merged: &mut Vec<Vec<u8>>,
+        hunks: &'a [Hunk<'_, T>],
+    ) -> Result<Vec<HunkReport>, ApplyError<T>> {
+        hunks
+            .iter()
+            .enumerate()
+            .map(|(i, hunk)| {
+                apply_hunk(image, merged, hunk, self)
+                    .ok_or_else(|| hunk_error(image, hunk, i + 1, self))
+            })
+            .collect()
+    }
+
+    // Applies each hunk which can be applied, collecting the rest into a patch of rejects
+    fn apply_hunks_partial<'a, 'b, T: MergeText + ?Sized>(
+        &self,
+        image: &mut Vec<ImageLine<'b, T>>,
+        merged: &mut Vec<Vec<u8>>,
+        patch: &'b Patch<'a, T>,
+    ) -> (HunkResults<T>, Option<Patch<'a, T>>) {
+        let mut hunks = Vec::new();
+        let mut rejected = Vec::new();
+        for (i, hunk) in patch.hunks().iter().enumerate() {
+            match apply_hunk(image, merged, hunk, self) {
+                Some(report) => hunks.push(Ok(report)),
+                None => {
+                    hunks.push(Err(hunk_error(image, hunk, i + 1, self)));
+                    rejected.push(hunk.clone());
+                }
+            }
+        }
+
+        let rejects = if rejected.is_empty() {
+            None
+        } else {
+            Some(patch.with_hunks(rejected))
+        };
+        (hunks, rejects)
+    }
+}
+
+impl Default for ApplyOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How to handle hunks whose changes are already present in the base image
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AlreadyApplied {
+    /// Fail to apply the hunk, with an `ApplyError` whose
+    /// `is_already_applied` returns `true`
+    Error,
+    /// Skip the hunk, marking its `HunkReport` as already applied
+    Report,
+    /// Skip the hunk, reporting it as if it had been applied
+    Skip,
+}
+
+/// The result of successfully applying a patch with [`ApplyOptions`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Applied<T> {
+    image: T,
+    hunks: Vec<HunkReport>,
+}
+
+impl<T> Applied<T> {
+    fn new(image: T, hunks: Vec<HunkReport>) -> Self {
+        Self { image, hunks }
+    }
+
+    /// Returns the patched image
+    pub fn image(&self) -> &T {
+        &self.image
+    }
+
+    /// Consumes the result, returning the patched image
+    pub fn into_image(self) -> T {
+        self.image
+    }
+
+    /// Returns a report for each of the patch's hunks, in order
+    pub fn hunks(&self) -> &[HunkReport] {
+        &self.hunks
+    }
+}
+
+// The outcome of each hunk of a partially applied patch
+type HunkResults<T> = Vec<Result<HunkReport, ApplyError<T>>>;
+
+/// The result of applying as much of a patch as possible with
+/// [`ApplyOptions::apply_partial`]
+pub struct PartiallyApplied<'a, T: ToOwned + ?Sized> {
+    image: T::Owned,
+    hunks: HunkResults<T>,
+    rejects: Option<Patch<'a, T>>,
+}
+
+impl<'a, T: ToOwned + ?Sized> PartiallyApplied<'a, T> {
+    fn new(image: T::Owned, hunks: HunkResults<T>, rejects: Option<Patch<'a, T>>) -> Self {
+        Self {
+            image,
+            hunks,
+            rejects,
+        }
+    }
+
+    /// Returns the image with every hunk which could be applied applied
+    pub fn image(&self) -> &T::Owned {
+        &self.image
+    }
+
+    /// Consumes the result, returning the patched image
+    pub fn into_image(self) -> T::Owned {
+        self.image
+    }
+
+    /// Returns a report for each of the patch's hunks, in order, or the error
+    /// describing why it couldn't be applied
+    pub fn hunks(&self) -> &[Result<HunkReport, ApplyError<T>>] {
+        &self.hunks
+    }
+
+    /// Returns a patch holding the hunks which couldn't be applied, or `None`
+    /// if the whole patch was applied
+    pub fn rejects(&self) -> Option<&Patch<'a, T>> {
+        self.rejects.as_ref()
+    }
+
+    /// Consumes the result, returning the patched image along with the patch
+    /// of rejected hunks
+    pub fn into_parts(self) -> (T::Owned, Option<Patch<'a, T>>) {
+        (self.image, self.rejects)
+    }
+}
+
+impl<T: ?Sized, O> fmt::Debug for PartiallyApplied<'_, T>
+where
+    T: ToOwned<Owned = O> + fmt::Debug,
+    O: std::borrow::Borrow<T> + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PartiallyApplied")
+            .field("image", &self.image)
+            .field("hunks", &self.hunks)
+            .field("rejects", &self.rejects)
+            .finish()
+    }
+}
+
+/// A report of how a single hunk was applied
+///
+/// This carries the same information as GNU patch's
+/// `Hunk #2 succeeded at 140 with fuzz 1 (offset 12 lines).`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HunkReport {
+    line: usize,
+    offset: isize,
+    fuzz: usize,
+    already_applied: bool,
+    merged: bool,
+    conflicts: bool,
+}
+
+impl HunkReport {
+    /// Returns the line in the patched image where the hunk was applied
+    ///
+    /// Like the hunk's `new_range().start()`, this is the 1-based line where
+    /// the hunk's post-image starts, or the line before it if the hunk only
+    /// deletes lines.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the number of lines between where the hunk was applied and
+    /// where its `new_range().start()` said it would be
+    pub fn offset(&self) -> isize {
+        self.offset
+    }
+
+    /// Returns the number of context lines which were ignored at the start
+    /// and end of the hunk in order to apply it, 0 if it applied exactly
+    pub fn fuzz(&self) -> usize {
+        self.fuzz
+    }
+
+    /// Returns `true` if the hunk was skipped because its changes were
+    /// already present, when reporting this was requested with
+    /// [`AlreadyApplied::Report`]
+    pub fn is_already_applied(&self) -> bool {
+        self.already_applied
+    }
+
+    /// Returns `true` if the hunk couldn't be applied and was instead merged
+    /// into the image, see [`ApplyOptions::set_three_way`]
+    pub fn is_merged(&self) -> bool {
+        self.merged
+    }
+
+    /// Returns `true` if merging the hunk into the image resulted in conflicts
+    pub fn has_conflicts(&self) -> bool {
+        self.conflicts
+    }
+}
+
+/// Apply a `Patch` to a base image
+///
+/// ```
+/// use diffy::{apply, Patch};
+///
+/// let s = "\
+/// --- a/ideals
+/// +++ b/ideals
+/// @@ -1,4 +1,6 @@
+///  First:
+///      Life before death,
+///      strength before weakness,
+///      journey before destination.
+/// +Second:
+/// +    I will protect those who cannot protect themselves.
+/// ";
+///
+/// let patch = Patch::from_str(s).unwrap();
+///
+/// let base_image = "\
+/// First:
+///     Life before death,
+///     strength before weakness,
+///     journey before destination.
+/// ";
+///
+/// let expected = "\
+/// First:
+///     Life before death,
+///     strength before weakness,
+///     journey before destination.
+/// Second:
+///     I will protect those who cannot protect themselves.
+/// ";
+///
+/// assert_eq!(apply(base_image, &patch).unwrap(), expected);
+/// ```
+pub fn apply(base_image: &str, patch: &Patch<'_, str>) -> Result<String, ApplyError> {
+    ApplyOptions::new()
+        .apply(base_image, patch)
+        .map(Applied::into_image)
+}
+
+/// Apply a non-utf8 `Patch` to a base image
+///
+/// This includes patches to binary files in git's `GIT binary patch` format.
+pub fn apply_bytes(
+    base_image: &[u8],
+    patch: &Patch<'_, [u8]>,
+) -> Result<Vec<u8>, ApplyError<[u8]>> {
+    ApplyOptions::new()
+        .apply_bytes(base_image, patch)
+        .map(Applied::into_image)
+}
+
+/// Apply a `Patch` in reverse, undoing its changes to an image
+///
+/// ```
+/// use diffy::{create_patch, unapply};
+///
+/// let original = "Kaladin\nShallan\n";
+/// let modified = "Kaladin\nShallan\nDalinar\n";
+/// let patch = create_patch(original, modified);
+///
+/// assert_eq!(unapply(modified, &patch).unwrap(), original);
+/// ```
+pub fn unapply(image: &str, patch: &Patch<'_, str>) -> Result<String, ApplyError> {
+    ApplyOptions::new()
+        .set_reverse(true)
+        .apply(image, patch)
+        .map(Applied::into_image)
+}
+
+/// Apply a non-utf8 `Patch` in reverse, undoing its changes to an image
+///
+/// Binary patches can only be applied in reverse if they include the hunk
+/// turning the new file back into the old file.
+pub fn unapply_bytes(image: &[u8], patch: &Patch<'_, [u8]>) -> Result<Vec<u8>, ApplyError<[u8]>> {
+    ApplyOptions::new()
+        .set_reverse(true)
+        .apply_bytes(image, patch)
+        .map(Applied::into_image)
+}
+
+// Applies `hunk` to `image`, ignoring up to `options.fuzz` lines of context at either end of the
+// hunk if it doesn't match exactly
+fn apply_hunk<'a, T: MergeText + ?Sized>(
+    image: &mut Vec<ImageLine<'a, T>>,
+    merged: &mut Vec<Vec<u8>>,
+    hunk: &'a Hunk<'_, T>,
+    options: &ApplyOptions,
+) -> Option<HunkReport> {
+    if let Some(found) = find_hunk(image, hunk.lines(), hunk.new_range(), options) {
+        let range = found.pos..found.pos + pre_image_line_count(found.lines);
+        let mut replaced = image[range.clone()].iter().filter_map(ImageLine::unpatched);
+        let patched: Vec<_> = found
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                // Context lines keep their contents from the image, which may differ from the
+                // hunk's when whitespace or line endings are ignored
+                Line::Context(_) => replaced.next().map(ImageLine::Patched),
+                Line::Delete(_) => {
+                    replaced.next();
+                    None
+                }
+                Line::Insert(_) => Some(ImageLine::Inserted(line.inner())),
+            })
+            .collect();
+        image.splice(range, patched);
+        return Some(hunk_report(&found, hunk.new_range(), false));
+    }
+
+    if options.already_applied != AlreadyApplied::Error {
+        // Skip over a hunk whose changes are already present, marking them as patched so that
+        // later hunks can't be applied on top of them
+        let reversed = reverse_lines(hunk.lines());
+        if let Some(found) = find_already_applied(image, hunk, &reversed, options) {
+            for line in &mut image[found.pos..found.pos + pre_image_line_count(found.lines)] {
+                if let ImageLine::Unpatched(inner) = *line {
+                    *line = ImageLine::Patched(inner);
+                }
+            }
+            return Some(hunk_report(
+                &found,
+                hunk.new_range(),
+                options.already_applied == AlreadyApplied::Report,
+            ));
+        }
+    }
+
+    let merge_options = options.three_way.as_ref()?;
+    Some(merge_hunk(image, merged, hunk, options, merge_options))
+}
+
+// Merges the changes of a hunk which couldn't be applied into the part of `image` where it came
+// closest to matching, using the hunk's pre-image as the common ancestor
+fn merge_hunk<'a, T: MergeText + ?Sized>(
+    image: &mut Vec<ImageLine<'a, T>>,
+    merged: &mut Vec<Vec<u8>>,
+    hunk: &Hunk<'_, T>,
+    options: &ApplyOptions,
+    merge_options: &MergeOptions,
+) -> HunkReport {
+    let pos = match closest_match(image, hunk, options) {
+        Some(closest) => closest.line - 1,
+        None => cmp::min(expected_position(hunk.new_range()), image.len()),
+    };
+    // Our version of the lines stops short of any lines which were already patched
+    let ours: Vec<_> = image[pos..]
+        .iter()
+        .take(pre_image_line_count(hunk.lines()))
+        .map(ImageLine::unpatched)
+        .take_while(Option::is_some)
+        .flatten()
+        .collect();
+    let ancestor: Vec<_> = pre_image(hunk.lines()).collect();
+    let theirs: Vec<_> = post_image(hunk.lines()).collect();
+
+    let result = T::merge(
+        merge_options,
+        &T::concat(&ancestor),
+        &T::concat(&ours),
+        &T::concat(&theirs),
+    );
+    let conflicts = result.is_err();
+    let result = result.unwrap_or_else(|conflicted| conflicted);
+
+    let start = merged.len();
+    merged.extend(LineIter::new(&result[..]).map(<[u8]>::to_vec));
+    image.splice(
+        pos..pos + ours.len(),
+        (start..merged.len()).map(ImageLine::Merged),
+    );
+
+    let line = if hunk.new_range().is_empty() {
+        pos
+    } else {
+        pos + 1
+    };
+    HunkReport {
+        line,
+        offset: line as isize - hunk.new_range().start() as isize,
+        fuzz: 0,
+        already_applied: false,
+        merged: true,
+        conflicts,
+    }
+}
+
+// Text which can be 3-way merged
+trait MergeText: Text {
+    fn merge(
+        options: &MergeOptions,
+        ancestor: &Self,
+        ours: &Self,
+        theirs: &Self,
+    ) -> Result<Vec<u8>, Vec<u8>>;
+}
+
+impl MergeText for str {
+    fn merge(
+        options: &MergeOptions,
+        ancestor: &Self,
+        ours: &Self,
+        theirs: &Self,
+    ) -> Result<Vec<u8>, Vec<u8>> {
+        options
+            .merge(ancestor, ours, theirs)
+            .map(String::into_bytes)
+            .map_err(String::into_bytes)
+    }
+}
+
+impl MergeText for [u8] {
+    fn merge(
+        options: &MergeOptions,
+        ancestor: &Self,
+        ours: &Self,
+        theirs: &Self,
+    ) -> Result<Vec<u8>, Vec<u8>> {
+        options.merge_bytes(ancestor, ours, theirs)
+    }
+}
+
+// Reports where the whole hunk covering `range`, including any ignored leading context, ended up
+fn hunk_report<T: ToOwned + ?Sized>(
+    found: &FoundHunk<'_, '_, T>,
+    range: HunkRange,
+    already_applied: bool,
+) -> HunkReport {
+    let hunk_pos = found.pos.saturating_sub(found.leading);
+    let line = if range.is_empty() {
+        hunk_pos
+    } else {
+        hunk_pos + 1
+    };
+    HunkReport {
+        line,
+        offset: line as isize - range.start() as isize,
+        fuzz: found.fuzz,
+        already_applied,
+        merged: false,
+        conflicts: false,
+    }
+}
+
+// Where a hunk was found in an image
+struct FoundHunk<'a, 'b, T: ToOwned + ?Sized> {
+    // The position in the image where `lines` match
+    pos: usize,
+    fuzz: usize,
+    // The number of leading context lines which were ignored
+    leading: usize,
+    // The lines of the hunk left after ignoring context
+    lines: &'b [Line<'a, T>],
+}
+
+// Finds where in `image` the hunk made up of `lines` applies, ignoring up to `options.fuzz` lines
+// of context at either end of the hunk if it doesn't match exactly
+fn find_hunk<'a, 'b, T: Text + ?Sized>(
+    image: &[ImageLine<'_, T>],
+    lines: &'b [Line<'a, T>],
+    range: HunkRange,
+    options: &ApplyOptions,
+) -> Option<FoundHunk<'a, 'b, T>> {
+    let is_context = |line: &&Line<'_, T>| matches!(line, Line::Context(_));
+    let leading_context = lines.iter().take_while(is_context).count();
+    let trailing_context = lines.iter().rev().take_while(is_context).count();
+    let has_pre_image = pre_image_line_count(lines) > 0;
+
+    let mut previous = None;
+    for fuzz in 0..=options.fuzz {
+        let leading = cmp::min(fuzz, leading_context);
+        let trailing = cmp::min(fuzz, trailing_context);
+        // Stop once there's no more context to ignore
+        if previous == Some((leading, trailing)) || leading + trailing > lines.len() {
+            break;
+        }
+        previous = Some((leading, trailing));
+
+        let fuzzed = &lines[leading..lines.len() - trailing];
+        if has_pre_image && pre_image_line_count(fuzzed) == 0 {
+            break;
+        }
+
+        if let Some(pos) = find_position(image, fuzzed, expected_position(range) + leading, options)
+        {
+            return Some(FoundHunk {
+                pos,
+                fuzz,
+                leading,
+                lines: fuzzed,
+            });
+        }
+    }
+
+    None
+}
+
+// The 0-based position in the image where a hunk covering `range` is expected to apply. An empty
+// range starts at the line before the hunk.
+fn expected_position(range: HunkRange) -> usize {
+    if range.is_empty() {
+        range.start()
+    } else {
+        range.start().saturating_sub(1)
+    }
+}
+
+// Builds the error for a hunk which failed to apply, working out whether it has already been
+// applied and where in the image it came closest to matching
+fn hunk_error<T: Text + ?Sized>(
+    image: &[ImageLine<'_, T>],
+    hunk: &Hunk<'_, T>,
+    index: usize,
+    options: &ApplyOptions,
+) -> ApplyError<T> {
+    let reversed = reverse_lines(hunk.lines());
+    let already_applied = find_already_applied(image, hunk, &reversed, options).is_some();
+
+    ApplyError {
+        hunk: index,
+        old_range: Some(hunk.old_range()),
+        new_range: Some(hunk.new_range()),
+        pre_image: pre_image(hunk.lines()).map(ToOwned::to_owned).collect(),
+        already_applied,
+        closest_match: closest_match(image, hunk, options),
+    }
+}
+
+// Finds where in `image` the changes of `hunk` are already present, i.e. where its post-image can
+// be found, given the `reversed` lines of the hunk
+fn find_already_applied<'a, 'b, T: Text + ?Sized>(
+    image: &[ImageLine<'_, T>],
+    hunk: &Hunk<'_, T>,
+    reversed: &'b [Line<'a, T>],
+    options: &ApplyOptions,
+) -> Option<FoundHunk<'a, 'b, T>> {
+    // A hunk which only deletes lines would trivially be found
+    post_image(hunk.lines()).next()?;
+    find_hunk(image, reversed, hunk.new_range(), options)
+}
+
+// Returns the lines of a hunk which undoes `lines`, borrowing their text
+fn reverse_lines<'a, T: ToOwned + ?Sized>(lines: &'a [Line<'_, T>]) -> Vec<Line<'a, T>> {
+    lines
+        .iter()
+        .map(|line| {
+            let l = Cow::Borrowed(line.inner());
+            match line {
+                Line::Context(_) => Line::Context(l),
+                Line::Delete(_) => Line::Insert(l),
+                Line::Insert(_) => Line::Delete(l),
+            }
+        })
+        .collect()
+}
+
+// Finds the position in `image` where the most lines of the hunk's pre-image match, preferring
+// positions closer to where the hunk is expected to apply
+fn closest_match<T: Text + ?Sized>(
+    image: &[ImageLine<'_, T>],
+    hunk: &Hunk<'_, T>,
+    options: &ApplyOptions,
+) -> Option<ClosestMatch> {
+    let pre_image: Vec<_> = pre_image(hunk.lines()).collect();
+    let expected = expected_position(hunk.new_range());
+
+    // (matching lines, distance from the expected position, position)
+    let mut best: Option<(usize, usize, usize)> = None;
+    for pos in 0..image.len() {
+        let matching_lines = image[pos..]
+            .iter()
+            .zip(&pre_image)
+            .filter(|(image_line, line)| {
+                image_line
+                    .unpatched()
+                    .map_or(false, |image_line| options.lines_eq(image_line, *line))
+            })
+            .count();
+        let distance = cmp::max(pos, expected) - cmp::min(pos, expected);
+        let is_better = match best {
+            Some((best_matching, best_distance, _)) => {
+                matching_lines > best_matching
+                    || (matching_lines == best_matching && distance < best_distance)
+            }
+            None => matching_lines > 0,
+        };
+        if is_better {
+            best = Some((matching_lines, distance, pos));
+        }
+    }
+
+    best.map(|(matching_lines, _, pos)| ClosestMatch {
+        line: pos + 1,
+        matching_lines,
+        len: pre_image.len(),
+    })
+}
+
+// Search in `image` for a palce to apply the hunk `lines`, starting from `start`.
+// This follows the general algorithm described in GNU patch's man page, with fuzzy-matching of
+// context lines handled by `apply_hunk`.
+//
+// It might be worth looking into other possible positions to apply the hunk to as described here:
+// https://neil.fraser.name/writing/patch/
+fn find_position<T: Text + ?Sized>(
+    image: &[ImageLine<T>],
+    lines: &[Line<'_, T>],
+    start: usize,
+    options: &ApplyOptions,
+) -> Option<usize> {
+    // In order to avoid searching through positions which are out of bounds of the image,
+    // clamp the starting position based on the length of the image
+    let pos = cmp::min(start, image.len());
+
+    // Create an iterator that starts with 'pos' and then interleaves
+    // moving pos backward/foward by one.
+    let backward = (0..pos).rev();
+    let forward = pos + 1..image.len();
+    iter::once(pos)
+        .chain(interleave(backward, forward))
+        .find(|&pos| match_fragment(image, lines, pos, options))
+}
+
+fn pre_image_line_count<T: ToOwned + ?Sized>(lines: &[Line<'_, T>]) -> usize {
+    pre_image(lines).count()
+}
+
+fn post_image<'a, T: ToOwned + ?Sized>(lines: &'a [Line<'a, T>]) -> impl Iterator<Item = &'a T> {
+    lines.iter().filter_map(|line| match line {
+        Line::Context(l) | Line::Insert(l) => Some(&**l),
+        Line::Delete(_) => None,
+    })
+}
+
+fn pre_image<'a, T: ToOwned + ?Sized>(lines: &'a [Line<'a, T>]) -> impl Iterator<Item = &'a T> {
+    lines.iter().filter_map(|line| match line {
+        Line::Context(l) | Line::Delete(l) => Some(&**l),
+        Line::Insert(_) => None,
+    })
+}
+
+fn match_fragment<T: Text + ?Sized>(
+    image: &[ImageLine<T>],
+    lines: &[Line<'_, T>],
+    pos: usize,
+    options: &ApplyOptions,
+) -> bool {
+    let len = pre_image_line_count(lines);
+
+    let image = if let Some(image) = image.get(pos..pos + len) {
+        image
+    } else {
+        return false;
+    };
+
+    // If any of these lines have already been patched then we can't match at this position
+    pre_image(lines).zip(image).all(|(line, image_line)| {
+        image_line
+            .unpatched()
+            .map_or(false, |image_line| options.lines_eq(line, image_line))
+    })
+}
+
+// Returns the line ending used by most lines of `text`
+fn line_ending(text: &[u8]) -> &'static [u8] {
+    let newlines = text.iter().filter(|&&b| b == b'\n').count();
+    let crlfs = text.windows(2).filter(|pair| pair == b"\r\n").count();
+    if crlfs > newlines - crlfs {
+        b"\r\n"
+    } else {
+        b"\n"
+    }
+}
+
+fn strip_line_ending(line: &[u8]) -> &[u8] {
+    line.strip_suffix(b"\r\n")
+        .or_else(|| line.strip_suffix(b"\n"))
+        .unwrap_or(line)
+}
+
+#[derive(Debug)]
+struct Interleave<I, J> {
+    a: iter::Fuse<I>,
+    b: iter::Fuse<J>,
+    flag: bool,
+}
+
+fn interleave<I, J>(
+    i: I,
+    j: J,
+) -> Interleave<<I as IntoIterator>::IntoIter, <J as IntoIterator>::IntoIter>
+where
+    I: IntoIterator,
+    J: IntoIterator<Item = I::Item>,
+{
+    Interleave {
+        a: i.into_iter().fuse(),
+        b: j.into_iter().fuse(),
+        flag: false,
+    }
+}
+
+impl<I, J> Iterator for Interleave<I, J>
+where
+    I: Iterator,
+    J: Iterator<Item = I::Item>,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        self.flag = !self.flag;
+        if self.flag {
+            match self.a.next() {
+                None => self.b.next(),
+                item => item,
+            }
+        } else {
+            match self.b.next() {
+                None => self.a.next(),
+                item => item,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATCH: &str = "\
+--- a/file
++++ b/file
+@@ -2,7 +2,7 @@
+ b
+ c
+ d
+-e
++E
+ f
+ g
+ h
+";
+
+    #[test]
+    fn fuzz() {
+        let patch = Patch::from_str(PATCH).unwrap();
+        let exact = "a\nb\nc\nd\ne\nf\ng\nh\ni\n";
+        let fuzzy = "a\nB\nC\nd\ne\nf\ng\nH\ni\n";
+
+        let applied = ApplyOptions::new().apply(exact, &patch).unwrap();
+        assert_eq!(applied.image(), "a\nb\nc\nd\nE\nf\ng\nh\ni\n");
+        assert_eq!(
+            applied.hunks(),
+            [HunkReport {
+                line: 2,
+                offset: 0,
+                fuzz: 0,
+                already_applied: false,
+                merged: false,
+                conflicts: false,
+            }]
+        );
+
+        apply(fuzzy, &patch).unwrap_err();
+        ApplyOptions::new()
+            .set_fuzz(1)
+            .apply(fuzzy, &patch)
+            .unwrap_err();
+
+        // Ignored context is left as is
+        for max_fuzz in 2..5 {
+            let applied = ApplyOptions::new()
+                .set_fuzz(max_fuzz)
+                .apply(fuzzy, &patch)
+                .unwrap();
+            assert_eq!(applied.image(), "a\nB\nC\nd\nE\nf\ng\nH\ni\n");
+            assert_eq!(applied.hunks()[0].fuzz(), 2);
+
+            let applied = ApplyOptions::new()
+                .set_fuzz(max_fuzz)
+                .apply_bytes(
+                    fuzzy.as_bytes(),
+                    &Patch::from_bytes(PATCH.as_bytes()).unwrap(),
+                )
+                .unwrap();
+            assert_eq!(applied.image(), b"a\nB\nC\nd\nE\nf\ng\nH\ni\n");
+            assert_eq!(applied.hunks()[0].fuzz(), 2);
+        }
+    }
+
+    #[test]
+    fn report_offset() {
+        let patch = Patch::from_str(PATCH).unwrap();
+        let applied = ApplyOptions::new()
+            .apply("x\ny\nz\na\nb\nc\nd\ne\nf\ng\nh\ni\n", &patch)
+            .unwrap();
+        assert_eq!(applied.hunks()[0].line(), 5);
+        assert_eq!(applied.hunks()[0].offset(), 3);
+
+        let applied = ApplyOptions::new()
+            .set_fuzz(2)
+            .apply("c\nd\ne\nf\ng\nh\ni\n", &patch)
+            .unwrap();
+        assert_eq!(applied.image(), "c\nd\nE\nf\ng\nh\ni\n");
+        // The ignored context would have started before the first line
+        assert_eq!(applied.hunks()[0].line(), 1);
+        assert_eq!(applied.hunks()[0].offset(), -1);
+    }
+
+    #[test]
+    fn error_diagnostics() {
+        let patch = Patch::from_str(PATCH).unwrap();
+
+        let err = apply("a\nb\nc\nd\nX\nf\ng\nh\ni\n", &patch).unwrap_err();
+        assert_eq!(err.hunk(), 1);
+        assert_eq!(err.old_range(), Some(patch.hunks()[0].old_range()));
+        assert_eq!(
+            err.pre_image(),
+            ["b\n", "c\n", "d\n", "e\n", "f\n", "g\n", "h\n"]
+        );
+        assert!(!err.is_already_applied());
+        let closest = err.closest_match().unwrap();
+        assert_eq!(closest.line(), 2);
+        assert_eq!(closest.matching_lines(), 6);
+        assert!((closest.similarity() - 6.0 / 7.0).abs() < f64::EPSILON);
+        assert_eq!(
+            err.to_string(),
+            "error applying hunk #1 (closest match at line 2 with 6 of 7 lines matching)"
+        );
+
+        let err = apply("a\nb\nc\nd\nE\nf\ng\nh\ni\n", &patch).unwrap_err();
+        assert!(err.is_already_applied());
+        assert_eq!(
+            err.to_string(),
+            "error applying hunk #1 (it appears to already be applied)"
+        );
+
+        let err = apply("1\n2\n", &patch).unwrap_err();
+        assert_eq!(err.closest_match(), None);
+        assert_eq!(err.to_string(), "error applying hunk #1");
+    }
+
+    #[test]
+    fn reverse() {
+        let patch = Patch::from_str(PATCH).unwrap();
+        let original = "a\nb\nc\nd\ne\nf\ng\nh\ni\n";
+        let modified = "a\nb\nc\nd\nE\nf\ng\nh\ni\n";
+
+        assert_eq!(unapply(modified, &patch).unwrap(), original);
+        assert_eq!(
+            unapply_bytes(
+                modified.as_bytes(),
+                &Patch::from_bytes(PATCH.as_bytes()).unwrap()
+            )
+            .unwrap(),
+            original.as_bytes()
+        );
+        let err = unapply(original, &patch).unwrap_err();
+        assert!(err.is_already_applied());
+
+        let applied = ApplyOptions::new()
+            .set_reverse(true)
+            .set_fuzz(2)
+            .apply("a\nB\nC\nd\nE\nf\ng\nH\ni\n", &patch)
+            .unwrap();
+        assert_eq!(applied.image(), "a\nB\nC\nd\ne\nf\ng\nH\ni\n");
+        assert_eq!(applied.hunks()[0].fuzz(), 2);
+
+        // Deletions are kept ahead of insertions when a hunk is reversed
+        let patch = Patch::from_str("--- a\n+++ b\n@@ -1,2 +1,3 @@\n-a\n-b\n+A\n+B\n+C\n").unwrap();
+        assert_eq!(
+            patch.reverse().to_string(),
+            "--- b\n+++ a\n@@ -1,3 +1,2 @@\n-A\n-B\n-C\n+a\n+b\n"
+        );
+    }
+
+    #[test]
+    fn partial() {
+        let s = "\
+--- a/file
++++ b/file
+@@ -1,3 +1,3 @@
+ a
+-b
++B
+ c
+@@ -5,3 +5,3 @@
+ e
+-f
++F
+ g
+@@ -8,2 +8,2 @@
+ h
+-i
++I
+";
+        let patch = Patch::from_str(s).unwrap();
+
+        let applied = ApplyOptions::new().apply_partial("a\nb\nc\nd\ne\nX\ng\nh\ni\n", &patch);
+        assert_eq!(applied.image(), "a\nB\nc\nd\ne\nX\ng\nh\nI\n");
+        let errors: Vec<_> = applied
+            .hunks()
+            .iter()
+            .filter_map(|hunk| hunk.as_ref().err())
+            .map(ApplyError::hunk)
+            .collect();
+        assert_eq!(errors, [2]);
+        let rejects = applied.rejects().unwrap();
+        assert_eq!(rejects.hunks(), &patch.hunks()[1..2]);
+        assert_eq!(rejects.original(), Some("a/file"));
+
+        let applied = ApplyOptions::new().apply_partial_bytes(
+            b"a\nb\nc\nd\ne\nf\ng\nh\ni\n",
+            &Patch::from_bytes(s.as_bytes()).unwrap(),
+        );
+        assert!(applied.hunks().iter().all(Result::is_ok));
+        let (image, rejects) = applied.into_parts();
+        assert_eq!(image, b"a\nB\nc\nd\ne\nF\ng\nh\nI\n");
+        assert!(rejects.is_none());
+
+        // Rejects of a reversed patch are reversed as well
+        let applied = ApplyOptions::new()
+            .set_reverse(true)
+            .apply_partial("a\nB\nc\nd\ne\nF\ng\nh\ni\n", &patch);
+        assert_eq!(applied.image(), "a\nb\nc\nd\ne\nf\ng\nh\ni\n");
+        assert_eq!(
+            applied.rejects().unwrap().hunks(),
+            &patch.reverse().hunks()[2..]
+        );
+    }
+
+    #[test]
+    fn already_applied() {
+        let s = "\
+--- a/file
++++ b/file
+@@ -1,3 +1,3 @@
+ a
+-b
++B
+ c
+@@ -5,3 +5,3 @@
+ e
+-f
++F
+ g
+";
+        let patch = Patch::from_str(s).unwrap();
+        let original = "a\nb\nc\nd\ne\nf\ng\n";
+        let modified = "a\nB\nc\nd\ne\nF\ng\n";
+
+        let err = apply("a\nB\nc\nd\ne\nf\ng\n", &patch).unwrap_err();
+        assert_eq!(err.hunk(), 1);
+        assert!(err.is_already_applied());
+
+        let mut options = ApplyOptions::new();
+        options.set_already_applied(AlreadyApplied::Skip);
+        let applied = options.apply("a\nB\nc\nd\ne\nf\ng\n", &patch).unwrap();
+        assert_eq!(applied.image(), modified);
+        assert!(applied
+            .hunks()
+            .iter()
+            .all(|hunk| !hunk.is_already_applied()));
+
+        // Applying the patch again is a no-op
+        let applied = options.apply(applied.image(), &patch).unwrap();
+        assert_eq!(applied.image(), modified);
+        assert_eq!(applied.hunks()[1].line(), 5);
+
+        options.set_already_applied(AlreadyApplied::Report);
+        let applied = options.apply(modified, &patch).unwrap();
+        assert!(applied.hunks().iter().all(HunkReport::is_already_applied));
+        let applied = options.apply(original, &patch).unwrap();
+        assert!(!applied.hunks()[0].is_already_applied());
+
+        // A hunk which neither applies nor is already applied still fails
+        let err = options.apply("a\nX\nc\n", &patch).unwrap_err();
+        assert!(!err.is_already_applied());
+    }
+
+    #[test]
+    fn whitespace() {
+        // Context mangled by an email client
+        let s = "\
+--- a/main.rs
++++ b/main.rs
+@@ -1,3 +1,3 @@
+ fn main() {
+-    println!(\"hello\");
++    println!(\"hello, world\");
+ }
+";
+        let patch = Patch::from_str(s).unwrap();
+        let base_image = "fn main()  {\n\tprintln!(\"hello\");  \n}\n";
+
+        apply(base_image, &patch).unwrap_err();
+        ApplyOptions::new()
+            .set_whitespace(Whitespace::IgnoreAtEol)
+            .apply(base_image, &patch)
+            .unwrap_err();
+        let applied = ApplyOptions::new()
+            .set_whitespace(Whitespace::IgnoreChange)
+            .apply(base_image, &patch)
+            .unwrap();
+        // Context keeps the image's whitespace
+        assert_eq!(
+            applied.image(),
+            "fn main()  {\n    println!(\"hello, world\");\n}\n"
+        );
+    }
+
+    #[test]
+    fn line_endings() {
+        let lf = "--- a/file\n+++ b/file\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n";
+        // What git produces for a file with CRLF line endings
+        let crlf = "--- a/file\n+++ b/file\n@@ -1,3 +1,3 @@\n a\r\n-b\r\n+B\r\n c\r\n";
+        let mut options = ApplyOptions::new();
+        options.set_ignore_line_endings(true);
+
+        let patch = Patch::from_str(lf).unwrap();
+        apply("a\r\nb\r\nc\r\n", &patch).unwrap_err();
+        let applied = options.apply("a\r\nb\r\nc\r\n", &patch).unwrap();
+        assert_eq!(applied.image(), "a\r\nB\r\nc\r\n");
+
+        let patch = Patch::from_bytes(crlf.as_bytes()).unwrap();
+        apply_bytes(b"a\nb\nc\n", &patch).unwrap_err();
+        let applied = options.apply_bytes(b"a\nb\nc\n", &patch).unwrap();
+        assert_eq!(applied.image(), b"a\nB\nc\n");
+
+        // Lines keep their own line endings in a file which mixes them
+        let applied = options.apply_bytes(b"a\nb\r\nc\r\n", &patch).unwrap();
+        assert_eq!(applied.image(), b"a\nB\r\nc\r\n");
+
+        // Whitespace modes other than `Exact` already ignore line endings when matching
+        let patch = Patch::from_str(lf).unwrap();
+        let applied = ApplyOptions::new()
+            .set_whitespace(Whitespace::IgnoreAtEol)
+            .apply("a\r\nb\r\nc\r\n", &patch)
+            .unwrap();
+        assert_eq!(applied.image(), "a\r\nB\nc\r\n");
+    }
+
+    #[test]
+    fn fuzz_needs_part_of_the_pre_image() {
+        let patch = Patch::from_str(PATCH).unwrap();
+        let mut options = ApplyOptions::new();
+        options.set_fuzz(10);
+
+        // The deleted line doesn't match
+        options
+            .apply("a\nb\nc\nd\nX\nf\ng\nh\ni\n", &patch)
+            .unwrap_err();
+
+        // Ignoring all of an insertion's context would let it apply anywhere
+        let patch = Patch::from_str("--- a\n+++ b\n@@ -1,2 +1,3 @@\n a\n+b\n c\n").unwrap();
+        options.apply("A\nC\n", &patch).unwrap_err();
+    }
+
+    #[test]
+    fn three_way() {
+        let s = "\
+--- a/file
++++ b/file
+@@ -1,5 +1,5 @@
+ a
+ b
+-c
++C
+ d
+ e
+@@ -8,3 +8,3 @@
+ h
+-i
++I
+ j
+";
+        let patch = Patch::from_str(s).unwrap();
+        let base_image = "A\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        apply(base_image, &patch).unwrap_err();
+
+        // The change to the hunk's context merges cleanly and later hunks still apply
+        let mut options = ApplyOptions::new();
+        options.set_three_way(MergeOptions::new());
+        let applied = options.apply(base_image, &patch).unwrap();
+        assert_eq!(applied.image(), "A\nb\nC\nd\ne\nf\ng\nh\nI\nj\n");
+        assert!(applied.hunks()[0].is_merged());
+        assert!(!applied.hunks()[0].has_conflicts());
+        assert_eq!(applied.hunks()[0].line(), 1);
+        assert!(!applied.hunks()[1].is_merged());
+
+        let applied = options
+            .apply_bytes(
+                base_image.as_bytes(),
+                &Patch::from_bytes(s.as_bytes()).unwrap(),
+            )
+            .unwrap();
+        assert_eq!(applied.image(), b"A\nb\nC\nd\ne\nf\ng\nh\nI\nj\n");
+
+        // Conflicting changes leave conflict markers in the image
+        let applied = options
+            .apply("a\nb\nX\nd\ne\nf\ng\nh\ni\nj\n", &patch)
+            .unwrap();
+        let expected = "\
+a
+b
+<<<<<<< ours
+X
+||||||| original
+c
+=======
+C
+>>>>>>> theirs
+d
+e
+f
+g
+h
+I
+j
+";
+        assert_eq!(applied.image(), expected);
+        assert!(applied.hunks()[0].has_conflicts());
+    }
+}
@@ -0,0 +1,716 @@
+//! Applying a `PatchSet` to the files of a `FileSystem`
+
+use super::{filesystem::FileSystem, ApplyError, ApplyOptions, HunkReport};
+use crate::patch::{ExtendedHeader, Patch, PatchSet};
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    fmt, io,
+    path::{Component, Path, PathBuf},
+};
+
+const DEV_NULL: &[u8] = b"/dev/null";
+
+/// An error returned when applying a `PatchSet` to a `FileSystem` fails
+#[derive(Debug)]
+pub struct PatchSetError {
+    patch: usize,
+    path: Option<PathBuf>,
+    kind: PatchSetErrorKind,
+}
+
+impl PatchSetError {
+    fn new(patch: usize, path: Option<&Path>, kind: PatchSetErrorKind) -> Self {
+        Self {
+            patch,
+            path: path.map(Path::to_owned),
+            kind,
+        }
+    }
+
+    /// Returns the 1-based index of the patch in the set which failed to apply
+    pub fn patch(&self) -> usize {
+        self.patch
+    }
+
+    /// Returns the path of the file the patch failed to apply to, if it could
+    /// be determined
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Returns the reason the patch failed to apply
+    pub fn kind(&self) -> &PatchSetErrorKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for PatchSetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error applying patch #{}", self.patch)?;
+        if let Some(path) = &self.path {
+            write!(f, " to {}", path.display())?;
+        }
+        match &self.kind {
+            PatchSetErrorKind::InvalidPath => write!(f, ": invalid path"),
+            PatchSetErrorKind::AlreadyExists => write!(f, ": file already exists"),
+            PatchSetErrorKind::NotEmpty => write!(f, ": deleted file still has contents"),
+            PatchSetErrorKind::Io(e) => write!(f, ": {}", e),
+            PatchSetErrorKind::Apply(e) => write!(f, ": {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PatchSetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            PatchSetErrorKind::Io(e) => Some(e),
+            PatchSetErrorKind::Apply(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// The reason a patch in a `PatchSet` failed to apply to a `FileSystem`
+#[derive(Debug)]
+pub enum PatchSetErrorKind {
+    /// The patch doesn't name a file, or one of its filenames isn't a
+    /// relative path without any `..` components once its leading components
+    /// are stripped
+    InvalidPath,
+    /// The patch creates, renames or copies a file to a path which already
+    /// exists
+    AlreadyExists,
+    /// The patch deletes a file but doesn't remove all of its contents
+    NotEmpty,
+    /// Reading or writing a file failed
+    Io(io::Error),
+    /// The patch doesn't apply to the file
+    Apply(Box<ApplyError<[u8]>>),
+}
+
+/// A file changed by applying a `PatchSet` to a `FileSystem`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AppliedFile {
+    original: Option<PathBuf>,
+    modified: Option<PathBuf>,
+    hunks: Vec<HunkReport>,
+}
+
+impl AppliedFile {
+    /// Returns the path of the file the patch was applied to, or `None` if the
+    /// patch created a new file
+    pub fn original(&self) -> Option<&Path> {
+        self.original.as_deref()
+    }
+
+    /// Returns the path the patched file was written to, or `None` if the
+    /// patch deleted the file
+    ///
+    /// This differs from `original` when the patch renames or copies a file.
+    pub fn modified(&self) -> Option<&Path> {
+        self.modified.as_deref()
+    }
+
+    /// Returns a report for each of the patch's hunks, in order
+    pub fn hunks(&self) -> &[HunkReport] {
+        &self.hunks
+    }
+}
+
+impl ApplyOptions {
+    /// Apply each `Patch` of a `PatchSet` to the files of a [`FileSystem`],
+    /// such as a directory on disk with [`StdFs`] or [`MemoryFs`]
+    ///
+    /// The files a patch applies to are found by stripping the number of
+    /// leading path components set with
+    /// [`set_strip`](ApplyOptions::set_strip) from its filenames. Patches
+    /// with git headers can create, delete, rename and copy files as well as
+    /// change their modes, while other patches create a file when their old
+    /// filename is `/dev/null` or the file is missing and the patch only adds
+    /// lines, and delete a file when their new filename is `/dev/null`.
+    ///
+    /// Nothing is written unless every patch applies, in which case a report
+    /// for each patch is returned in order. With
+    /// [`set_dry_run`](ApplyOptions::set_dry_run) nothing is written at all.
+    /// An error from the file system while writing can still leave the files
+    /// partially patched.
+    ///
+    /// ```
+    /// use diffy::{ApplyOptions, MemoryFs, PatchSet};
+    ///
+    /// let s = "\
+    /// diff --git a/bridge4 b/bridge4
+    /// --- a/bridge4
+    /// +++ b/bridge4
+    /// @@ -1,2 +1,2 @@
+    ///  Kaladin
+    /// -Moash
+    /// +Teft
+    /// diff --git a/cobalt b/cobalt
+    /// new file mode 100644
+    /// --- /dev/null
+    /// +++ b/cobalt
+    /// @@ -0,0 +1 @@
+    /// +Jasnah
+    /// ";
+    /// let patch_set = PatchSet::from_bytes(s.as_bytes()).unwrap();
+    ///
+    /// let mut fs = MemoryFs::new();
+    /// fs.insert("bridge4", "Kaladin\nMoash\n");
+    ///
+    /// // Check that the changes apply before applying them
+    /// let mut options = ApplyOptions::new();
+    /// options.set_dry_run(true).apply_patch_set(&mut fs, &patch_set).unwrap();
+    /// assert_eq!(fs.get("cobalt"), None);
+    ///
+    /// let applied = options
+    ///     .set_dry_run(false)
+    ///     .apply_patch_set(&mut fs, &patch_set)
+    ///     .unwrap();
+    /// assert_eq!(applied[1].original(), None);
+    /// assert_eq!(fs.get("bridge4"), Some(&b"Kaladin\nTeft\n"[..]));
+    /// assert_eq!(fs.get("cobalt"), Some(&b"Jasnah\n"[..]));
+    /// ```
+    ///
+    /// [`StdFs`]: crate::StdFs
+    /// [`MemoryFs`]: crate::MemoryFs
+    pub fn apply_patch_set<F: FileSystem + ?Sized>(
+        &self,
+        fs: &mut F,
+        patch_set: &PatchSet<'_, [u8]>,
+    ) -> Result<Vec<AppliedFile>, PatchSetError> {
+        let mut files = Files::new(fs);
+        let applied = patch_set
+            .patches()
+            .iter()
+            .enumerate()
+            .map(|(i, patch)| self.apply_to_files(&mut files, i + 1, patch))
+            .collect::<Result<_, _>>()?;
+
+        if !self.dry_run {
+            files.write()?;
+        }
+        Ok(applied)
+    }
+
+    fn apply_to_files<F: FileSystem + ?Sized>(
+        &self,
+        files: &mut Files<'_, F>,
+        index: usize,
+        patch: &Patch<'_, [u8]>,
+    ) -> Result<AppliedFile, PatchSetError> {
+        let error = |path: Option<&Path>, kind| PatchSetError::new(index, path, kind);
+        let io_error = |path: &Path, e| error(Some(path), PatchSetErrorKind::Io(e));
+
+        // The paths and modes of a reversed patch are swapped
+        let paths = if self.reverse {
+            FilePaths::new(&patch.reverse(), self.strip)
+        } else {
+            FilePaths::new(patch, self.strip)
+        }
+        .ok_or_else(|| error(None, PatchSetErrorKind::InvalidPath))?;
+
+        let mut original = None;
+        let mut base_image = Vec::new();
+        if let Some(path) = paths.original {
+            match files.read(&path).map_err(|e| io_error(&path, e))? {
+                Some(contents) => {
+                    original = Some(path);
+                    base_image = contents;
+                }
+                None if paths.create_if_missing => {}
+                None => return Err(io_error(&path, io::ErrorKind::NotFound.into())),
+            }
+        }
+
+        let modified = paths.modified;
+        if let Some(path) = &modified {
+            if original.as_ref() != Some(path) && files.exists(path) {
+                return Err(error(Some(path), PatchSetErrorKind::AlreadyExists));
+            }
+        }
+
+        let path = original.as_deref().or(modified.as_deref());
+        let applied = self
+            .apply_bytes(&base_image, patch)
+            .map_err(|e| error(path, PatchSetErrorKind::Apply(Box::new(e))))?;
+
+        match (&original, &modified) {
+            (Some(original), None) => {
+                if !applied.image().is_empty() {
+                    return Err(error(Some(original), PatchSetErrorKind::NotEmpty));
+                }
+                files.record(index, original, Operation::Delete);
+            }
+            (Some(original), Some(modified)) if original != modified => {
+                // Renamed and copied files keep their mode unless the patch changes it
+                let mode = match paths.mode {
+                    Some(mode) => Some(mode),
+                    None => files.mode(original).map_err(|e| io_error(original, e))?,
+                };
+                if paths.rename {
+                    files.record(index, original, Operation::Rename(modified.clone()));
+                }
+                if !paths.rename || *applied.image() != base_image {
+                    let image = applied.image().clone();
+                    files.record(index, modified, Operation::Write(image));
+                }
+                if let Some(mode) = mode {
+                    files.record(index, modified, Operation::SetMode(mode));
+                }
+            }
+            (original, Some(modified)) => {
+                if original.is_none() || *applied.image() != base_image {
+                    let image = applied.image().clone();
+                    files.record(index, modified, Operation::Write(image));
+                }
+                if let Some(mode) = paths.mode {
+                    files.record(index, modified, Operation::SetMode(mode));
+                }
+            }
+            (None, None) => return Err(error(None, PatchSetErrorKind::InvalidPath)),
+        }
+
+        Ok(AppliedFile {
+            original,
+            modified,
+            hunks: applied.hunks().to_vec(),
+        })
+    }
+}
+
+/// Apply a `PatchSet` to the files of a [`FileSystem`]
+///
+/// See [`ApplyOptions::apply_patch_set`] for details.
+pub fn apply_patch_set<F: FileSystem + ?Sized>(
+    fs: &mut F,
+    patch_set: &PatchSet<'_, [u8]>,
+) -> Result<Vec<AppliedFile>, PatchSetError> {
+    ApplyOptions::new().apply_patch_set(fs, patch_set)
+}
+
+// The files a patch applies to
+struct FilePaths {
+    // `None` if the patch creates a file
+    original: Option<PathBuf>,
+    // `None` if the patch deletes a file
+    modified: Option<PathBuf>,
+    rename: bool,
+    // The mode of the new file, if the patch sets it
+    mode: Option<u32>,
+    // Whether a missing original file should be treated as empty, as `diff -N` does instead of
+    // using `/dev/null`
+    create_if_missing: bool,
+}
+
+impl FilePaths {
+    fn new(patch: &Patch<'_, [u8]>, strip: usize) -> Option<Self> {
+        if let Some(git) = patch.git_header() {
+            let mode = git
+                .extended_headers()
+                .iter()
+                .find_map(|header| match header {
+                    ExtendedHeader::NewMode(mode) | ExtendedHeader::NewFileMode(mode) => {
+                        Some(*mode)
+                    }
+                    _ => None,
+                });
+
+            let renamed = git
+                .rename()
+                .map(|paths| (paths, true))
+                .or_else(|| git.copy().map(|paths| (paths, false)));
+            if let Some(((from, to), rename)) = renamed {
+                // Like git, the paths of `rename from` and `copy from` lines don't have the
+                // `a/` and `b/` prefixes
+                let strip = strip.saturating_sub(1);
+                return Some(Self {
+                    original: Some(strip_path(from, strip)?),
+                    modified: Some(strip_path(to, strip)?),
+                    rename,
+                    mode,
+                    create_if_missing: false,
+                });
+            }
+
+            let original = if git.is_new_file() {
+                None
+            } else {
+                Some(strip_path(git.original(), strip)?)
+            };
+            let modified = if git.is_deleted_file() {
+                None
+            } else {
+                Some(strip_path(git.modified(), strip)?)
+            };
+            return Some(Self {
+                original,
+                modified,
+                rename: false,
+                mode,
+                create_if_missing: false,
+            });
+        }
+
+        let original = patch.original().filter(|&name| name != DEV_NULL);
+        let modified = patch.modified().filter(|&name| name != DEV_NULL);
+        let path = strip_path(modified.or(original)?, strip)?;
+        Some(Self {
+            original: original.map(|_| path.clone()),
+            modified: modified.map(|_| path.clone()),
+            rename: false,
+            mode: None,
+            create_if_missing: patch.hunks().iter().all(|hunk| hunk.old_range().is_empty()),
+        })
+    }
+}
+
+// Converts a filename from a patch into a relative path after stripping `strip` leading
+// components, returning `None` if there aren't enough components left or the path could refer to
+// a file outside of the directory being patched, e.g. through `..`
+fn strip_path(name: &[u8], strip: usize) -> Option<PathBuf> {
+    if name.starts_with(b"/") {
+        return None;
+    }
+
+    // Like GNU patch, repeated slashes count as a single separator
+    let mut components = name.split(|&b| b == b'/').filter(|c| !c.is_empty());
+    for _ in 0..strip {
+        components.next()?;
+    }
+
+    let mut path = PathBuf::new();
+    for component in components.filter(|&c| c != b".") {
+        let component = Path::new(os_str(component)?);
+        let mut parts = component.components();
+        match (parts.next(), parts.next()) {
+            (Some(Component::Normal(_)), None) => path.push(component),
+            _ => return None,
+        }
+    }
+
+    if path.as_os_str().is_empty() {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+#[cfg(unix)]
+fn os_str(bytes: &[u8]) -> Option<&OsStr> {
+    use std::os::unix::ffi::OsStrExt;
+    Some(OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn os_str(bytes: &[u8]) -> Option<&OsStr> {
+    std::str::from_utf8(bytes).ok().map(OsStr::new)
+}
+
+// A change to a file, which is only made once every patch has applied
+enum Operation {
+    Write(Vec<u8>),
+    Delete,
+    Rename(PathBuf),
+    SetMode(u32),
+}
+
+// The contents and mode of a file changed by a patch
+type ChangedFile = (Vec<u8>, Option<u32>);
+
+// The files of a file system along with the changes made by the patches applied so far
+struct Files<'a, F: FileSystem + ?Sized> {
+    fs: &'a mut F,
+    // The contents and mode of each changed file, or `None` if it was deleted
+    changed: BTreeMap<PathBuf, Option<ChangedFile>>,
+    // The changes to make to the file system along with the index of the patch making them
+    operations: Vec<(usize, PathBuf, Operation)>,
+}
+
+impl<'a, F: FileSystem + ?Sized> Files<'a, F> {
+    fn new(fs: &'a mut F) -> Self {
+        Self {
+            fs,
+            changed: BTreeMap::new(),
+            operations: Vec::new(),
+        }
+    }
+
+    // Returns the contents of a file, or `None` if it doesn't exist
+    fn read(&self, path: &Path) -> io::Result<Option<Vec<u8>>> {
+        if let Some(file) = self.changed.get(path) {
+            return Ok(file.as_ref().map(|(contents, _)| contents.clone()));
+        }
+
+        match self.fs.read(path) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn mode(&self, path: &Path) -> io::Result<Option<u32>> {
+        match self.changed.get(path) {
+            Some(file) => Ok(file.as_ref().and_then(|&(_, mode)| mode)),
+            None => self.fs.mode(path),
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        match self.changed.get(path) {
+            Some(file) => file.is_some(),
+            None => self.fs.exists(path),
+        }
+    }
+
+    fn record(&mut self, patch: usize, path: &Path, operation: Operation) {
+        match &operation {
+            Operation::Write(contents) => {
+                let mode = self.mode(path).ok().flatten();
+                self.changed
+                    .insert(path.to_owned(), Some((contents.clone(), mode)));
+            }
+            Operation::Delete => {
+                self.changed.insert(path.to_owned(), None);
+            }
+            Operation::Rename(to) => {
+                let file = self
+                    .read(path)
+                    .ok()
+                    .flatten()
+                    .map(|contents| (contents, self.mode(path).ok().flatten()));
+                self.changed.insert(to.clone(), file);
+                self.changed.insert(path.to_owned(), None);
+            }
+            Operation::SetMode(mode) => {
+                if let Some(contents) = self.read(path).ok().flatten() {
+                    self.changed
+                        .insert(path.to_owned(), Some((contents, Some(*mode))));
+                }
+            }
+        }
+        self.operations.push((patch, path.to_owned(), operation));
+    }
+
+    fn write(self) -> Result<(), PatchSetError> {
+        for (patch, path, operation) in self.operations {
+            match &operation {
+                Operation::Write(contents) => self.fs.write(&path, contents),
+                Operation::Delete => self.fs.delete(&path),
+                Operation::Rename(to) => self.fs.rename(&path, to),
+                Operation::SetMode(mode) => self.fs.set_mode(&path, *mode),
+            }
+            .map_err(|e| PatchSetError::new(patch, Some(&path), PatchSetErrorKind::Io(e)))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{create_patch_bytes, MemoryFs};
+
+    fn memory_fs(files: &[(&str, &str)]) -> MemoryFs {
+        let mut fs = MemoryFs::new();
+        for (path, contents) in files {
+            fs.insert(*path, *contents);
+        }
+        fs
+    }
+
+    fn read<'a>(fs: &'a MemoryFs, path: &str) -> Option<&'a str> {
+        fs.get(path)
+            .map(|contents| std::str::from_utf8(contents).unwrap())
+    }
+
+    const GIT_PATCHES: &str = "\
+diff --git a/src/alpha b/src/alpha
+index 1111111..2222222 100644
+--- a/src/alpha
++++ b/src/alpha
+@@ -1,2 +1,2 @@
+ Kaladin
+-Shallan
++Jasnah
+diff --git a/beta b/beta
+new file mode 100755
+index 0000000..3333333
+--- /dev/null
++++ b/beta
+@@ -0,0 +1 @@
++Szeth
+diff --git a/gamma b/gamma
+deleted file mode 100644
+index 4444444..0000000
+--- a/gamma
++++ /dev/null
+@@ -1 +0,0 @@
+-Lift
+diff --git a/delta b/docs/delta
+similarity index 100%
+rename from delta
+rename to docs/delta
+";
+
+    #[test]
+    fn apply_git_patches() {
+        let original = memory_fs(&[
+            ("src/alpha", "Kaladin\nShallan\n"),
+            ("gamma", "Lift\n"),
+            ("delta", "Navani\n"),
+        ]);
+        let mut fs = original.clone();
+        let patch_set = PatchSet::from_bytes(GIT_PATCHES.as_bytes()).unwrap();
+
+        let applied = apply_patch_set(&mut fs, &patch_set).unwrap();
+        assert_eq!(applied.len(), 4);
+        assert_eq!(applied[1].original(), None);
+        assert_eq!(applied[1].modified(), Some(Path::new("beta")));
+        assert_eq!(applied[2].modified(), None);
+        assert_eq!(applied[3].original(), Some(Path::new("delta")));
+        assert_eq!(applied[3].modified(), Some(Path::new("docs/delta")));
+
+        assert_eq!(read(&fs, "src/alpha"), Some("Kaladin\nJasnah\n"));
+        assert_eq!(read(&fs, "beta"), Some("Szeth\n"));
+        assert_eq!(fs.mode(Path::new("beta")).unwrap(), Some(0o100755));
+        assert_eq!(read(&fs, "gamma"), None);
+        assert_eq!(read(&fs, "delta"), None);
+        assert_eq!(read(&fs, "docs/delta"), Some("Navani\n"));
+
+        // Applying the set in reverse restores the original files
+        ApplyOptions::new()
+            .set_reverse(true)
+            .apply_patch_set(&mut fs, &patch_set)
+            .unwrap();
+        assert_eq!(fs, original);
+    }
+
+    #[test]
+    fn modes() {
+        let s = "\
+diff --git a/build.sh b/build.sh
+old mode 100644
+new mode 100755
+diff --git a/build.sh b/scripts/build.sh
+similarity index 50%
+copy from build.sh
+copy to scripts/build.sh
+--- a/build.sh
++++ b/scripts/build.sh
+@@ -1 +1 @@
+-make
++cargo build
+";
+        let mut fs = memory_fs(&[("build.sh", "make\n")]);
+        let patch_set = PatchSet::from_bytes(s.as_bytes()).unwrap();
+
+        apply_patch_set(&mut fs, &patch_set).unwrap();
+        assert_eq!(read(&fs, "build.sh"), Some("make\n"));
+        assert_eq!(fs.mode(Path::new("build.sh")).unwrap(), Some(0o100755));
+        assert_eq!(read(&fs, "scripts/build.sh"), Some("cargo build\n"));
+        // The copy has the mode the file had once the first patch was applied
+        assert_eq!(
+            fs.mode(Path::new("scripts/build.sh")).unwrap(),
+            Some(0o100755)
+        );
+    }
+
+    #[test]
+    fn nothing_is_written_on_failure() {
+        let original = memory_fs(&[
+            ("src/alpha", "Kaladin\nShallan\n"),
+            ("gamma", "Hoid\n"),
+            ("delta", "Navani\n"),
+        ]);
+        let mut fs = original.clone();
+        let patch_set = PatchSet::from_bytes(GIT_PATCHES.as_bytes()).unwrap();
+
+        let err = apply_patch_set(&mut fs, &patch_set).unwrap_err();
+        assert_eq!(err.patch(), 3);
+        assert_eq!(err.path(), Some(Path::new("gamma")));
+        assert!(matches!(err.kind(), PatchSetErrorKind::Apply(_)));
+        assert_eq!(fs, original);
+
+        // A dry run doesn't write anything even when every patch applies
+        let original = memory_fs(&[
+            ("src/alpha", "Kaladin\nShallan\n"),
+            ("gamma", "Lift\n"),
+            ("delta", "Navani\n"),
+        ]);
+        let mut fs = original.clone();
+        let applied = ApplyOptions::new()
+            .set_dry_run(true)
+            .apply_patch_set(&mut fs, &patch_set)
+            .unwrap();
+        assert_eq!(applied.len(), 4);
+        assert_eq!(fs, original);
+
+        // A new file can't replace an existing one
+        fs.insert("beta", "Szeth\n");
+        let err = apply_patch_set(&mut fs, &patch_set).unwrap_err();
+        assert_eq!(err.patch(), 2);
+        assert!(matches!(err.kind(), PatchSetErrorKind::AlreadyExists));
+    }
+
+    #[test]
+    fn recursive_diff() {
+        let s = "\
+diff -ruN old/alpha new/alpha
+--- old/alpha\t2024-01-01 00:00:00.000000000 +0000
++++ new/alpha\t2024-01-02 00:00:00.000000000 +0000
+@@ -1,2 +1,2 @@
+ Kaladin
+-Shallan
++Jasnah
+diff -ruN old/beta new/beta
+--- old/beta\t1970-01-01 00:00:00.000000000 +0000
++++ new/beta\t2024-01-02 00:00:00.000000000 +0000
+@@ -0,0 +1 @@
++Szeth
+";
+        let mut fs = memory_fs(&[("alpha", "Kaladin\nShallan\n")]);
+        let patch_set = PatchSet::from_bytes(s.as_bytes()).unwrap();
+
+        let err = ApplyOptions::new()
+            .set_strip(2)
+            .apply_patch_set(&mut fs, &patch_set)
+            .unwrap_err();
+        assert!(matches!(err.kind(), PatchSetErrorKind::InvalidPath));
+
+        let applied = apply_patch_set(&mut fs, &patch_set).unwrap();
+        assert_eq!(applied[1].original(), None);
+        assert_eq!(read(&fs, "alpha"), Some("Kaladin\nJasnah\n"));
+        assert_eq!(read(&fs, "beta"), Some("Szeth\n"));
+
+        // Patches to the same file are applied one after the other
+        fs.insert("modified", "Szeth\n");
+        let patch_set = PatchSet::new(vec![
+            create_patch_bytes(b"Szeth\n", b"Szeth\nNightblood\n"),
+            create_patch_bytes(b"Szeth\nNightblood\n", b"Nightblood\n"),
+        ]);
+        ApplyOptions::new()
+            .set_strip(0)
+            .apply_patch_set(&mut fs, &patch_set)
+            .unwrap();
+        assert_eq!(read(&fs, "modified"), Some("Nightblood\n"));
+    }
+
+    #[test]
+    fn strip() {
+        assert_eq!(
+            strip_path(b"a/src/lib.rs", 1).unwrap(),
+            Path::new("src/lib.rs")
+        );
+        assert_eq!(
+            strip_path(b"a//src/./lib.rs", 2).unwrap(),
+            Path::new("lib.rs")
+        );
+        assert_eq!(strip_path(b"lib.rs", 0).unwrap(), Path::new("lib.rs"));
+        assert_eq!(strip_path(b"a/lib.rs", 2), None);
+        assert_eq!(strip_path(b"/etc/passwd", 1), None);
+        assert_eq!(strip_path(b"a/../../etc/passwd", 1), None);
+        assert_eq!(strip_path(b"a/.", 1), None);
+    }
+}
@@ -26,6 +26,27 @@ pub fn compact<'a, 'b, T: ?Sized + SliceLike>(diffs: &mut Vec<DiffRange<'a, 'b,
     }
 }
 
+// Joins adjacent ranges of the same kind and drops empty ones. Algorithms which split the inputs up
+// into many pieces use this to produce the same shape of solution as `myers::diff`.
+pub fn merge_adjacent<T: ?Sized + SliceLike>(diffs: &mut Vec<DiffRange<'_, '_, T>>) {
+    let mut merged: Vec<DiffRange<'_, '_, T>> = Vec::with_capacity(diffs.len());
+    for diff in diffs.drain(..) {
+        if diff.is_empty() {
+            continue;
+        }
+
+        match (merged.last_mut(), diff) {
+            (Some(last @ DiffRange::Equal(..)), DiffRange::Equal(..))
+            | (Some(last @ DiffRange::Delete(_)), DiffRange::Delete(_))
+            | (Some(last @ DiffRange::Insert(_)), DiffRange::Insert(_)) => {
+                last.grow_down(diff.len())
+            }
+            _ => merged.push(diff),
+        }
+    }
+    *diffs = merged;
+}
+
 // Attempts to shift the Insertion or Deletion at location `pointer` as far upwards as possible.
 #[allow(clippy::needless_lifetimes)]
 fn shift_diff_up<'a, 'b, T: ?Sized + SliceLike>(
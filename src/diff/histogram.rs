@@ -0,0 +1,198 @@
+//! Histogram diff
+//!
+//! An extension of patience diff, based on the implementation in git and
+//! JGit. Rather than only considering lines which are unique, the common
+//! region whose lines occur the fewest times in the old input is matched up
+//! and the regions on either side of it are diffed recursively, falling back
+//! to Myers when no suitable region exists.
+
+use super::{cleanup, myers};
+use crate::range::{DiffRange, Range};
+use std::{collections::HashMap, hash::Hash};
+
+// Lines which occur more often than this in the old input aren't considered when looking for a
+// common region to split on
+const MAX_CHAIN_LEN: usize = 64;
+
+pub fn diff<'a, 'b, T: Eq + Hash>(
+    old: &'a [T],
+    new: &'b [T],
+    max_cost: Option<usize>,
+) -> Vec<DiffRange<'a, 'b, [T]>> {
+    let mut solution = Vec::new();
+    conquer(
+        Range::new(old, ..),
+        Range::new(new, ..),
+        max_cost,
+        &mut solution,
+    );
+    cleanup::merge_adjacent(&mut solution);
+    solution
+}
+
+fn conquer<'a, 'b, T: Eq + Hash>(
+    mut old: Range<'a, [T]>,
+    mut new: Range<'b, [T]>,
+    max_cost: Option<usize>,
+    solution: &mut Vec<DiffRange<'a, 'b, [T]>>,
+) {
+    // The region following a common region is handled iteratively rather than recursively in order
+    // to bound the depth of recursion for inputs that are split many times
+    let mut suffixes = Vec::new();
+    loop {
+        let common_prefix_len = old.common_prefix_len(new);
+        solution.push(DiffRange::Equal(
+            old.slice(..common_prefix_len),
+            new.slice(..common_prefix_len),
+        ));
+        old = old.slice(common_prefix_len..);
+        new = new.slice(common_prefix_len..);
+
+        let common_suffix_len = old.common_suffix_len(new);
+        let (old_rest, old_suffix) = old.split_at(old.len() - common_suffix_len);
+        let (new_rest, new_suffix) = new.split_at(new.len() - common_suffix_len);
+        suffixes.push(DiffRange::Equal(old_suffix, new_suffix));
+        old = old_rest;
+        new = new_rest;
+
+        if old.is_empty() {
+            solution.push(DiffRange::Insert(new));
+            break;
+        } else if new.is_empty() {
+            solution.push(DiffRange::Delete(old));
+            break;
+        }
+
+        match find_common_region(old, new) {
+            Some((old_start, new_start, len)) => {
+                conquer(
+                    old.slice(..old_start),
+                    new.slice(..new_start),
+                    max_cost,
+                    solution,
+                );
+                solution.push(DiffRange::Equal(
+                    old.slice(old_start..old_start + len),
+                    new.slice(new_start..new_start + len),
+                ));
+                old = old.slice(old_start + len..);
+                new = new.slice(new_start + len..);
+            }
+            None => {
+                myers::diff_range(old, new, max_cost, solution);
+                break;
+            }
+        }
+    }
+
+    solution.extend(suffixes.into_iter().rev());
+}
+
+// Finds the common region of `old` and `new` whose rarest line occurs the fewest times in `old`,
+// preferring longer regions among those which are equally rare. Like git, lines which occur more
+// often than the rarest line of the best region found so far aren't considered at all. Returns the
+// start of the region in each input along with its length.
+fn find_common_region<T: Eq + Hash>(
+    old: Range<'_, [T]>,
+    new: Range<'_, [T]>,
+) -> Option<(usize, usize, usize)> {
+    let old = old.as_slice();
+    let new = new.as_slice();
+
+    let mut occurrences: HashMap<&T, Vec<usize>> = HashMap::new();
+    for (i, line) in old.iter().enumerate() {
+        occurrences.entry(line).or_default().push(i);
+    }
+    let count = |line: &T| occurrences.get(line).map_or(0, Vec::len);
+
+    // (rarest line count, old start, new start, len)
+    let mut best: Option<(usize, usize, usize, usize)> = None;
+    let mut new_pos = 0;
+    while new_pos < new.len() {
+        let mut next_new_pos = new_pos + 1;
+
+        let max_count = best.map_or(MAX_CHAIN_LEN, |(best_rarest, ..)| best_rarest);
+        let positions = match occurrences.get(&new[new_pos]) {
+            Some(positions) if positions.len() <= max_count => positions,
+            _ => {
+                new_pos = next_new_pos;
+                continue;
+            }
+        };
+
+        for &old_pos in positions {
+            let (mut old_start, mut new_start) = (old_pos, new_pos);
+            while old_start > 0 && new_start > 0 && old[old_start - 1] == new[new_start - 1] {
+                old_start -= 1;
+                new_start -= 1;
+            }
+            let (mut old_end, mut new_end) = (old_pos + 1, new_pos + 1);
+            while old_end < old.len() && new_end < new.len() && old[old_end] == new[new_end] {
+                old_end += 1;
+                new_end += 1;
+            }
+
+            let rarest = old[old_start..old_end]
+                .iter()
+                .map(&count)
+                .min()
+                .unwrap_or(0);
+            let len = old_end - old_start;
+            let is_better = match best {
+                Some((best_rarest, _, _, best_len)) => {
+                    rarest < best_rarest || (rarest == best_rarest && len > best_len)
+                }
+                None => true,
+            };
+            if is_better {
+                best = Some((rarest, old_start, new_start, len));
+            }
+
+            // Lines which were part of this region don't need to be considered again
+            next_new_pos = next_new_pos.max(new_end);
+        }
+
+        new_pos = next_new_pos;
+    }
+
+    best.map(|(_, old_start, new_start, len)| (old_start, new_start, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefers_rare_lines() {
+        // A unique line wins over a longer run of lines which occur more often
+        let old = ["fn a() {", "}", "}", "}", "unique();"];
+        let new = ["unique();", "}", "}", "}", "fn b() {"];
+        assert_eq!(
+            find_common_region(Range::new(&old[..], ..), Range::new(&new[..], ..)),
+            Some((4, 0, 1))
+        );
+
+        // The same holds when the frequent lines are found after the unique one
+        let old = ["unique();", "}", "}", "}", "fn a() {"];
+        let new = ["fn b() {", "}", "}", "}", "unique();"];
+        assert_eq!(
+            find_common_region(Range::new(&old[..], ..), Range::new(&new[..], ..)),
+            Some((0, 4, 1))
+        );
+
+        // Among equally rare regions the longest one is chosen
+        let old = ["a", "b", "x", "c", "d", "e"];
+        let new = ["c", "d", "e", "y", "a", "b"];
+        assert_eq!(
+            find_common_region(Range::new(&old[..], ..), Range::new(&new[..], ..)),
+            Some((3, 0, 3))
+        );
+
+        let old = ["a", "b"];
+        let new = ["c", "d"];
+        assert_eq!(
+            find_common_region(Range::new(&old[..], ..), Range::new(&new[..], ..)),
+            None
+        );
+    }
+}
@@ -1,29 +1,47 @@
 use crate::{
-    patch::{Hunk, HunkRange, Line, Patch},
+    patch::{BinaryPatch, Hunk, HunkRange, Line, Patch},
     range::{DiffRange, SliceLike},
-    utils::Classifier,
+    token::{GroupTokenizer, Tokenizer},
+    utils::{is_blank, Classifier, Text},
 };
 use std::{
     borrow::Cow,
     cmp,
+    hash::Hash,
     ops::{self},
+    sync::Arc,
 };
 
 pub mod cleanup;
+mod histogram;
 pub mod myers;
+mod patience;
 
 #[cfg(test)]
 mod tests;
 
-// TODO determine if this should be exposed in the public API
-#[allow(dead_code)]
+/// A chunk of a diff between two texts, as returned by
+/// [`DiffOptions::diff_lines`], [`DiffOptions::diff_words`] and
+/// [`DiffOptions::diff_chars`]
 #[derive(Debug, PartialEq, Eq)]
-enum Diff<'a, T: ?Sized> {
+pub enum Diff<'a, T: ?Sized> {
+    /// Text present in both the original and modified texts
     Equal(&'a T),
+    /// Text only present in the original text
     Delete(&'a T),
+    /// Text only present in the modified text
     Insert(&'a T),
 }
 
+impl<'a, T: ?Sized> Diff<'a, T> {
+    /// Returns the text of the chunk
+    pub fn inner(&self) -> &'a T {
+        match *self {
+            Diff::Equal(text) | Diff::Delete(text) | Diff::Insert(text) => text,
+        }
+    }
+}
+
 impl<T: ?Sized> Copy for Diff<'_, T> {}
 
 impl<T: ?Sized> Clone for Diff<'_, T> {
@@ -45,31 +63,162 @@ where
     }
 }
 
+/// A region of a diff between two slices, given as ranges of indices into
+/// them, as returned by [`DiffOptions::diff_slices`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SliceDiff {
+    /// Items present in both slices, with their indices in the original and
+    /// modified slices
+    Equal(ops::Range<usize>, ops::Range<usize>),
+    /// Items only present in the original slice
+    Delete(ops::Range<usize>),
+    /// Items only present in the modified slice
+    Insert(ops::Range<usize>),
+}
+
+impl<T: ?Sized> From<DiffRange<'_, '_, T>> for SliceDiff {
+    fn from(diff: DiffRange<'_, '_, T>) -> Self {
+        match diff {
+            DiffRange::Equal(old, new) => SliceDiff::Equal(old.range(), new.range()),
+            DiffRange::Delete(old) => SliceDiff::Delete(old.range()),
+            DiffRange::Insert(new) => SliceDiff::Insert(new.range()),
+        }
+    }
+}
+
+/// The algorithm used to find the differences between two texts
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Myers' O(ND) algorithm, which finds a minimal diff
+    #[default]
+    Myers,
+    /// Patience diff, which aligns the texts on lines that occur exactly once
+    /// in both. This avoids matching up common lines like `}` or blank lines
+    /// in unrelated parts of the texts, at the cost of sometimes producing a
+    /// larger diff.
+    Patience,
+    /// Histogram diff, an extension of patience diff which aligns the texts on
+    /// the lines that occur the fewest times rather than only on unique lines.
+    /// This is the algorithm used by `git diff --histogram`.
+    Histogram,
+}
+
+/// How whitespace is treated when comparing lines
+///
+/// Only the comparison is affected, lines are always emitted with their
+/// original whitespace.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Whitespace {
+    /// Lines must match exactly
+    #[default]
+    Exact,
+    /// Ignore whitespace at the end of lines, including the line ending, like
+    /// `git diff --ignore-space-at-eol`
+    IgnoreAtEol,
+    /// Ignore whitespace at the end of lines and treat all other runs of
+    /// whitespace as equivalent, like `git diff --ignore-space-change`
+    IgnoreChange,
+    /// Ignore all whitespace, like `git diff --ignore-all-space`
+    IgnoreAll,
+}
+
 /// A collection of options for modifying the way a diff is performed
 #[derive(Debug)]
 pub struct DiffOptions {
+    algorithm: Algorithm,
     compact: bool,
     context_len: usize,
+    max_cost: Option<usize>,
+    whitespace: Whitespace,
+    ignore_blank_lines: bool,
+    tokenizer: Arc<dyn Tokenizer>,
 }
 
 impl DiffOptions {
     /// Construct a new `DiffOptions` with default settings
     ///
     /// ## Defaults
+    /// * algorithm = Algorithm::Myers
     /// * context_len = 3
+    /// * max_cost = None
+    /// * whitespace = Whitespace::Exact
+    /// * ignore_blank_lines = false
+    /// * tokenizer = GroupTokenizer::default()
     pub fn new() -> Self {
         Self {
+            algorithm: Algorithm::default(),
             compact: true,
             context_len: 3,
+            max_cost: None,
+            whitespace: Whitespace::default(),
+            ignore_blank_lines: false,
+            tokenizer: Arc::new(GroupTokenizer::default()),
         }
     }
 
+    /// Set the algorithm used to find the differences between two texts
+    pub fn set_algorithm(&mut self, algorithm: Algorithm) -> &mut Self {
+        self.algorithm = algorithm;
+        self
+    }
+
     /// Set the number of context lines that should be used when producing a patch
     pub fn set_context_len(&mut self, context_len: usize) -> &mut Self {
         self.context_len = context_len;
         self
     }
 
+    /// Set a limit on the number of edits Myers' algorithm will consider
+    /// before settling for a diff which may not be minimal
+    ///
+    /// Finding a minimal diff between large, very different texts can take a
+    /// long time. When a limit is set and the search for a region of the diff
+    /// exceeds it, the region is split at the furthest point reached so far,
+    /// similar to git's heuristic for expensive diffs. The patience and
+    /// histogram algorithms apply the limit to the regions they fall back to
+    /// Myers for. `None`, the default, always finds a minimal diff.
+    pub fn set_max_cost(&mut self, max_cost: Option<usize>) -> &mut Self {
+        self.max_cost = max_cost;
+        self
+    }
+
+    /// Set how whitespace is treated when comparing lines
+    ///
+    /// Note that a patch produced while ignoring whitespace may not apply
+    /// cleanly since its context and deleted lines are only guaranteed to
+    /// match the original text up to whitespace.
+    pub fn set_whitespace(&mut self, whitespace: Whitespace) -> &mut Self {
+        self.whitespace = whitespace;
+        self
+    }
+
+    /// Ignore changes whose lines are all blank, like
+    /// `git diff --ignore-blank-lines`
+    ///
+    /// Hunks which only insert or delete blank lines are left out of the
+    /// patch. Blank line changes close enough to another change to share a
+    /// hunk with it are still included.
+    pub fn set_ignore_blank_lines(&mut self, ignore_blank_lines: bool) -> &mut Self {
+        self.ignore_blank_lines = ignore_blank_lines;
+        self
+    }
+
+    /// Set the tokenizer used to split texts into words by
+    /// [`diff_words`](Self::diff_words)
+    pub fn set_tokenizer<T: Tokenizer + 'static>(&mut self, tokenizer: T) -> &mut Self {
+        self.tokenizer = Arc::new(tokenizer);
+        self
+    }
+
+    pub(crate) fn set_shared_tokenizer(&mut self, tokenizer: Arc<dyn Tokenizer>) -> &mut Self {
+        self.tokenizer = tokenizer;
+        self
+    }
+
+    pub(crate) fn tokenizer(&self) -> &dyn Tokenizer {
+        &*self.tokenizer
+    }
+
     /// Enable/Disable diff compaction. Compaction is a post-processing step which attempts to
     /// produce a prettier diff by reducing the number of edited blocks by shifting and merging
     /// edit blocks.
@@ -80,10 +229,114 @@ impl DiffOptions {
         self
     }
 
-    // TODO determine if this should be exposed in the public API
-    #[allow(dead_code)]
-    fn diff<'a>(&self, original: &'a str, modified: &'a str) -> Vec<Diff<'a, str>> {
-        let solution = myers::diff(original.as_bytes(), modified.as_bytes());
+    /// Diff two texts line by line
+    ///
+    /// Each chunk holds one or more whole lines, including their line endings.
+    /// Lines are compared according to the configured [`Whitespace`] mode,
+    /// with `Equal` chunks holding the lines from `original`.
+    ///
+    /// ```
+    /// use diffy::{Diff, DiffOptions};
+    ///
+    /// let diff = DiffOptions::new().diff_lines("a\nb\nc\n", "a\nB\nc\n");
+    /// assert_eq!(
+    ///     diff,
+    ///     [
+    ///         Diff::Equal("a\n"),
+    ///         Diff::Delete("b\n"),
+    ///         Diff::Insert("B\n"),
+    ///         Diff::Equal("c\n"),
+    ///     ]
+    /// );
+    /// ```
+    pub fn diff_lines<'a>(&self, original: &'a str, modified: &'a str) -> Vec<Diff<'a, str>> {
+        let mut classifier = Classifier::with_whitespace(self.whitespace);
+        let (old_lines, old_ids, _) = classifier.classify_lines(original);
+        let (new_lines, new_ids, _) = classifier.classify_lines(modified);
+        self.diff_tokens(
+            original, &old_lines, &old_ids, modified, &new_lines, &new_ids,
+        )
+    }
+
+    /// Diff two texts word by word
+    ///
+    /// The texts are split into words using the configured [`Tokenizer`]. By
+    /// default this splits them into runs of letters and digits, runs of
+    /// whitespace and individual punctuation characters, the same way changed
+    /// words are highlighted by [`PatchFormatter::with_color`].
+    ///
+    /// ```
+    /// use diffy::{Diff, DiffOptions};
+    ///
+    /// let diff = DiffOptions::new().diff_words("The quick fox", "The slow fox");
+    /// assert_eq!(
+    ///     diff,
+    ///     [
+    ///         Diff::Equal("The "),
+    ///         Diff::Delete("quick"),
+    ///         Diff::Insert("slow"),
+    ///         Diff::Equal(" fox"),
+    ///     ]
+    /// );
+    /// ```
+    ///
+    /// [`PatchFormatter::with_color`]: crate::PatchFormatter::with_color
+    pub fn diff_words<'a>(&self, original: &'a str, modified: &'a str) -> Vec<Diff<'a, str>> {
+        let mut classifier = Classifier::default();
+        let (old_words, old_ids) = classifier.classify_groups(original, self.tokenizer());
+        let (new_words, new_ids) = classifier.classify_groups(modified, self.tokenizer());
+        self.diff_tokens(
+            original, &old_words, &old_ids, modified, &new_words, &new_ids,
+        )
+    }
+
+    // Converts a diff between the ids of two tokenized texts into chunks of the texts themselves
+    fn diff_tokens<'a>(
+        &self,
+        original: &'a str,
+        old_tokens: &[&'a str],
+        old_ids: &[u64],
+        modified: &'a str,
+        new_tokens: &[&'a str],
+        new_ids: &[u64],
+    ) -> Vec<Diff<'a, str>> {
+        let old_offsets = token_offsets(old_tokens);
+        let new_offsets = token_offsets(new_tokens);
+        let old_text =
+            |range: ops::Range<usize>| &original[old_offsets[range.start]..old_offsets[range.end]];
+        let new_text =
+            |range: ops::Range<usize>| &modified[new_offsets[range.start]..new_offsets[range.end]];
+
+        self.diff_slice(old_ids, new_ids)
+            .into_iter()
+            .filter(|diff_range| !diff_range.is_empty())
+            .map(|diff_range| match diff_range {
+                DiffRange::Equal(range, _) => Diff::Equal(old_text(range.range())),
+                DiffRange::Delete(range) => Diff::Delete(old_text(range.range())),
+                DiffRange::Insert(range) => Diff::Insert(new_text(range.range())),
+            })
+            .collect()
+    }
+
+    /// Diff two texts character by character
+    ///
+    /// ```
+    /// use diffy::{Diff, DiffOptions};
+    ///
+    /// let diff = DiffOptions::new().diff_chars("bat", "map");
+    /// assert_eq!(
+    ///     diff,
+    ///     [
+    ///         Diff::Delete("b"),
+    ///         Diff::Insert("m"),
+    ///         Diff::Equal("a"),
+    ///         Diff::Delete("t"),
+    ///         Diff::Insert("p"),
+    ///     ]
+    /// );
+    /// ```
+    pub fn diff_chars<'a>(&self, original: &'a str, modified: &'a str) -> Vec<Diff<'a, str>> {
+        let solution = self.solve(original.as_bytes(), modified.as_bytes());
 
         let mut solution = solution
             .into_iter()
@@ -114,22 +367,25 @@ impl DiffOptions {
     where
         P: Into<Cow<'a, str>>,
     {
-        let mut classifier = Classifier::default();
+        let mut classifier = Classifier::with_whitespace(self.whitespace);
         let (old_lines, old_ids, old_pos) = classifier.classify_lines(original);
         let (new_lines, new_ids, new_pos) = classifier.classify_lines(modified);
 
         let solution = self.diff_slice(&old_ids, &new_ids);
 
-        let hunks = to_hunks(
+        let mut hunks = to_hunks(
             &old_lines,
             &new_lines,
             &solution,
             self.context_len,
-            &original,
-            &modified,
+            original,
+            modified,
             &old_pos,
             &new_pos,
         );
+        if self.ignore_blank_lines {
+            hunks.retain(|hunk| !only_changes_blank_lines(hunk));
+        }
         Patch::new(Some(original_name), Some(modified_name), hunks)
     }
 
@@ -139,31 +395,63 @@ impl DiffOptions {
         original: &'a [u8],
         modified: &'a [u8],
     ) -> Patch<'a, [u8]> {
-        let mut classifier = Classifier::default();
+        let mut classifier = Classifier::with_whitespace(self.whitespace);
         let (old_lines, old_ids, old_pos) = classifier.classify_lines(original);
         let (new_lines, new_ids, new_pos) = classifier.classify_lines(modified);
 
         let solution = self.diff_slice(&old_ids, &new_ids);
 
-        let hunks = to_hunks(
+        let mut hunks = to_hunks(
             &old_lines,
             &new_lines,
             &solution,
             self.context_len,
-            &original,
-            &modified,
+            original,
+            modified,
             &old_pos,
             &new_pos,
         );
+        if self.ignore_blank_lines {
+            hunks.retain(|hunk| !only_changes_blank_lines(hunk));
+        }
         Patch::new(Some(&b"original"[..]), Some(&b"modified"[..]), hunks)
     }
 
-    pub(crate) fn diff_slice<'a, T: PartialEq>(
+    /// Diff two slices of arbitrary items
+    ///
+    /// The differences are returned as ranges of indices into the slices.
+    /// Only the diff algorithm and its cost limit apply, the whitespace options
+    /// are specific to text.
+    ///
+    /// ```
+    /// use diffy::{DiffOptions, SliceDiff};
+    ///
+    /// let original = [1, 2, 3, 4];
+    /// let modified = [1, 3, 4, 5];
+    /// assert_eq!(
+    ///     DiffOptions::new().diff_slices(&original, &modified),
+    ///     [
+    ///         SliceDiff::Equal(0..1, 0..1),
+    ///         SliceDiff::Delete(1..2),
+    ///         SliceDiff::Equal(2..4, 1..3),
+    ///         SliceDiff::Insert(3..4),
+    ///     ]
+    /// );
+    /// ```
+    pub fn diff_slices<T: Eq + Hash>(&self, original: &[T], modified: &[T]) -> Vec<SliceDiff> {
+        self.diff_slice(original, modified)
+            .into_iter()
+            .filter(|diff_range| !diff_range.is_empty())
+            .map(SliceDiff::from)
+            .collect()
+    }
+
+    pub(crate) fn diff_slice<'a, T: Eq + Hash>(
         &self,
         old: &'a [T],
         new: &'a [T],
     ) -> Vec<DiffRange<'a, 'a, [T]>> {
-        let mut solution = myers::diff(old, new);
+        let mut solution = self.solve(old, new);
 
         if self.compact {
             cleanup::compact(&mut solution);
@@ -171,6 +459,14 @@ impl DiffOptions {
 
         solution
     }
+
+    fn solve<'a, T: Eq + Hash>(&self, old: &'a [T], new: &'a [T]) -> Vec<DiffRange<'a, 'a, [T]>> {
+        match self.algorithm {
+            Algorithm::Myers => myers::diff(old, new, self.max_cost),
+            Algorithm::Patience => patience::diff(old, new, self.max_cost),
+            Algorithm::Histogram => histogram::diff(old, new, self.max_cost),
+        }
+    }
 }
 
 impl Default for DiffOptions {
@@ -179,10 +475,43 @@ impl Default for DiffOptions {
     }
 }
 
-// TODO determine if this should be exposed in the public API
-#[allow(dead_code)]
-fn diff<'a>(original: &'a str, modified: &'a str) -> Vec<Diff<'a, str>> {
-    DiffOptions::default().diff(original, modified)
+// The offset of the start of each token in the text it was split from, followed by the length of
+// the text
+fn token_offsets(tokens: &[&str]) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(tokens.len() + 1);
+    let mut offset = 0;
+    offsets.push(offset);
+    for token in tokens {
+        offset += token.len();
+        offsets.push(offset);
+    }
+    offsets
+}
+
+/// Diff two slices of arbitrary items
+///
+/// See [`DiffOptions::diff_slices`] for details.
+pub fn diff_slices<T: Eq + Hash>(original: &[T], modified: &[T]) -> Vec<SliceDiff> {
+    DiffOptions::default().diff_slices(original, modified)
+}
+
+/// Diff two texts line by line
+///
+/// See [`DiffOptions::diff_lines`] for details.
+pub fn diff_lines<'a>(original: &'a str, modified: &'a str) -> Vec<Diff<'a, str>> {
+    DiffOptions::default().diff_lines(original, modified)
+}
+
+/// Diff two texts word by word
+///
+/// See [`DiffOptions::diff_words`] for details.
+pub fn diff_words<'a>(original: &'a str, modified: &'a str) -> Vec<Diff<'a, str>> {
+    DiffOptions::default().diff_words(original, modified)
+}
+
+/// Diff two texts character by character
+pub fn diff_chars<'a>(original: &'a str, modified: &'a str) -> Vec<Diff<'a, str>> {
+    DiffOptions::default().diff_chars(original, modified)
 }
 
 /// Create a patch between two texts.
@@ -227,15 +556,45 @@ pub fn create_patch_bytes<'a>(original: &'a [u8], modified: &'a [u8]) -> Patch<'
     DiffOptions::default().create_patch_bytes(original, modified)
 }
 
-fn to_hunks<'a, T: ?Sized + SliceLike>(
+/// Create a git binary patch between two files
+///
+/// ```
+/// # use diffy::{apply_bytes, create_binary_patch, Patch};
+/// let original = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR";
+/// let modified = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR\x00\x00\x00\x01";
+///
+/// let patch = create_binary_patch(original, modified);
+/// assert_eq!(apply_bytes(original, &patch).unwrap(), modified);
+///
+/// let bytes = patch.to_bytes();
+/// assert!(bytes.starts_with(b"diff --git original modified\nGIT binary patch\n"));
+/// assert_eq!(Patch::from_bytes(&bytes).unwrap(), patch);
+/// ```
+pub fn create_binary_patch<'a>(original: &[u8], modified: &[u8]) -> Patch<'a, [u8]> {
+    Patch::new_binary(
+        &b"original"[..],
+        &b"modified"[..],
+        BinaryPatch::new(original, modified),
+    )
+}
+
+fn only_changes_blank_lines<T: ?Sized + Text>(hunk: &Hunk<'_, T>) -> bool {
+    hunk.lines().iter().all(|line| match line {
+        Line::Context(_) => true,
+        Line::Delete(line) | Line::Insert(line) => is_blank(line.as_bytes()),
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn to_hunks<'a, T: ?Sized + ToOwned + SliceLike>(
     lines1: &[&'a T],
     lines2: &[&'a T],
     solution: &[DiffRange<[u64]>],
     context_len: usize,
     original: &'a T,
     modified: &'a T,
-    old_pos: &Vec<usize>,
-    new_pos: &Vec<usize>,
+    old_pos: &[usize],
+    new_pos: &[usize],
 ) -> Vec<Hunk<'a, T>> {
     let edit_script = build_edit_script(solution);
 
@@ -258,7 +617,7 @@ fn to_hunks<'a, T: ?Sized + SliceLike>(
 
         // Pre-context
         for line in lines2.get(start2..script.new.start).into_iter().flatten() {
-            lines.push(Line::Context(*line));
+            lines.push(Line::Context(Cow::Borrowed(*line)));
         }
 
         let mut originals = Vec::new();
@@ -268,7 +627,7 @@ fn to_hunks<'a, T: ?Sized + SliceLike>(
             // Delete lines from text1
             let range = script.old.clone();
             for line in lines1.get(range.clone()).into_iter().flatten() {
-                lines.push(Line::Delete(*line));
+                lines.push(Line::Delete(Cow::Borrowed(*line)));
             }
             let substr = match old_pos.get(range.start) {
                 None => original.as_slice(0..0),
@@ -277,12 +636,12 @@ fn to_hunks<'a, T: ?Sized + SliceLike>(
                     Some(&end) => original.as_slice(start..end),
                 },
             };
-            originals.push(substr);
+            originals.push(Cow::Borrowed(substr));
 
             // Insert lines from text2
             let range = script.new.clone();
             for line in lines2.get(range.clone()).into_iter().flatten() {
-                lines.push(Line::Insert(*line));
+                lines.push(Line::Insert(Cow::Borrowed(*line)));
             }
             let substr = match new_pos.get(range.start) {
                 None => modified.as_slice(0..0),
@@ -291,7 +650,7 @@ fn to_hunks<'a, T: ?Sized + SliceLike>(
                     Some(&end) => modified.as_slice(start..end),
                 },
             };
-            modifieds.push(substr);
+            modifieds.push(Cow::Borrowed(substr));
 
             if let Some(s) = edit_script.get(idx + 1) {
                 // Check to see if we can merge the hunks
@@ -302,7 +661,7 @@ fn to_hunks<'a, T: ?Sized + SliceLike>(
                     for (_i1, i2) in (script.old.end..s.old.start).zip(script.new.end..s.new.start)
                     {
                         if let Some(line) = lines2.get(i2) {
-                            lines.push(Line::Context(*line));
+                            lines.push(Line::Context(Cow::Borrowed(*line)));
                         }
                     }
 
@@ -328,7 +687,7 @@ fn to_hunks<'a, T: ?Sized + SliceLike>(
 
         // Post-context
         for line in lines2.get(script.new.end..end2).into_iter().flatten() {
-            lines.push(Line::Context(*line));
+            lines.push(Line::Context(Cow::Borrowed(*line)));
         }
 
         let len1 = end1 - start1;
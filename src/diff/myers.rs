@@ -1,5 +1,8 @@
 use crate::range::{DiffRange, Range};
-use std::ops::{Index, IndexMut};
+use std::{
+    cmp,
+    ops::{Index, IndexMut},
+};
 
 // A D-path is a path which starts at (0,0) that has exactly D non-diagonal edges. All D-paths
 // consist of a (D - 1)-path followed by a non-diagonal edge and then a possibly empty sequence of
@@ -77,11 +80,16 @@ fn max_d(len1: usize, len2: usize) -> usize {
 // D-path. The idea for doing so is to simultaneously run the basic algorithm in both the
 // forward and reverse directions until furthest reaching forward and reverse paths starting at
 // opposing corners 'overlap'.
+//
+// If `max_cost` is set and the search reaches that many edits without the paths overlapping, the
+// search is abandoned and the input is split at the end of the furthest reaching path instead,
+// trading minimality for a bound on the running time (see `heuristic_split`).
 fn find_middle_snake<T: PartialEq>(
     old: Range<'_, [T]>,
     new: Range<'_, [T]>,
     vf: &mut V,
     vb: &mut V,
+    max_cost: Option<usize>,
 ) -> (isize, Snake) {
     let n = old.len();
     let m = new.len();
@@ -176,71 +184,155 @@ fn find_middle_snake<T: PartialEq>(
             }
         }
 
-        // TODO: Maybe there's an opportunity to optimize and bail early?
+        if max_cost.map_or(false, |max_cost| d as usize >= max_cost) {
+            if let Some(snake) = heuristic_split(n, m, d, vf, vb) {
+                // The true edit distance is at least this large
+                return (2 * d + 1, snake);
+            }
+        }
     }
 
     unreachable!("unable to find a middle snake");
 }
 
+// Similar to the `too_expensive` heuristic used by git's xdiff, picks whichever of the forward and
+// backward D-paths has made the most progress through the edit graph and returns an empty snake
+// at its end. Returns `None` if no path ends strictly inside the graph, which would result in a
+// split that doesn't make any progress.
+fn heuristic_split(n: usize, m: usize, d: isize, vf: &V, vb: &V) -> Option<Snake> {
+    // The furthest reaching point of `v` along with how far it is from its starting corner. Points
+    // which lie outside of the edit graph are skipped.
+    let furthest = |v: &V| {
+        (-d..=d)
+            .step_by(2)
+            .filter_map(|k| {
+                let x = v[k];
+                let y = x as isize - k;
+                if x <= n && y >= 0 && y as usize <= m {
+                    Some((x + y as usize, x, y as usize))
+                } else {
+                    None
+                }
+            })
+            .max()
+    };
+
+    // Convert the backward point to forward coordinates
+    let backward = furthest(vb).map(|(progress, x, y)| (progress, n - x, m - y));
+    let (_, x, y) = cmp::max(furthest(vf), backward)?;
+
+    if x + y == 0 || x + y == n + m {
+        return None;
+    }
+
+    Some(Snake {
+        x_start: x,
+        y_start: y,
+        x_end: x,
+        y_end: y,
+    })
+}
+
+// The pieces of work left to do by `conquer`
+enum Step<'a, 'b, T> {
+    // Diff a pair of regions
+    Conquer(Range<'a, [T]>, Range<'b, [T]>),
+    // Append a common suffix which was split off of a region before it was diffed
+    Suffix(DiffRange<'a, 'b, [T]>),
+}
+
 fn conquer<'a, 'b, T: PartialEq>(
-    mut old: Range<'a, [T]>,
-    mut new: Range<'b, [T]>,
+    old: Range<'a, [T]>,
+    new: Range<'b, [T]>,
     vf: &mut V,
     vb: &mut V,
+    max_cost: Option<usize>,
     solution: &mut Vec<DiffRange<'a, 'b, [T]>>,
 ) {
-    // Check for common prefix
-    let common_prefix_len = old.common_prefix_len(new);
-    if common_prefix_len > 0 {
-        let common_prefix = DiffRange::Equal(
-            old.slice(..common_prefix_len),
-            new.slice(..common_prefix_len),
-        );
-        solution.push(common_prefix);
-    }
+    // An explicit stack is used rather than recursion since, when `max_cost` is hit, a split may
+    // only peel a few lines off of a region, leading to very deep recursion on large inputs
+    let mut stack = vec![Step::Conquer(old, new)];
+    while let Some(step) = stack.pop() {
+        let (mut old, mut new) = match step {
+            Step::Conquer(old, new) => (old, new),
+            Step::Suffix(common_suffix) => {
+                solution.push(common_suffix);
+                continue;
+            }
+        };
+
+        // Check for common prefix
+        let common_prefix_len = old.common_prefix_len(new);
+        if common_prefix_len > 0 {
+            let common_prefix = DiffRange::Equal(
+                old.slice(..common_prefix_len),
+                new.slice(..common_prefix_len),
+            );
+            solution.push(common_prefix);
+        }
 
-    old = old.slice(common_prefix_len..old.len());
-    new = new.slice(common_prefix_len..new.len());
+        old = old.slice(common_prefix_len..old.len());
+        new = new.slice(common_prefix_len..new.len());
 
-    // Check for common suffix
-    let common_suffix_len = old.common_suffix_len(new);
-    let common_suffix = DiffRange::Equal(
-        old.slice(old.len() - common_suffix_len..),
-        new.slice(new.len() - common_suffix_len..),
-    );
-    old = old.slice(..old.len() - common_suffix_len);
-    new = new.slice(..new.len() - common_suffix_len);
-
-    if old.is_empty() && new.is_empty() {
-        // Do nothing
-    } else if old.is_empty() {
-        // Inserts
-        solution.push(DiffRange::Insert(new));
-    } else if new.is_empty() {
-        // Deletes
-        solution.push(DiffRange::Delete(old));
-    } else {
-        // Divide & Conquer
-        let (_shortest_edit_script_len, snake) = find_middle_snake(old, new, vf, vb);
-
-        let (old_a, old_b) = old.split_at(snake.x_start);
-        let (new_a, new_b) = new.split_at(snake.y_start);
-
-        conquer(old_a, new_a, vf, vb, solution);
-        conquer(old_b, new_b, vf, vb, solution);
-    }
+        // Check for common suffix
+        let common_suffix_len = old.common_suffix_len(new);
+        let common_suffix = DiffRange::Equal(
+            old.slice(old.len() - common_suffix_len..),
+            new.slice(new.len() - common_suffix_len..),
+        );
+        old = old.slice(..old.len() - common_suffix_len);
+        new = new.slice(..new.len() - common_suffix_len);
+
+        if common_suffix_len > 0 {
+            stack.push(Step::Suffix(common_suffix));
+        }
 
-    if common_suffix_len > 0 {
-        solution.push(common_suffix);
+        if old.is_empty() && new.is_empty() {
+            // Do nothing
+        } else if old.is_empty() {
+            // Inserts
+            solution.push(DiffRange::Insert(new));
+        } else if new.is_empty() {
+            // Deletes
+            solution.push(DiffRange::Delete(old));
+        } else {
+            // Divide & Conquer
+            let (_shortest_edit_script_len, snake) = find_middle_snake(old, new, vf, vb, max_cost);
+
+            let (old_a, old_b) = old.split_at(snake.x_start);
+            let (new_a, new_b) = new.split_at(snake.y_start);
+
+            stack.push(Step::Conquer(old_b, new_b));
+            stack.push(Step::Conquer(old_a, new_a));
+        }
     }
 }
 
-pub fn diff<'a, 'b, T: PartialEq>(old: &'a [T], new: &'b [T]) -> Vec<DiffRange<'a, 'b, [T]>> {
-    let old_recs = Range::new(old, ..);
-    let new_recs = Range::new(new, ..);
-
+/// Diff `old` and `new`, finding a minimal diff unless `max_cost` is set, in which case the search
+/// for the middle snake of any region which costs more than `max_cost` edits is cut short.
+pub fn diff<'a, 'b, T: PartialEq>(
+    old: &'a [T],
+    new: &'b [T],
+    max_cost: Option<usize>,
+) -> Vec<DiffRange<'a, 'b, [T]>> {
     let mut solution = Vec::new();
+    diff_range(
+        Range::new(old, ..),
+        Range::new(new, ..),
+        max_cost,
+        &mut solution,
+    );
+    solution
+}
 
+// Diffs a portion of two slices, appending the result to `solution`. This is used by the other
+// algorithms for the regions they aren't able to handle themselves.
+pub(crate) fn diff_range<'a, 'b, T: PartialEq>(
+    old: Range<'a, [T]>,
+    new: Range<'b, [T]>,
+    max_cost: Option<usize>,
+    solution: &mut Vec<DiffRange<'a, 'b, [T]>>,
+) {
     // The arrays that hold the 'best possible x values' in search from:
     // `vf`: top left to bottom right
     // `vb`: bottom right to top left
@@ -248,9 +340,7 @@ pub fn diff<'a, 'b, T: PartialEq>(old: &'a [T], new: &'b [T]) -> Vec<DiffRange<'
     let mut vf = V::new(max_d);
     let mut vb = V::new(max_d);
 
-    conquer(old_recs, new_recs, &mut vf, &mut vb, &mut solution);
-
-    solution
+    conquer(old, new, &mut vf, &mut vb, max_cost, solution);
 }
 
 #[cfg(test)]
@@ -264,6 +354,53 @@ mod tests {
         let max_d = max_d(a.len(), b.len());
         let mut vf = V::new(max_d);
         let mut vb = V::new(max_d);
-        find_middle_snake(a, b, &mut vf, &mut vb);
+        find_middle_snake(a, b, &mut vf, &mut vb, None);
+    }
+
+    #[test]
+    fn max_cost() {
+        let old: Vec<u32> = (0..1000).map(|i| i * 7 % 13).collect();
+        let new: Vec<u32> = (0..1000).map(|i| i * 5 % 11).collect();
+
+        let minimal = diff(&old, &new, None);
+        for &max_cost in &[1, 8, 64] {
+            let solution = diff(&old, &new, Some(max_cost));
+
+            let mut reconstructed_old = Vec::new();
+            let mut reconstructed_new = Vec::new();
+            for diff_range in &solution {
+                match diff_range {
+                    DiffRange::Equal(o, n) => {
+                        assert_eq!(o.as_slice(), n.as_slice());
+                        reconstructed_old.extend_from_slice(o.as_slice());
+                        reconstructed_new.extend_from_slice(n.as_slice());
+                    }
+                    DiffRange::Delete(o) => reconstructed_old.extend_from_slice(o.as_slice()),
+                    DiffRange::Insert(n) => reconstructed_new.extend_from_slice(n.as_slice()),
+                }
+            }
+            assert_eq!(reconstructed_old, old);
+            assert_eq!(reconstructed_new, new);
+            assert!(edit_len(&solution) >= edit_len(&minimal));
+        }
+
+        // Inputs which are cheaper to diff than the limit still get a minimal diff
+        let old = b"ABCABBA";
+        let new = b"CBABAC";
+        assert_eq!(
+            edit_len(&diff(old, new, Some(64))),
+            edit_len(&diff(old, new, None))
+        );
+    }
+
+    fn edit_len<T>(solution: &[DiffRange<'_, '_, [T]>]) -> usize {
+        solution
+            .iter()
+            .map(|diff_range| match diff_range {
+                DiffRange::Equal(..) => 0,
+                DiffRange::Delete(o) => o.len(),
+                DiffRange::Insert(n) => n.len(),
+            })
+            .sum()
     }
 }
@@ -0,0 +1,183 @@
+//! Patience diff
+//!
+//! Lines which occur exactly once in both inputs are used as anchors. The
+//! longest sequence of these anchors which appear in the same order in both
+//! inputs is matched up and the regions between them are diffed recursively,
+//! falling back to Myers when a region has no unique lines in common.
+
+use super::{cleanup, myers};
+use crate::range::{DiffRange, Range};
+use std::{cmp::Ordering, collections::HashMap, hash::Hash};
+
+pub fn diff<'a, 'b, T: Eq + Hash>(
+    old: &'a [T],
+    new: &'b [T],
+    max_cost: Option<usize>,
+) -> Vec<DiffRange<'a, 'b, [T]>> {
+    let mut solution = Vec::new();
+    conquer(
+        Range::new(old, ..),
+        Range::new(new, ..),
+        max_cost,
+        &mut solution,
+    );
+    cleanup::merge_adjacent(&mut solution);
+    solution
+}
+
+fn conquer<'a, 'b, T: Eq + Hash>(
+    old: Range<'a, [T]>,
+    new: Range<'b, [T]>,
+    max_cost: Option<usize>,
+    solution: &mut Vec<DiffRange<'a, 'b, [T]>>,
+) {
+    let common_prefix_len = old.common_prefix_len(new);
+    solution.push(DiffRange::Equal(
+        old.slice(..common_prefix_len),
+        new.slice(..common_prefix_len),
+    ));
+    let old = old.slice(common_prefix_len..);
+    let new = new.slice(common_prefix_len..);
+
+    let common_suffix_len = old.common_suffix_len(new);
+    let (old, old_suffix) = old.split_at(old.len() - common_suffix_len);
+    let (new, new_suffix) = new.split_at(new.len() - common_suffix_len);
+
+    if old.is_empty() {
+        solution.push(DiffRange::Insert(new));
+    } else if new.is_empty() {
+        solution.push(DiffRange::Delete(old));
+    } else {
+        let anchors = unique_anchors(old, new);
+        if anchors.is_empty() {
+            myers::diff_range(old, new, max_cost, solution);
+        } else {
+            let (mut old_pos, mut new_pos) = (0, 0);
+            for (old_anchor, new_anchor) in anchors {
+                conquer(
+                    old.slice(old_pos..old_anchor),
+                    new.slice(new_pos..new_anchor),
+                    max_cost,
+                    solution,
+                );
+                solution.push(DiffRange::Equal(
+                    old.slice(old_anchor..old_anchor + 1),
+                    new.slice(new_anchor..new_anchor + 1),
+                ));
+                old_pos = old_anchor + 1;
+                new_pos = new_anchor + 1;
+            }
+            conquer(
+                old.slice(old_pos..),
+                new.slice(new_pos..),
+                max_cost,
+                solution,
+            );
+        }
+    }
+
+    solution.push(DiffRange::Equal(old_suffix, new_suffix));
+}
+
+// Returns the positions of the longest sequence of lines which are unique in both `old` and `new`
+// and appear in the same order in both
+fn unique_anchors<T: Eq + Hash>(old: Range<'_, [T]>, new: Range<'_, [T]>) -> Vec<(usize, usize)> {
+    // (occurrences in old, position in old, occurrences in new, position in new)
+    let mut lines: HashMap<&T, (usize, usize, usize, usize)> = HashMap::new();
+    for (i, line) in old.as_slice().iter().enumerate() {
+        let entry = lines.entry(line).or_default();
+        entry.0 += 1;
+        entry.1 = i;
+    }
+    for (i, line) in new.as_slice().iter().enumerate() {
+        if let Some(entry) = lines.get_mut(line) {
+            entry.2 += 1;
+            entry.3 = i;
+        }
+    }
+
+    let mut unique: Vec<(usize, usize)> = lines
+        .values()
+        .filter(|&&(old_count, _, new_count, _)| old_count == 1 && new_count == 1)
+        .map(|&(_, old_pos, _, new_pos)| (old_pos, new_pos))
+        .collect();
+    unique.sort_unstable();
+
+    longest_increasing_subsequence(&unique)
+}
+
+// Patience sorting: finds the longest subsequence of `pairs`, which are sorted by their first
+// element, whose second elements are also increasing
+fn longest_increasing_subsequence(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // The index into `pairs` of the top card of each pile
+    let mut piles: Vec<usize> = Vec::new();
+    // For each pair, the index of the top card of the previous pile when it was placed
+    let mut predecessors = vec![None; pairs.len()];
+
+    for (i, &(_, new_pos)) in pairs.iter().enumerate() {
+        let pile = piles
+            .binary_search_by(|&top| {
+                if pairs[top].1 < new_pos {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            })
+            .unwrap_err();
+        predecessors[i] = pile.checked_sub(1).map(|prev| piles[prev]);
+        if pile == piles.len() {
+            piles.push(i);
+        } else {
+            piles[pile] = i;
+        }
+    }
+
+    let mut sequence = Vec::with_capacity(piles.len());
+    let mut next = piles.last().copied();
+    while let Some(i) = next {
+        sequence.push(pairs[i]);
+        next = predecessors[i];
+    }
+    sequence.reverse();
+    sequence
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lis() {
+        let pairs = [
+            (0, 9),
+            (1, 4),
+            (2, 6),
+            (3, 12),
+            (4, 8),
+            (5, 7),
+            (6, 1),
+            (7, 3),
+            (8, 2),
+        ];
+        assert_eq!(
+            longest_increasing_subsequence(&pairs),
+            [(1, 4), (2, 6), (5, 7)]
+        );
+        assert!(longest_increasing_subsequence(&[]).is_empty());
+    }
+
+    #[test]
+    fn unique_lines_are_anchors() {
+        let old = ["a", "}", "b", "}", "c"];
+        let new = ["b", "}", "a", "}", "c"];
+        let solution = diff(&old, &new, None);
+        let equal: Vec<_> = solution
+            .iter()
+            .filter_map(|d| match d {
+                DiffRange::Equal(o, _) => Some(o.as_slice()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(equal, [&["b"][..], &["}", "c"][..]]);
+    }
+}
@@ -101,7 +101,7 @@ macro_rules! assert_diff {
 fn test_diff_str() {
     let a = "ABCABBA";
     let b = "CBABAC";
-    let solution = diff(a, b);
+    let solution = diff_chars(a, b);
     assert_diff!(
         [
             Delete("AB"),
@@ -117,7 +117,7 @@ fn test_diff_str() {
 
     let a = "abgdef";
     let b = "gh";
-    let solution = diff(a, b);
+    let solution = diff_chars(a, b);
     assert_diff!(
         [Delete("ab"), Equal("g"), Delete("def"), Insert("h")],
         solution,
@@ -125,7 +125,7 @@ fn test_diff_str() {
 
     let a = "bat";
     let b = "map";
-    let solution = diff(a, b);
+    let solution = diff_chars(a, b);
     assert_diff!(
         [
             Delete("b"),
@@ -139,7 +139,7 @@ fn test_diff_str() {
 
     let a = "ACZBDZ";
     let b = "ACBCBDEFD";
-    let solution = diff(a, b);
+    let solution = diff_chars(a, b);
     assert_diff!(
         [
             Equal("AC"),
@@ -154,17 +154,17 @@ fn test_diff_str() {
 
     let a = "1A ";
     let b = "1A B A 2";
-    let solution = diff(a, b);
+    let solution = diff_chars(a, b);
     assert_diff!([Equal("1A "), Insert("B A 2")], solution);
 
     let a = "ACBD";
     let b = "ACBCBDEFD";
-    let solution = diff(a, b);
+    let solution = diff_chars(a, b);
     assert_diff!([Equal("ACB"), Insert("CBDEF"), Equal("D")], solution);
 
     let a = "abc";
     let b = "def";
-    let solution = diff(a, b);
+    let solution = diff_chars(a, b);
     assert_diff!([Delete("abc"), Insert("def")], solution, "No Equal");
 }
 
@@ -193,7 +193,7 @@ fn test_unicode() {
     let comet = "\u{2604}";
     assert_eq!(snowman.as_bytes()[..2], comet.as_bytes()[..2]);
 
-    let d = diff(snowman, comet);
+    let d = diff_chars(snowman, comet);
     assert_eq!(d, vec![Diff::Delete(snowman), Diff::Insert(comet)]);
 }
 
@@ -331,6 +331,10 @@ macro_rules! assert_patch {
         assert_eq!(patch_str, $expected);
         assert_eq!(patch_bytes, patch_str.as_bytes());
         assert_eq!(patch_bytes, $expected.as_bytes());
+        assert_eq!(Patch::from_str($expected).unwrap(), patch);
+        assert_eq!(Patch::from_str(&patch_str).unwrap(), patch);
+        assert_eq!(Patch::from_bytes($expected.as_bytes()).unwrap(), bpatch);
+        assert_eq!(Patch::from_bytes(&patch_bytes).unwrap(), bpatch);
         assert_eq!(apply($old, &patch).unwrap(), $new);
         assert_eq!(
             crate::apply_bytes($old.as_bytes(), &bpatch).unwrap(),
@@ -576,3 +580,403 @@ void Chunk_copy(Chunk *src, size_t src_start, Chunk *dst, size_t dst_start, size
 ";
     assert_patch!(original, a, expected_diffy);
 }
+
+#[test]
+fn patience_and_histogram() {
+    let original = "\
+#include <stdio.h>
+
+// Frobs foo heartily
+int frobnitz(int foo)
+{
+    int i;
+    for(i = 0; i < 10; i++)
+    {
+        printf(\"Your answer is: \");
+        printf(\"%d\\n\", foo);
+    }
+}
+
+int fact(int n)
+{
+    if(n > 1)
+    {
+        return fact(n-1) * n;
+    }
+    return 1;
+}
+
+int main(int argc, char **argv)
+{
+    frobnitz(fact(10));
+}
+";
+    let modified = "\
+#include <stdio.h>
+
+int fib(int n)
+{
+    if(n > 2)
+    {
+        return fib(n-1) + fib(n-2);
+    }
+    return 1;
+}
+
+// Frobs foo heartily
+int frobnitz(int foo)
+{
+    int i;
+    for(i = 0; i < 10; i++)
+    {
+        printf(\"%d\\n\", foo);
+    }
+}
+
+int main(int argc, char **argv)
+{
+    frobnitz(fib(10));
+}
+";
+
+    // Matches the output of `git diff --patience` and `git diff --histogram`, minus the function
+    // context git adds to the second hunk header
+    let expected = "\
+--- original
++++ modified
+@@ -1,5 +1,14 @@
+ #include <stdio.h>
+
++int fib(int n)
++{
++    if(n > 2)
++    {
++        return fib(n-1) + fib(n-2);
++    }
++    return 1;
++}
++
+ // Frobs foo heartily
+ int frobnitz(int foo)
+ {
+@@ -6,21 +15,11 @@
+     int i;
+     for(i = 0; i < 10; i++)
+     {
+-        printf(\"Your answer is: \");
+         printf(\"%d\\n\", foo);
+     }
+ }
+
+-int fact(int n)
+-{
+-    if(n > 1)
+-    {
+-        return fact(n-1) * n;
+-    }
+-    return 1;
+-}
+-
+ int main(int argc, char **argv)
+ {
+-    frobnitz(fact(10));
++    frobnitz(fib(10));
+ }
+";
+    assert_patch!(
+        DiffOptions::new().set_algorithm(Algorithm::Patience),
+        original,
+        modified,
+        expected
+    );
+    assert_patch!(
+        DiffOptions::new().set_algorithm(Algorithm::Histogram),
+        original,
+        modified,
+        expected
+    );
+
+    // Myers finds a smaller diff, but lines up the wrong functions
+    let patch = DiffOptions::new()
+        .set_algorithm(Algorithm::Myers)
+        .create_patch(original, modified);
+    assert_ne!(patch.to_string(), expected);
+    assert_eq!(apply(original, &patch).unwrap(), modified);
+}
+
+#[test]
+fn histogram_prefers_unique_lines() {
+    let original = "fn a() {\n}\n}\n}\nunique();\n";
+    let modified = "unique();\n}\n}\n}\nfn b() {\n";
+
+    // Matches the output of `git diff --histogram`, which lines up the unique line rather than the
+    // longer run of closing braces
+    let expected = "\
+--- original
++++ modified
+@@ -1,5 +1,5 @@
+-fn a() {
+-}
+-}
+-}
+ unique();
++}
++}
++}
++fn b() {
+";
+    assert_patch!(
+        DiffOptions::new().set_algorithm(Algorithm::Histogram),
+        original,
+        modified,
+        expected
+    );
+}
+
+#[test]
+fn algorithms_agree_on_simple_inputs() {
+    let inputs = [
+        ("", ""),
+        ("", "a\nb\n"),
+        ("a\nb\n", ""),
+        ("a\nb\nc\n", "a\nb\nc\n"),
+        ("a\nb\nc\n", "a\nx\nc\n"),
+        ("a\na\na\n", "a\na\n"),
+        ("a\nb\na\nb\n", "b\na\nb\na\n"),
+        ("x\ny\nz", "x\nz\ny"),
+    ];
+    for algorithm in &[Algorithm::Myers, Algorithm::Patience, Algorithm::Histogram] {
+        for (original, modified) in &inputs {
+            let patch = DiffOptions::new()
+                .set_algorithm(*algorithm)
+                .create_patch(original, modified);
+            assert_eq!(
+                apply(original, &patch).unwrap(),
+                *modified,
+                "{:?}\n{}",
+                algorithm,
+                patch
+            );
+        }
+    }
+}
+
+#[test]
+fn max_cost() {
+    let original: String = (0..2000).map(|i| format!("{}\n", i * 7 % 13)).collect();
+    let modified: String = (0..2000).map(|i| format!("{}\n", i * 5 % 11)).collect();
+
+    for algorithm in &[Algorithm::Myers, Algorithm::Patience, Algorithm::Histogram] {
+        let patch = DiffOptions::new()
+            .set_algorithm(*algorithm)
+            .set_max_cost(Some(100))
+            .create_patch(&original, &modified);
+        assert_eq!(
+            apply(&original, &patch).unwrap(),
+            modified,
+            "{:?}",
+            algorithm
+        );
+    }
+
+    // A limit which isn't reached has no effect
+    let original = "a\nb\nc\nd\n";
+    let modified = "a\nx\nc\ny\n";
+    assert_eq!(
+        DiffOptions::new()
+            .set_max_cost(Some(100))
+            .create_patch(original, modified),
+        create_patch(original, modified)
+    );
+}
+
+#[test]
+fn whitespace() {
+    let original = "\
+fn main() {
+    let x = 1;
+    let  y = 2;
+    println!(\"{}\", x + y);
+}
+";
+    let modified = "\
+fn main() {
+    let x = 1;   
+    let y = 2;
+    println!(\"{}\",x+y);\r
+}
+";
+
+    let patch = |whitespace| {
+        DiffOptions::new()
+            .set_whitespace(whitespace)
+            .create_patch(original, modified)
+            .to_string()
+    };
+
+    assert_eq!(
+        patch(Whitespace::Exact),
+        create_patch(original, modified).to_string()
+    );
+
+    let expected = "\
+--- original
++++ modified
+@@ -1,5 +1,5 @@
+ fn main() {
+     let x = 1;   
+-    let  y = 2;
+-    println!(\"{}\", x + y);
++    let y = 2;
++    println!(\"{}\",x+y);\r
+ }
+";
+    assert_eq!(patch(Whitespace::IgnoreAtEol), expected);
+
+    let expected = "\
+--- original
++++ modified
+@@ -1,5 +1,5 @@
+ fn main() {
+     let x = 1;   
+     let y = 2;
+-    println!(\"{}\", x + y);
++    println!(\"{}\",x+y);\r
+ }
+";
+    assert_eq!(patch(Whitespace::IgnoreChange), expected);
+
+    assert_eq!(patch(Whitespace::IgnoreAll), "--- original\n+++ modified\n");
+
+    // Blank lines which differ only in whitespace are equal when ignoring trailing whitespace
+    let patch = DiffOptions::new()
+        .set_whitespace(Whitespace::IgnoreAtEol)
+        .create_patch_bytes(b"a\n\nb\n", b"a\n  \r\nb\n");
+    assert!(patch.hunks().is_empty());
+}
+
+#[test]
+fn ignore_blank_lines() {
+    let original = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+    let modified = "a\n\nb\nc\nd\ne\nf\ng\nh\nI\nj\n\n";
+
+    let expected = "\
+--- original
++++ modified
+@@ -6,5 +7,6 @@
+ f
+ g
+ h
+-i
++I
+ j
++
+";
+    let patch = DiffOptions::new()
+        .set_ignore_blank_lines(true)
+        .create_patch(original, modified);
+    assert_eq!(patch.to_string(), expected);
+    assert_eq!(create_patch(original, modified).hunks().len(), 2);
+
+    let patch = DiffOptions::new()
+        .set_ignore_blank_lines(true)
+        .create_patch("a\nb\n", "a\n\t\nb\n\n");
+    assert!(patch.hunks().is_empty());
+}
+
+#[test]
+fn test_diff_lines_and_words() {
+    let original = "The quick brown fox\njumps over\nthe lazy dog\n";
+    let modified = "The quick red fox\njumps over\nthe lazy dog";
+
+    let solution = diff_lines(original, modified);
+    assert_diff!(
+        [
+            Delete("The quick brown fox\n"),
+            Insert("The quick red fox\n"),
+            Equal("jumps over\n"),
+            Delete("the lazy dog\n"),
+            Insert("the lazy dog"),
+        ],
+        solution,
+    );
+
+    let solution = DiffOptions::new()
+        .set_whitespace(Whitespace::IgnoreAtEol)
+        .diff_lines(original, modified);
+    assert_diff!(
+        [
+            Delete("The quick brown fox\n"),
+            Insert("The quick red fox\n"),
+            Equal("jumps over\nthe lazy dog\n"),
+        ],
+        solution,
+    );
+
+    let solution = diff_words(original, modified);
+    assert_diff!(
+        [
+            Equal("The quick "),
+            Delete("brown"),
+            Insert("red"),
+            Equal(" fox\njumps over\nthe lazy dog"),
+            Delete("\n"),
+        ],
+        solution,
+    );
+
+    assert!(diff_words("", "").is_empty());
+    let solution = diff_words("", "word");
+    assert_diff!([Insert("word")], solution);
+}
+
+#[test]
+fn test_diff_slices() {
+    #[derive(Debug, PartialEq, Eq, Hash)]
+    struct Record<'a> {
+        name: &'a str,
+        age: u32,
+    }
+
+    let original = [
+        Record { name: "a", age: 1 },
+        Record { name: "b", age: 2 },
+        Record { name: "c", age: 3 },
+    ];
+    let modified = [
+        Record { name: "a", age: 1 },
+        Record { name: "b", age: 20 },
+        Record { name: "c", age: 3 },
+        Record { name: "d", age: 4 },
+    ];
+
+    let expected = [
+        SliceDiff::Equal(0..1, 0..1),
+        SliceDiff::Delete(1..2),
+        SliceDiff::Insert(1..2),
+        SliceDiff::Equal(2..3, 2..3),
+        SliceDiff::Insert(3..4),
+    ];
+    assert_eq!(diff_slices(&original, &modified), expected);
+    for algorithm in &[Algorithm::Patience, Algorithm::Histogram] {
+        assert_eq!(
+            DiffOptions::new()
+                .set_algorithm(*algorithm)
+                .diff_slices(&original, &modified),
+            expected
+        );
+    }
+
+    // Compaction slides the insertion to after the existing run of equal items
+    assert_eq!(
+        diff_slices(&[1, 2, 2, 3], &[1, 2, 2, 2, 3]),
+        [
+            SliceDiff::Equal(0..3, 0..3),
+            SliceDiff::Insert(3..4),
+            SliceDiff::Equal(3..4, 4..5),
+        ]
+    );
+
+    assert!(diff_slices::<u8>(&[], &[]).is_empty());
+    assert_eq!(diff_slices(&[], &[1]), [SliceDiff::Insert(0..1)]);
+}
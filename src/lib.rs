@@ -180,7 +180,7 @@
 //!
 //! let original = "The Final Empire\nThe Well of Ascension\nThe hero of ages\n";
 //! let a = "The Final Empire\nThe Well of Ascension\nThe Hero of Ages\nSecret History\n";
-//! let b = "The Final Empire\nThe Well of Ascension\nThe hero of ages\nThe Alloy of Law\n";
+//! let b = "The Final Empire\nThe Well of Ascension\nThe hero of AGES\nThe Alloy of Law\n";
 //! let expected = "\
 //! The Final Empire
 //! The Well of Ascension
@@ -190,7 +190,7 @@
 //! ||||||| original
 //! The hero of ages
 //! =======
-//! The hero of ages
+//! The hero of AGES
 //! The Alloy of Law
 //! >>>>>>> theirs
 //! ";
@@ -217,9 +217,21 @@ mod diff;
 mod merge;
 mod patch;
 mod range;
+pub mod token;
 mod utils;
 
-pub use apply::{apply, apply_bytes, ApplyError};
-pub use diff::{create_patch, create_patch_bytes, DiffOptions};
-pub use merge::{merge, merge_bytes, ConflictStyle, MergeOptions};
-pub use patch::{Hunk, HunkRange, Line, Patch, PatchFormatter};
+pub use apply::{
+    apply, apply_bytes, apply_patch_set, unapply, unapply_bytes, AlreadyApplied, Applied,
+    AppliedFile, ApplyError, ApplyOptions, ClosestMatch, FileSystem, HunkReport, MemoryFs,
+    PartiallyApplied, PatchSetError, PatchSetErrorKind, StdFs,
+};
+pub use diff::{
+    create_binary_patch, create_patch, create_patch_bytes, diff_chars, diff_lines, diff_slices,
+    diff_words, Algorithm, Diff, DiffOptions, SliceDiff, Whitespace,
+};
+pub use merge::{merge, merge_bytes, merge_slices, ConflictStyle, MergeChunk, MergeOptions};
+pub use patch::{
+    BinaryHunk, BinaryHunkKind, BinaryPatch, BuildPatchError, ComposePatchError, ExtendedHeader,
+    GitHeader, Hunk, HunkBuilder, HunkRange, Line, ParsePatchError, Patch, PatchBuilder,
+    PatchFormatter, PatchSet, RebasePatchError,
+};
@@ -1,9 +1,10 @@
 use crate::{
-    diff::DiffOptions,
+    diff::{Algorithm, DiffOptions, Whitespace},
     range::{DiffRange, Range, SliceLike},
+    token::{GroupTokenizer, Tokenizer},
     utils::Classifier,
 };
-use std::{cmp, fmt};
+use std::{cmp, fmt, hash::Hash, sync::Arc};
 
 #[cfg(test)]
 mod tests;
@@ -112,11 +113,39 @@ pub enum ConflictStyle {
     Diff3,
 }
 
+/// A region of a 3-way merge of slices, as returned by
+/// [`MergeOptions::merge_slices`]
+#[derive(Debug, PartialEq, Eq)]
+pub enum MergeChunk<'a, T> {
+    /// Items which were merged cleanly
+    Resolved(&'a [T]),
+    /// Items which both sides changed in different ways
+    Conflict {
+        /// The items from the common ancestor
+        ancestor: &'a [T],
+        /// Our version of the items
+        ours: &'a [T],
+        /// Their version of the items
+        theirs: &'a [T],
+    },
+}
+
+impl<T> Copy for MergeChunk<'_, T> {}
+
+impl<T> Clone for MergeChunk<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
 /// A collection of options for modifying the way a merge is performed
 #[derive(Debug)]
 pub struct MergeOptions {
     conflict_marker_length: usize,
     style: ConflictStyle,
+    algorithm: Algorithm,
+    whitespace: Whitespace,
+    tokenizer: Arc<dyn Tokenizer>,
 }
 
 impl MergeOptions {
@@ -125,10 +154,16 @@ impl MergeOptions {
     /// ## Defaults
     /// * conflict_marker_length = 7
     /// * style = ConflictStyle::Diff3
+    /// * algorithm = Algorithm::Myers
+    /// * whitespace = Whitespace::Exact
+    /// * tokenizer = GroupTokenizer::default()
     pub fn new() -> Self {
         Self {
             conflict_marker_length: DEFAULT_CONFLICT_MARKER_LENGTH,
             style: ConflictStyle::Diff3,
+            algorithm: Algorithm::default(),
+            whitespace: Whitespace::default(),
+            tokenizer: Arc::new(GroupTokenizer::default()),
         }
     }
 
@@ -144,6 +179,41 @@ impl MergeOptions {
         self
     }
 
+    /// Set the algorithm used to diff each file against the common ancestor
+    pub fn set_algorithm(&mut self, algorithm: Algorithm) -> &mut Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Set how whitespace is treated when comparing lines
+    ///
+    /// When whitespace is ignored, lines which one side only reformatted are
+    /// treated as unchanged so they don't conflict with changes made by the
+    /// other side. Where neither side made a change that isn't ignored, our
+    /// version of the lines is used, like git's `-Xignore-space-change`.
+    pub fn set_whitespace(&mut self, whitespace: Whitespace) -> &mut Self {
+        self.whitespace = whitespace;
+        self
+    }
+
+    /// Set the tokenizer used to split conflicting lines into words
+    ///
+    /// When both sides change the same lines, the lines are diffed word by word
+    /// and the conflict is resolved if the sides changed different words.
+    /// This only applies to [`merge`](Self::merge), not to
+    /// [`merge_bytes`](Self::merge_bytes).
+    pub fn set_tokenizer<T: Tokenizer + 'static>(&mut self, tokenizer: T) -> &mut Self {
+        self.tokenizer = Arc::new(tokenizer);
+        self
+    }
+
+    fn diff_options(&self) -> DiffOptions {
+        let mut opts = DiffOptions::new();
+        opts.set_algorithm(self.algorithm)
+            .set_shared_tokenizer(self.tokenizer.clone());
+        opts
+    }
+
     /// Merge two files, given a common ancestor, based on the configured options
     pub fn merge<'a>(
         &self,
@@ -151,12 +221,12 @@ impl MergeOptions {
         ours: &'a str,
         theirs: &'a str,
     ) -> Result<String, String> {
-        let mut classifier = Classifier::default();
-        let (ancestor_lines, ancestor_ids, ancestor_pos) = classifier.classify_lines(ancestor);
+        let mut classifier = Classifier::with_whitespace(self.whitespace);
+        let (_, ancestor_ids, ancestor_pos) = classifier.classify_lines(ancestor);
         let (our_lines, our_ids, our_pos) = classifier.classify_lines(ours);
         let (their_lines, their_ids, their_pos) = classifier.classify_lines(theirs);
 
-        let opts = DiffOptions::default();
+        let opts = self.diff_options();
         let our_solution = opts.diff_slice(&ancestor_ids, &our_ids);
         let their_solution = opts.diff_slice(&ancestor_ids, &their_ids);
 
@@ -166,15 +236,15 @@ impl MergeOptions {
         cleanup_conflicts(&mut merge);
 
         output_result(
-            &ancestor_lines,
             &our_lines,
             &their_lines,
             &merge,
             self.conflict_marker_length,
             self.style,
-            &ancestor,
-            &ours,
-            &theirs,
+            &opts,
+            ancestor,
+            ours,
+            theirs,
             &ancestor_pos,
             &our_pos,
             &their_pos,
@@ -182,18 +252,22 @@ impl MergeOptions {
     }
 
     /// Perform a 3-way merge between potentially non-utf8 texts
+    ///
+    /// Conflicting lines aren't split into words, so the tokenizer set with
+    /// [`set_tokenizer`](Self::set_tokenizer) isn't used and overlapping
+    /// changes are always reported as conflicts.
     pub fn merge_bytes<'a>(
         &self,
         ancestor: &'a [u8],
         ours: &'a [u8],
         theirs: &'a [u8],
     ) -> Result<Vec<u8>, Vec<u8>> {
-        let mut classifier = Classifier::default();
+        let mut classifier = Classifier::with_whitespace(self.whitespace);
         let (ancestor_lines, ancestor_ids, _) = classifier.classify_lines(ancestor);
         let (our_lines, our_ids, _) = classifier.classify_lines(ours);
         let (their_lines, their_ids, _) = classifier.classify_lines(theirs);
 
-        let opts = DiffOptions::default();
+        let opts = self.diff_options();
         let our_solution = opts.diff_slice(&ancestor_ids, &our_ids);
         let their_solution = opts.diff_slice(&ancestor_ids, &their_ids);
 
@@ -211,6 +285,84 @@ impl MergeOptions {
             self.style,
         )
     }
+
+    /// Merge two slices of arbitrary items, given a common ancestor, based on
+    /// the configured options
+    ///
+    /// Returns the merged items upon a successful merge. If there were
+    /// conflicts, the merge is returned as a list of chunks so the conflicts
+    /// can be resolved by the caller. Only the diff algorithm applies, the
+    /// remaining options are specific to text.
+    ///
+    /// ```
+    /// use diffy::{MergeChunk, MergeOptions};
+    ///
+    /// let ancestor = [1, 2, 3];
+    /// let ours = [0, 1, 2, 3];
+    /// let theirs = [1, 2, 3, 4];
+    /// let merged = MergeOptions::new().merge_slices(&ancestor, &ours, &theirs);
+    /// assert_eq!(merged.unwrap(), [&0, &1, &2, &3, &4]);
+    ///
+    /// let theirs = [5, 2, 3];
+    /// let merged = MergeOptions::new().merge_slices(&ancestor, &ours, &theirs);
+    /// assert_eq!(
+    ///     merged.unwrap_err(),
+    ///     [
+    ///         MergeChunk::Conflict {
+    ///             ancestor: &[1][..],
+    ///             ours: &[0, 1][..],
+    ///             theirs: &[5][..],
+    ///         },
+    ///         MergeChunk::Resolved(&[2, 3][..]),
+    ///     ]
+    /// );
+    /// ```
+    pub fn merge_slices<'a, T: Eq + Hash>(
+        &self,
+        ancestor: &'a [T],
+        ours: &'a [T],
+        theirs: &'a [T],
+    ) -> Result<Vec<&'a T>, Vec<MergeChunk<'a, T>>> {
+        let opts = self.diff_options();
+        let our_solution = opts.diff_slice(ancestor, ours);
+        let their_solution = opts.diff_slice(ancestor, theirs);
+
+        let merged = merge_solutions(&our_solution, &their_solution);
+        let mut merge = diff3_range_to_merge_range(&merged);
+
+        cleanup_conflicts(&mut merge);
+
+        let chunks: Vec<_> = merge
+            .into_iter()
+            .map(|merge_range| match merge_range {
+                MergeRange::Equal(_, range, _)
+                | MergeRange::Ours(range)
+                | MergeRange::Both(range, _) => MergeChunk::Resolved(range.as_slice()),
+                MergeRange::Theirs(range) => MergeChunk::Resolved(range.as_slice()),
+                MergeRange::Conflict(ancestor, ours, theirs) => MergeChunk::Conflict {
+                    ancestor: ancestor.as_slice(),
+                    ours: ours.as_slice(),
+                    theirs: theirs.as_slice(),
+                },
+            })
+            .filter(|chunk| !matches!(chunk, MergeChunk::Resolved(items) if items.is_empty()))
+            .collect();
+
+        if chunks
+            .iter()
+            .any(|chunk| matches!(chunk, MergeChunk::Conflict { .. }))
+        {
+            Err(chunks)
+        } else {
+            Ok(chunks
+                .into_iter()
+                .flat_map(|chunk| match chunk {
+                    MergeChunk::Resolved(items) => items,
+                    MergeChunk::Conflict { .. } => unreachable!(),
+                })
+                .collect())
+        }
+    }
 }
 
 impl Default for MergeOptions {
@@ -283,6 +435,17 @@ pub fn merge_bytes<'a>(
     MergeOptions::default().merge_bytes(ancestor, ours, theirs)
 }
 
+/// Perform a 3-way merge between slices of arbitrary items
+///
+/// See [`MergeOptions::merge_slices`] for details.
+pub fn merge_slices<'a, T: Eq + Hash>(
+    ancestor: &'a [T],
+    ours: &'a [T],
+    theirs: &'a [T],
+) -> Result<Vec<&'a T>, Vec<MergeChunk<'a, T>>> {
+    MergeOptions::default().merge_slices(ancestor, ours, theirs)
+}
+
 fn merge_solutions<'ancestor, 'ours, 'theirs, T: ?Sized + SliceLike>(
     our_solution: &[DiffRange<'ancestor, 'ours, T>],
     their_solution: &[DiffRange<'ancestor, 'theirs, T>],
@@ -495,7 +658,7 @@ fn cleanup_conflicts<'ancestor, 'ours, 'theirs, T: ?Sized + SliceLike + PartialE
 fn get_entire_orig_string<'a>(
     range: std::ops::Range<usize>,
     orig: &'a str,
-    positions: &Vec<usize>,
+    positions: &[usize],
 ) -> &'a str {
     match positions.get(range.start) {
         None => orig.as_slice(0..0),
@@ -506,27 +669,29 @@ fn get_entire_orig_string<'a>(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn output_result<'a, T: ?Sized>(
-    ancestor: &[&'a str],
     ours: &[&'a str],
     theirs: &[&'a str],
     merge: &[MergeRange<T>],
     marker_len: usize,
     style: ConflictStyle,
+    opts: &DiffOptions,
     ancestor_orig: &'a str,
     ours_orig: &'a str,
     theirs_orig: &'a str,
-    ancestor_pos: &Vec<usize>,
-    ours_pos: &Vec<usize>,
-    theirs_pos: &Vec<usize>,
+    ancestor_pos: &[usize],
+    ours_pos: &[usize],
+    theirs_pos: &[usize],
 ) -> Result<String, String> {
     let mut conflicts = 0;
     let mut output = String::new();
 
     for merge_range in merge {
         match merge_range {
-            MergeRange::Equal(range, ..) => {
-                output.extend(ancestor[range.range()].iter().copied());
+            // Use our lines rather than the ancestor's since they may differ in whitespace
+            MergeRange::Equal(_, range, _) => {
+                output.extend(ours[range.range()].iter().copied());
             }
             MergeRange::Conflict(ancestor_range, ours_range, theirs_range) => {
                 let ancestor_str =
@@ -536,21 +701,20 @@ fn output_result<'a, T: ?Sized>(
                     get_entire_orig_string(theirs_range.range(), theirs_orig, theirs_pos);
 
                 let mut classifier = Classifier::default();
-                let (ancestor, ancestor_ids) = classifier.classify_groups(ancestor_str);
-                let (ours, ours_ids) = classifier.classify_groups(ours_str);
-                let (theirs, theirs_ids) = classifier.classify_groups(theirs_str);
+                let (ancestor, ancestor_ids) =
+                    classifier.classify_groups(ancestor_str, opts.tokenizer());
+                let (ours, ours_ids) = classifier.classify_groups(ours_str, opts.tokenizer());
+                let (theirs, theirs_ids) = classifier.classify_groups(theirs_str, opts.tokenizer());
 
-                let opts = DiffOptions::default();
                 let our_solution = opts.diff_slice(&ancestor_ids, &ours_ids);
                 let their_solution = opts.diff_slice(&ancestor_ids, &theirs_ids);
 
                 let merged = merge_solutions(&our_solution, &their_solution);
                 let mut merge = diff3_range_to_merge_range(&merged);
                 cleanup_conflicts(&mut merge);
-                let has_conflicts = merge.iter().any(|r| match r {
-                    MergeRange::Conflict(_, _, _) => true,
-                    _ => false,
-                });
+                let has_conflicts = merge
+                    .iter()
+                    .any(|r| matches!(r, MergeRange::Conflict(_, _, _)));
 
                 if !has_conflicts {
                     for merge_range in merge {
@@ -637,8 +801,8 @@ fn output_result_bytes<'a, T: ?Sized>(
 
     for merge_range in merge {
         match merge_range {
-            MergeRange::Equal(range, ..) => {
-                ancestor[range.range()]
+            MergeRange::Equal(_, range, _) => {
+                ours[range.range()]
                     .iter()
                     .for_each(|line| output.extend_from_slice(line));
             }
@@ -1,4 +1,5 @@
 use super::*;
+use crate::token::Tokenizer;
 
 macro_rules! assert_merge {
     ($original:ident, $ours:ident, $theirs:ident, $kind:ident($expected:expr), $msg:literal $(,)?) => {
@@ -343,3 +344,178 @@ class Call(models.Model):
 
     assert_merge!(base, ours, theirs, Ok(expected), "MergeRange::Both case");
 }
+
+#[test]
+fn merge_with_algorithm() {
+    let original = "\
+fn a() {
+    1
+}
+
+fn b() {
+    2
+}
+";
+    let ours = "\
+fn c() {
+    3
+}
+
+fn a() {
+    1
+}
+
+fn b() {
+    2
+}
+";
+    let theirs = "\
+fn a() {
+    1
+}
+
+fn b() {
+    20
+}
+";
+    let expected = "\
+fn c() {
+    3
+}
+
+fn a() {
+    1
+}
+
+fn b() {
+    20
+}
+";
+
+    for algorithm in &[Algorithm::Myers, Algorithm::Patience, Algorithm::Histogram] {
+        let mut options = MergeOptions::new();
+        options.set_algorithm(*algorithm);
+        assert_eq!(
+            options.merge(original, ours, theirs).unwrap(),
+            expected,
+            "{:?}",
+            algorithm
+        );
+        assert_eq!(
+            options
+                .merge_bytes(original.as_bytes(), ours.as_bytes(), theirs.as_bytes())
+                .unwrap(),
+            expected.as_bytes(),
+            "{:?}",
+            algorithm
+        );
+    }
+}
+
+#[test]
+fn merge_ignoring_whitespace() {
+    let original = "\
+if x {
+    a();
+    b();
+}
+";
+    // Reindented
+    let ours = "\
+if x {
+        a();
+        b();
+}
+";
+    let theirs = "\
+if x {
+    a();
+    c();
+}
+";
+
+    merge(original, ours, theirs).unwrap_err();
+
+    let mut options = MergeOptions::new();
+    options.set_whitespace(Whitespace::IgnoreChange);
+    let expected = "\
+if x {
+        a();
+    c();
+}
+";
+    assert_eq!(options.merge(original, ours, theirs).unwrap(), expected);
+    assert_eq!(
+        options
+            .merge_bytes(original.as_bytes(), ours.as_bytes(), theirs.as_bytes())
+            .unwrap(),
+        expected.as_bytes()
+    );
+
+    // Where only whitespace differs, our version is kept
+    assert_eq!(options.merge(original, theirs, ours).unwrap(), theirs);
+}
+
+#[test]
+fn merge_with_tokenizer() {
+    // Splits camelCase identifiers into their words
+    #[derive(Debug)]
+    struct CamelCase;
+
+    impl Tokenizer for CamelCase {
+        fn token_len(&self, text: &str) -> usize {
+            let mut chars = text.char_indices();
+            let (_, first) = chars.next().unwrap();
+            if !first.is_alphabetic() {
+                return first.len_utf8();
+            }
+            chars
+                .find(|&(_, c)| !c.is_lowercase())
+                .map_or(text.len(), |(i, _)| i)
+        }
+    }
+
+    let original = "let fooBarBaz = 1;\n";
+    let ours = "let quxBarBaz = 1;\n";
+    let theirs = "let fooBarQux = 1;\n";
+
+    merge(original, ours, theirs).unwrap_err();
+
+    let mut options = MergeOptions::new();
+    options.set_tokenizer(CamelCase);
+    assert_eq!(
+        options.merge(original, ours, theirs).unwrap(),
+        "let quxBarQux = 1;\n"
+    );
+}
+
+#[test]
+fn test_merge_slices() {
+    let ancestor = ["a", "b", "c", "d", "e"];
+    let ours = ["a", "B", "c", "d", "e"];
+    let theirs = ["a", "b", "c", "D", "e", "f"];
+    assert_eq!(
+        merge_slices(&ancestor, &ours, &theirs).unwrap(),
+        [&"a", &"B", &"c", &"D", &"e", &"f"]
+    );
+
+    // Identical changes on both sides don't conflict
+    assert_eq!(
+        merge_slices(&ancestor, &ours, &ours).unwrap(),
+        ours.iter().collect::<Vec<_>>()
+    );
+
+    let theirs = ["a", "X", "c", "d", "e"];
+    assert_eq!(
+        merge_slices(&ancestor, &ours, &theirs).unwrap_err(),
+        [
+            MergeChunk::Resolved(&["a"][..]),
+            MergeChunk::Conflict {
+                ancestor: &["b"][..],
+                ours: &["B"][..],
+                theirs: &["X"][..],
+            },
+            MergeChunk::Resolved(&["c", "d", "e"][..]),
+        ]
+    );
+}
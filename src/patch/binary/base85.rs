@@ -0,0 +1,108 @@
+//! The base85 encoding used by git for binary patches
+//!
+//! Each line of encoded data starts with a character indicating how many bytes
+//! it holds ('A'-'Z' for 1-26 and 'a'-'z' for 27-52) followed by the bytes
+//! encoded in groups of 4 bytes to 5 characters.
+
+const ALPHABET: &[u8; 85] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
+
+// The maximum number of bytes encoded on a single line
+const MAX_LINE_LEN: usize = 52;
+
+fn decode_char(c: u8) -> Option<u32> {
+    ALPHABET.iter().position(|&a| a == c).map(|n| n as u32)
+}
+
+/// Encode `data` as lines of base85, each terminated by a newline
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+
+    for line in data.chunks(MAX_LINE_LEN) {
+        let len = line.len() as u8;
+        encoded.push(if len <= 26 {
+            b'A' + len - 1
+        } else {
+            b'a' + len - 27
+        });
+
+        for group in line.chunks(4) {
+            let mut value = group
+                .iter()
+                .chain(std::iter::repeat(&0))
+                .take(4)
+                .fold(0u32, |value, &b| value << 8 | u32::from(b));
+
+            let mut chars = [0; 5];
+            for c in chars.iter_mut().rev() {
+                *c = ALPHABET[(value % 85) as usize];
+                value /= 85;
+            }
+            encoded.extend_from_slice(&chars);
+        }
+
+        encoded.push(b'\n');
+    }
+
+    encoded
+}
+
+/// Decode a single line of base85, without its trailing newline
+pub fn decode_line(line: &[u8], out: &mut Vec<u8>) -> Option<()> {
+    let (&len, chars) = line.split_first()?;
+    let len = match len {
+        b'A'..=b'Z' => usize::from(len - b'A') + 1,
+        b'a'..=b'z' => usize::from(len - b'a') + 27,
+        _ => return None,
+    };
+
+    if chars.len() != (len + 3) / 4 * 5 {
+        return None;
+    }
+
+    let start = out.len();
+    for group in chars.chunks(5) {
+        let mut value = 0u32;
+        for &c in group {
+            value = value.checked_mul(85)?.checked_add(decode_char(c)?)?;
+        }
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+    out.truncate(start + len);
+
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let data: Vec<u8> = (0..=255).cycle().take(300).collect();
+        for len in 0..data.len() {
+            let encoded = encode(&data[..len]);
+            let mut decoded = Vec::new();
+            for line in encoded.split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+                decode_line(line, &mut decoded).unwrap();
+            }
+            assert_eq!(decoded, &data[..len]);
+        }
+    }
+
+    #[test]
+    fn git_empty_literal() {
+        // The zlib stream git produces for an empty file
+        let data = b"x\x01\x03\x00\x00\x00\x00\x01";
+        assert_eq!(encode(data), b"HcmV?d00001\n");
+    }
+
+    #[test]
+    fn invalid() {
+        let mut out = Vec::new();
+        assert!(decode_line(b"", &mut out).is_none());
+        assert!(decode_line(b"Bcm", &mut out).is_none());
+        assert!(decode_line(b"AcmV\"d", &mut out).is_none());
+        assert!(decode_line(b"A~~~~~", &mut out).is_none());
+    }
+}
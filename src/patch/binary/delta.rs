@@ -0,0 +1,226 @@
+//! Git's delta format, as used by `delta` binary patches and packfiles
+//!
+//! A delta starts with the sizes of the source and target, each encoded as a
+//! little-endian base-128 varint, followed by a series of instructions which
+//! either copy a range of the source or insert literal bytes.
+
+use std::collections::HashMap;
+
+// The largest number of bytes a single insert instruction can hold
+const MAX_INSERT_LEN: usize = 0x7f;
+// The largest number of bytes emitted for a single copy instruction
+const MAX_COPY_LEN: usize = 0x10000;
+// The size of the blocks of the source which are indexed when searching for matches
+const BLOCK_LEN: usize = 16;
+
+/// Apply a delta to `source`, returning `None` if the delta is malformed or
+/// was created against a source of a different size
+pub fn apply(source: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    // The target length comes from the delta, so only trust it as far as the delta could go. Every
+    // instruction is at least one byte and adds at most `MAX_COPY_LEN` bytes to the target.
+    let max_len = delta.len().saturating_mul(MAX_COPY_LEN);
+    let capacity = source.len().saturating_add(delta.len());
+    let mut delta = delta.iter().copied();
+
+    let source_len = read_varint(&mut delta)?;
+    let target_len = read_varint(&mut delta)?;
+    if source_len != source.len() || target_len > max_len {
+        return None;
+    }
+
+    let mut target = Vec::with_capacity(target_len.min(capacity));
+    while let Some(cmd) = delta.next() {
+        if cmd & 0x80 != 0 {
+            let mut offset = 0;
+            for i in 0..4 {
+                if cmd & (1 << i) != 0 {
+                    offset |= usize::from(delta.next()?) << (8 * i);
+                }
+            }
+            let mut len = 0;
+            for i in 0..3 {
+                if cmd & (0x10 << i) != 0 {
+                    len |= usize::from(delta.next()?) << (8 * i);
+                }
+            }
+            if len == 0 {
+                len = MAX_COPY_LEN;
+            }
+            target.extend_from_slice(source.get(offset..offset.checked_add(len)?)?);
+        } else if cmd != 0 {
+            for _ in 0..cmd {
+                target.push(delta.next()?);
+            }
+        } else {
+            // Instruction 0 is reserved
+            return None;
+        }
+        if target.len() > target_len {
+            return None;
+        }
+    }
+
+    if target.len() == target_len {
+        Some(target)
+    } else {
+        None
+    }
+}
+
+/// Create a delta which turns `source` into `target`
+pub fn create(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    write_varint(&mut delta, source.len());
+    write_varint(&mut delta, target.len());
+
+    // Index the first location of each aligned block of the source
+    let mut index = HashMap::new();
+    for (i, block) in source.chunks_exact(BLOCK_LEN).enumerate() {
+        index.entry(block).or_insert(i * BLOCK_LEN);
+    }
+
+    let mut insert_start = 0;
+    let mut pos = 0;
+    while pos + BLOCK_LEN <= target.len() {
+        let src = match index.get(&target[pos..pos + BLOCK_LEN]) {
+            Some(&src) => src,
+            None => {
+                pos += 1;
+                continue;
+            }
+        };
+
+        // Extend the match as far as possible in both directions
+        let mut len = BLOCK_LEN;
+        while src + len < source.len()
+            && pos + len < target.len()
+            && source[src + len] == target[pos + len]
+        {
+            len += 1;
+        }
+        let (mut src, mut start) = (src, pos);
+        while src > 0 && start > insert_start && source[src - 1] == target[start - 1] {
+            src -= 1;
+            start -= 1;
+            len += 1;
+        }
+
+        write_insert(&mut delta, &target[insert_start..start]);
+        write_copy(&mut delta, src, len);
+
+        pos = start + len;
+        insert_start = pos;
+    }
+    write_insert(&mut delta, &target[insert_start..]);
+
+    delta
+}
+
+fn read_varint<I: Iterator<Item = u8>>(bytes: &mut I) -> Option<usize> {
+    let mut value = 0usize;
+    let mut shift = 0;
+    loop {
+        let byte = bytes.next()?;
+        value |= usize::from(byte & 0x7f).checked_shl(shift)?;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+}
+
+fn write_varint(delta: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        delta.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    delta.push(value as u8);
+}
+
+fn write_insert(delta: &mut Vec<u8>, data: &[u8]) {
+    for chunk in data.chunks(MAX_INSERT_LEN) {
+        delta.push(chunk.len() as u8);
+        delta.extend_from_slice(chunk);
+    }
+}
+
+fn write_copy(delta: &mut Vec<u8>, mut offset: usize, mut len: usize) {
+    while len > 0 {
+        let chunk = std::cmp::min(len, MAX_COPY_LEN);
+
+        let mut cmd = 0x80;
+        let mut args = Vec::new();
+        for i in 0..4 {
+            let byte = (offset >> (8 * i)) as u8;
+            if byte != 0 {
+                cmd |= 1 << i;
+                args.push(byte);
+            }
+        }
+        // A size of 0 is used to encode MAX_COPY_LEN
+        let size = if chunk == MAX_COPY_LEN { 0 } else { chunk };
+        for i in 0..3 {
+            let byte = (size >> (8 * i)) as u8;
+            if byte != 0 {
+                cmd |= 0x10 << i;
+                args.push(byte);
+            }
+        }
+
+        delta.push(cmd);
+        delta.extend_from_slice(&args);
+
+        offset += chunk;
+        len -= chunk;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let source: Vec<u8> = (0..100_000u32).map(|i| (i * 7 % 251) as u8).collect();
+
+        let mut target = source.clone();
+        target.splice(500..510, b"inserted".iter().copied());
+        target.drain(40_000..40_100);
+        target.extend_from_slice(&source[..1000]);
+
+        let delta = create(&source, &target);
+        assert!(delta.len() < 1000);
+        assert_eq!(apply(&source, &delta).unwrap(), target);
+
+        for (source, target) in &[(&b""[..], &b""[..]), (b"", b"new"), (b"old", b"")] {
+            let delta = create(source, target);
+            assert_eq!(apply(source, &delta).unwrap(), *target);
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        let delta = create(
+            b"0123456789abcdefghijklmnopqrstuvwxyz",
+            b"0123456789abcdefghijklmnopqrstuvwxyz!",
+        );
+        // Wrong source
+        assert_eq!(apply(b"0123", &delta), None);
+        // Truncated
+        assert_eq!(
+            apply(
+                b"0123456789abcdefghijklmnopqrstuvwxyz",
+                &delta[..delta.len() - 1]
+            ),
+            None
+        );
+        // Reserved instruction
+        assert_eq!(apply(b"", &[0, 1, 0]), None);
+        // A target length which the delta can't possibly produce
+        let mut huge = Vec::new();
+        write_varint(&mut huge, 0);
+        write_varint(&mut huge, usize::MAX);
+        huge.extend_from_slice(&[1, b'!']);
+        assert_eq!(apply(b"", &huge), None);
+    }
+}
@@ -0,0 +1,440 @@
+//! Git binary patches
+
+mod base85;
+mod delta;
+
+pub(super) use base85::decode_line;
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use std::io::{self, Read, Write};
+
+// Deflating can shrink data by at most roughly this factor
+const MAX_DEFLATE_RATIO: usize = 1032;
+
+/// The binary portion of a patch generated by git
+///
+/// ```text
+/// GIT binary patch
+/// literal 3
+/// KcmZQzWC8#H2LJ>B
+///
+/// literal 0
+/// HcmV?d00001
+///
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BinaryPatch {
+    /// A `Binary files a/x and b/y differ` line, which doesn't include the
+    /// data needed to apply the patch
+    Marker,
+    /// A `GIT binary patch` section
+    Full {
+        /// The hunk turning the old file into the new file
+        forward: BinaryHunk,
+        /// The hunk turning the new file back into the old file
+        reverse: Option<BinaryHunk>,
+    },
+}
+
+impl BinaryPatch {
+    /// Create a binary patch which turns `original` into `modified`
+    ///
+    /// Like git, each direction is stored as a delta when that is smaller than
+    /// the literal contents of the file.
+    pub fn new(original: &[u8], modified: &[u8]) -> Self {
+        BinaryPatch::Full {
+            forward: BinaryHunk::new(original, modified),
+            reverse: Some(BinaryHunk::new(modified, original)),
+        }
+    }
+
+    /// Returns the hunk turning the old file into the new file
+    pub fn forward(&self) -> Option<&BinaryHunk> {
+        match self {
+            BinaryPatch::Marker => None,
+            BinaryPatch::Full { forward, .. } => Some(forward),
+        }
+    }
+
+    /// Returns the hunk turning the new file back into the old file
+    pub fn reverse(&self) -> Option<&BinaryHunk> {
+        match self {
+            BinaryPatch::Marker => None,
+            BinaryPatch::Full { reverse, .. } => reverse.as_ref(),
+        }
+    }
+
+    /// Apply the patch to `base_image`, returning `None` if the patch doesn't
+    /// contain the data needed to do so or it doesn't match `base_image`
+    ///
+    /// When the patch includes the hunk turning the new file back into the
+    /// old file, the result is checked by undoing the patch and comparing
+    /// with `base_image`. This is what keeps a `literal` hunk, which holds
+    /// the full contents of the new file, from applying to any file.
+    pub fn apply(&self, base_image: &[u8]) -> Option<Vec<u8>> {
+        let image = self.forward()?.apply(base_image)?;
+        match self.reverse() {
+            Some(reverse) if reverse.apply(&image)? != base_image => None,
+            _ => Some(image),
+        }
+    }
+
+    // Returns a patch which undoes this one, or a marker if this patch doesn't include the hunk
+    // needed to do so
+    pub(super) fn reversed(&self) -> Self {
+        match self {
+            BinaryPatch::Full {
+                forward,
+                reverse: Some(reverse),
+            } => BinaryPatch::Full {
+                forward: reverse.clone(),
+                reverse: Some(forward.clone()),
+            },
+            _ => BinaryPatch::Marker,
+        }
+    }
+
+    pub(super) fn write_into<W: Write>(&self, mut w: W) -> io::Result<()> {
+        if let BinaryPatch::Full { forward, reverse } = self {
+            writeln!(w, "GIT binary patch")?;
+            forward.write_into(&mut w)?;
+            if let Some(reverse) = reverse {
+                reverse.write_into(&mut w)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The kind of data held by a `BinaryHunk`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BinaryHunkKind {
+    /// The full contents of the resulting file
+    Literal,
+    /// A git delta against the file the hunk is applied to
+    Delta,
+}
+
+/// A single `literal` or `delta` hunk of a `BinaryPatch`
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawBinaryHunk"))]
+pub struct BinaryHunk {
+    kind: BinaryHunkKind,
+    // The size of the data once inflated
+    size: usize,
+    // The zlib deflated data, kept as-is so that parsed patches can be written back out unchanged
+    deflated: Vec<u8>,
+}
+
+impl BinaryHunk {
+    fn new(source: &[u8], target: &[u8]) -> Self {
+        let literal = Self::deflate(BinaryHunkKind::Literal, target);
+        if source.is_empty() || target.is_empty() {
+            return literal;
+        }
+
+        let delta = Self::deflate(BinaryHunkKind::Delta, &delta::create(source, target));
+        if delta.deflated.len() < literal.deflated.len() {
+            delta
+        } else {
+            literal
+        }
+    }
+
+    fn deflate(kind: BinaryHunkKind, data: &[u8]) -> Self {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        Self {
+            kind,
+            size: data.len(),
+            deflated: encoder.finish().unwrap(),
+        }
+    }
+
+    // Constructs a hunk from its parsed parts, checking that the data inflates to the expected size
+    pub(super) fn from_deflated(
+        kind: BinaryHunkKind,
+        size: usize,
+        deflated: Vec<u8>,
+    ) -> Option<Self> {
+        let hunk = Self {
+            kind,
+            size,
+            deflated,
+        };
+        hunk.data().map(|_| hunk)
+    }
+
+    /// Returns whether the hunk holds a literal or a delta
+    pub fn kind(&self) -> BinaryHunkKind {
+        self.kind
+    }
+
+    /// Returns the size of the hunk's data once inflated
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the inflated data of the hunk, either the contents of the
+    /// resulting file or a git delta depending on its `kind`
+    pub fn data(&self) -> Option<Vec<u8>> {
+        // The size comes from the patch, so only trust it as far as the deflated data could go and
+        // stop inflating once the data is known to be too long
+        let capacity = self
+            .size
+            .min(self.deflated.len().saturating_mul(MAX_DEFLATE_RATIO));
+        let mut data = Vec::with_capacity(capacity);
+        ZlibDecoder::new(&self.deflated[..])
+            .take((self.size as u64).saturating_add(1))
+            .read_to_end(&mut data)
+            .ok()?;
+        if data.len() == self.size {
+            Some(data)
+        } else {
+            None
+        }
+    }
+
+    /// Apply the hunk to `base_image`, returning `None` if it doesn't match
+    ///
+    /// A `literal` hunk holds the full contents of the resulting file, so it
+    /// doesn't depend on `base_image` at all. Use [`BinaryPatch::apply`] to
+    /// also check the base image against the reverse hunk.
+    pub fn apply(&self, base_image: &[u8]) -> Option<Vec<u8>> {
+        let data = self.data()?;
+        match self.kind {
+            BinaryHunkKind::Literal => Some(data),
+            BinaryHunkKind::Delta => delta::apply(base_image, &data),
+        }
+    }
+
+    fn write_into<W: Write>(&self, mut w: W) -> io::Result<()> {
+        match self.kind {
+            BinaryHunkKind::Literal => writeln!(w, "literal {}", self.size)?,
+            BinaryHunkKind::Delta => writeln!(w, "delta {}", self.size)?,
+        }
+        w.write_all(&base85::encode(&self.deflated))?;
+        writeln!(w)
+    }
+}
+
+impl std::fmt::Debug for BinaryHunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BinaryHunk")
+            .field("kind", &self.kind)
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+// The fields of a deserialized `BinaryHunk`, which are checked before the hunk is constructed
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawBinaryHunk {
+    kind: BinaryHunkKind,
+    size: usize,
+    deflated: Vec<u8>,
+}
+
+#[cfg(feature = "serde")]
+impl std::convert::TryFrom<RawBinaryHunk> for BinaryHunk {
+    type Error = &'static str;
+
+    fn try_from(raw: RawBinaryHunk) -> Result<Self, Self::Error> {
+        Self::from_deflated(raw.kind, raw.size, raw.deflated)
+            .ok_or("binary hunk data does not match its size")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{apply_bytes, create_binary_patch, unapply_bytes, Patch, PatchSet};
+
+    // `git diff --binary` output
+    const GIT_BINARY_PATCHES: &str = "\
+diff --git a/gone.bin b/gone.bin
+deleted file mode 100644
+index ebb76707fba3dd2c7b1f2483715ae907760e3b0b..0000000000000000000000000000000000000000
+GIT binary patch
+literal 0
+HcmV?d00001
+
+literal 5
+McmYe#&r4+h00r0qssI20
+
+diff --git a/new.bin b/new.bin
+new file mode 100644
+index 0000000000000000000000000000000000000000..8352675d67aed6625ece79af41c27fdb4ee2e867
+GIT binary patch
+literal 3
+KcmZQzWC8#H2LJ>B
+
+literal 0
+HcmV?d00001
+
+diff --git a/small.bin b/small.bin
+index db12d84d7d09898766cc3d68c37aa7d58f6c3702..294dc46eb8c864e126a6bc9ce249d77e9ae98814 100644
+GIT binary patch
+literal 17
+Ycmc~u&B@7UD9K1IN>wP&FUm;)06BFAbpQYW
+
+literal 11
+Scmc~u&B@7UD9<m-NdW*EO9VXt
+
+";
+
+    #[test]
+    fn parse_and_apply_literals() {
+        let set = PatchSet::from_bytes(GIT_BINARY_PATCHES.as_bytes()).unwrap();
+        assert_eq!(set.to_bytes(), GIT_BINARY_PATCHES.as_bytes());
+
+        let patches = set.patches();
+        assert_eq!(apply_bytes(b"gone\0", &patches[0]).unwrap(), b"");
+        assert_eq!(apply_bytes(b"", &patches[1]).unwrap(), b"\0\x01\x02");
+        assert_eq!(
+            apply_bytes(b"hello\0world", &patches[2]).unwrap(),
+            b"hello\0there world"
+        );
+
+        let reverse = patches[2].binary().unwrap().reverse().unwrap();
+        assert_eq!(reverse.kind(), BinaryHunkKind::Literal);
+        assert_eq!(
+            reverse.apply(b"hello\0there world").unwrap(),
+            b"hello\0world"
+        );
+
+        let set = PatchSet::from_str(GIT_BINARY_PATCHES).unwrap();
+        assert_eq!(set.to_string(), GIT_BINARY_PATCHES);
+    }
+
+    #[test]
+    fn parse_and_apply_deltas() {
+        let s = "\
+diff --git a/big.bin b/big.bin
+index 2c2494333a740e48131084794c014ce0b0891a73..5f8580970d216024e7c7dc54ce8aa666e937b25e 100644
+GIT binary patch
+delta 34
+ncmZ1=e?WZ03uX>azhKv(5LcJU8~NUEUeEK92~130&+{Ju^L!5I
+
+delta 55
+WcmX>gzCeD%3+BmhnSPUi-va=Eks$&A
+
+";
+        let original: Vec<u8> = (0..4000u32)
+            .map(|i| ((i * i * 31 + i * 7) % 251) as u8)
+            .collect();
+        let modified = [&original[..1000], b"INSERTED", &original[1000..3000]].concat();
+
+        let patch = Patch::from_bytes(s.as_bytes()).unwrap();
+        assert_eq!(patch.to_bytes(), s.as_bytes());
+        let binary = patch.binary().unwrap();
+        assert_eq!(binary.forward().unwrap().kind(), BinaryHunkKind::Delta);
+        assert_eq!(binary.forward().unwrap().size(), 34);
+
+        assert_eq!(apply_bytes(&original, &patch).unwrap(), modified);
+        assert_eq!(
+            binary.reverse().unwrap().apply(&modified).unwrap(),
+            original
+        );
+        assert_eq!(unapply_bytes(&modified, &patch).unwrap(), original);
+        assert_eq!(apply_bytes(&modified, &patch.reverse()).unwrap(), original);
+        // A delta can only be applied to the file it was created against
+        apply_bytes(&modified, &patch).unwrap_err();
+    }
+
+    #[test]
+    fn marker() {
+        let s = "\
+diff --git a/big.bin b/big.bin
+index 186e38c..4bfb8bb 100644
+Binary files a/big.bin and b/big.bin differ
+diff --git a/gone.bin b/gone.bin
+deleted file mode 100644
+index ebb7670..0000000
+Binary files a/gone.bin and /dev/null differ
+diff --git a/new.bin b/new.bin
+new file mode 100644
+index 0000000..8352675
+Binary files /dev/null and b/new.bin differ
+";
+        let set = PatchSet::from_str(s).unwrap();
+        assert_eq!(set.patches().len(), 3);
+        assert_eq!(set.patches()[0].binary(), Some(&BinaryPatch::Marker));
+        assert_eq!(set.to_string(), s);
+        assert_eq!(
+            PatchSet::from_bytes(s.as_bytes()).unwrap().to_bytes(),
+            s.as_bytes()
+        );
+
+        crate::apply("", &set.patches()[2]).unwrap_err();
+    }
+
+    #[test]
+    fn literal_checks_base_image() {
+        let set = PatchSet::from_bytes(GIT_BINARY_PATCHES.as_bytes()).unwrap();
+        let patches = set.patches();
+        // The literal hunks would otherwise replace any file
+        apply_bytes(b"not gone\0", &patches[0]).unwrap_err();
+        apply_bytes(b"hello\0everyone", &patches[2]).unwrap_err();
+        assert_eq!(
+            patches[2].binary().unwrap().forward().unwrap().apply(b""),
+            Some(b"hello\0there world".to_vec())
+        );
+    }
+
+    #[test]
+    fn create() {
+        let original: Vec<u8> = (0..10_000u32).map(|i| (i * i % 251) as u8).collect();
+        let mut modified = original.clone();
+        modified[5000] = 0xff;
+
+        let patch = create_binary_patch(&original, &modified);
+        let binary = patch.binary().unwrap();
+        assert_eq!(binary.forward().unwrap().kind(), BinaryHunkKind::Delta);
+        assert_eq!(apply_bytes(&original, &patch).unwrap(), modified);
+        assert_eq!(
+            binary.reverse().unwrap().apply(&modified).unwrap(),
+            original
+        );
+
+        let bytes = patch.to_bytes();
+        let parsed = Patch::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed, patch);
+        assert_eq!(apply_bytes(&original, &parsed).unwrap(), modified);
+
+        let patch = create_binary_patch(b"", b"\x00\x01");
+        assert_eq!(
+            patch.binary().unwrap().forward().unwrap().kind(),
+            BinaryHunkKind::Literal
+        );
+        assert_eq!(apply_bytes(b"", &patch).unwrap(), b"\x00\x01");
+    }
+
+    #[test]
+    fn invalid() {
+        let header = "diff --git a/x b/x\nGIT binary patch\n";
+        // Size doesn't match the data
+        Patch::from_str(&format!("{}literal 4\nKcmZQzWC8#H2LJ>B\n\n", header)).unwrap_err();
+        // Bad base85
+        Patch::from_str(&format!("{}literal 3\nKcmZQzWC8#H2LJ>\n\n", header)).unwrap_err();
+        // Not zlib data
+        Patch::from_str(&format!("{}literal 3\nJcmZQzWC8#H\n\n", header)).unwrap_err();
+        Patch::from_str(&format!("{}literally 3\n", header)).unwrap_err();
+        // Sizes larger than the data could possibly be
+        Patch::from_str(&format!(
+            "{}literal 18446744073709551615\nKcmZQzWC8#H2LJ>B\n\n",
+            header
+        ))
+        .unwrap_err();
+        Patch::from_str(&format!(
+            "{}literal 4000000000\nKcmZQzWC8#H2LJ>B\n\n",
+            header
+        ))
+        .unwrap_err();
+    }
+}
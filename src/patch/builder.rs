@@ -0,0 +1,575 @@
+//! Building a `Patch` by hand out of its hunks and lines
+
+use super::{parse, Filename, Hunk, HunkRange, Line, Patch};
+use crate::utils::Text;
+use std::{borrow::Cow, fmt};
+
+/// An error returned when a `PatchBuilder` or `HunkBuilder` is given lines
+/// which don't make up a valid patch
+///
+/// Hunks and lines are identified by their 0-based index, in the patch and
+/// within their hunk respectively, while the error message numbers them from
+/// 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BuildPatchError {
+    /// The hunk doesn't have any lines
+    EmptyHunk { hunk: usize },
+    /// The hunk starts at line `0`, line numbers start at `1`
+    InvalidStart { hunk: usize },
+    /// The hunk starts before the end of the previous hunk, in either the old
+    /// or the new file
+    OverlappingHunk { hunk: usize },
+    /// The line holds more than one line of text, or it doesn't end with a
+    /// newline even though lines of the same file follow it
+    InvalidLine { hunk: usize, line: usize },
+}
+
+impl fmt::Display for BuildPatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildPatchError::EmptyHunk { hunk } => {
+                write!(f, "error building patch: hunk #{} is empty", hunk + 1)
+            }
+            BuildPatchError::InvalidStart { hunk } => {
+                write!(
+                    f,
+                    "error building patch: hunk #{} starts at line 0",
+                    hunk + 1
+                )
+            }
+            BuildPatchError::OverlappingHunk { hunk } => write!(
+                f,
+                "error building patch: hunk #{} overlaps the previous hunk",
+                hunk + 1
+            ),
+            BuildPatchError::InvalidLine { hunk, line } => write!(
+                f,
+                "error building patch: line #{} of hunk #{} is malformed",
+                line + 1,
+                hunk + 1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BuildPatchError {}
+
+/// A builder for constructing a `Patch` by hand
+///
+/// The ranges of each hunk are computed from its lines, and the start of a
+/// hunk in the new file is derived from the hunks before it unless it is set
+/// with [`HunkBuilder::new_start`].
+///
+/// ```
+/// use diffy::{apply, HunkBuilder, PatchBuilder};
+///
+/// let patch = PatchBuilder::new()
+///     .original("a/heralds")
+///     .modified("b/heralds")
+///     .hunk(
+///         HunkBuilder::new(2)
+///             .context("Jezrien\n")
+///             .delete("Nale\n")
+///             .insert("Nalan\n")
+///             .context("Chanarach\n"),
+///     )
+///     .hunk(HunkBuilder::new(5).insert("Talenel\n"))
+///     .build()
+///     .unwrap();
+///
+/// let expected = "\
+/// --- a/heralds
+/// +++ b/heralds
+/// @@ -2,3 +2,3 @@
+///  Jezrien
+/// -Nale
+/// +Nalan
+///  Chanarach
+/// @@ -4,0 +5 @@
+/// +Talenel
+/// ";
+/// assert_eq!(patch.to_string(), expected);
+///
+/// let base_image = "Ishar\nJezrien\nNale\nChanarach\n";
+/// assert_eq!(
+///     apply(base_image, &patch).unwrap(),
+///     "Ishar\nJezrien\nNalan\nChanarach\nTalenel\n"
+/// );
+/// ```
+pub struct PatchBuilder<'a, T: ToOwned + ?Sized> {
+    original: Option<Filename<'a, T>>,
+    original_timestamp: Option<Cow<'a, T>>,
+    modified: Option<Filename<'a, T>>,
+    modified_timestamp: Option<Cow<'a, T>>,
+    hunks: Vec<HunkBuilder<'a, T>>,
+}
+
+impl<'a, T: ToOwned + ?Sized> PatchBuilder<'a, T> {
+    /// Construct a `PatchBuilder` for a patch without any filenames or hunks
+    pub fn new() -> Self {
+        Self {
+            original: None,
+            original_timestamp: None,
+            modified: None,
+            modified_timestamp: None,
+            hunks: Vec::new(),
+        }
+    }
+
+    /// Set the name of the old file
+    pub fn original<O: Into<Cow<'a, T>>>(mut self, original: O) -> Self {
+        self.original = Some(Filename(original.into()));
+        self
+    }
+
+    /// Set the name of the new file
+    pub fn modified<M: Into<Cow<'a, T>>>(mut self, modified: M) -> Self {
+        self.modified = Some(Filename(modified.into()));
+        self
+    }
+
+    /// Set the text following the name of the old file, e.g. a timestamp
+    ///
+    /// It is only written out if the old file has a name.
+    pub fn original_timestamp<S: Into<Cow<'a, T>>>(mut self, timestamp: S) -> Self {
+        self.original_timestamp = Some(timestamp.into());
+        self
+    }
+
+    /// Set the text following the name of the new file, e.g. a timestamp
+    ///
+    /// It is only written out if the new file has a name.
+    pub fn modified_timestamp<S: Into<Cow<'a, T>>>(mut self, timestamp: S) -> Self {
+        self.modified_timestamp = Some(timestamp.into());
+        self
+    }
+
+    /// Add a hunk after the hunks added so far
+    pub fn hunk(mut self, hunk: HunkBuilder<'a, T>) -> Self {
+        self.hunks.push(hunk);
+        self
+    }
+
+    fn build_patch(self, hunks: Vec<Hunk<'a, T>>) -> Patch<'a, T> {
+        Patch {
+            git: None,
+            original: self.original,
+            original_timestamp: self.original_timestamp,
+            modified: self.modified,
+            modified_timestamp: self.modified_timestamp,
+            hunks,
+            binary: None,
+            blank_context_space: false,
+        }
+    }
+}
+
+impl<'a> PatchBuilder<'a, str> {
+    /// Build the `Patch`, checking that its hunks are valid
+    pub fn build(self) -> Result<Patch<'a, str>, BuildPatchError> {
+        let hunks = build_hunks(&self.hunks)?;
+        Ok(self.build_patch(hunks))
+    }
+}
+
+impl<'a> PatchBuilder<'a, [u8]> {
+    /// Build the `Patch`, checking that its hunks are valid
+    pub fn build(self) -> Result<Patch<'a, [u8]>, BuildPatchError> {
+        let hunks = build_hunks(&self.hunks)?;
+        Ok(self.build_patch(hunks))
+    }
+}
+
+impl<T: ToOwned + ?Sized> Default for PatchBuilder<'_, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized, O> fmt::Debug for PatchBuilder<'_, T>
+where
+    T: ToOwned<Owned = O> + fmt::Debug,
+    O: std::borrow::Borrow<T> + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PatchBuilder")
+            .field("original", &self.original)
+            .field("original_timestamp", &self.original_timestamp)
+            .field("modified", &self.modified)
+            .field("modified_timestamp", &self.modified_timestamp)
+            .field("hunks", &self.hunks)
+            .finish()
+    }
+}
+
+/// A builder for constructing a `Hunk` by hand
+///
+/// Lines must end with a newline, except for the last line of the old or new
+/// file, which is written with a `\ No newline at end of file` marker.
+///
+/// ```
+/// use diffy::HunkBuilder;
+///
+/// let hunk = HunkBuilder::new(3)
+///     .function_context("fn main() {")
+///     .context("    let spren = \"Syl\";\n")
+///     .insert("    println!(\"{}\", spren);\n")
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(hunk.old_range().start(), 3);
+/// assert_eq!(hunk.old_range().len(), 1);
+/// assert_eq!(hunk.new_range().len(), 2);
+/// assert_eq!(hunk.function_context(), Some("fn main() {"));
+/// ```
+pub struct HunkBuilder<'a, T: ToOwned + ?Sized> {
+    old_start: usize,
+    new_start: Option<usize>,
+    function_context: Option<Cow<'a, T>>,
+    lines: Vec<Line<'a, T>>,
+}
+
+impl<'a, T: ToOwned + ?Sized> HunkBuilder<'a, T> {
+    /// Construct a `HunkBuilder` for a hunk whose first line is line
+    /// `old_start` of the old file
+    ///
+    /// Line numbers start at `1`. If the hunk only inserts lines then
+    /// `old_start` is the line they are inserted in front of, e.g. `1` when
+    /// creating a new file.
+    pub fn new(old_start: usize) -> Self {
+        Self {
+            old_start,
+            new_start: None,
+            function_context: None,
+            lines: Vec::new(),
+        }
+    }
+
+    /// Set the line of the new file which the hunk starts at
+    ///
+    /// By default this is derived from `old_start` and the number of lines
+    /// inserted and deleted by the hunks in front of this one.
+    pub fn new_start(mut self, new_start: usize) -> Self {
+        self.new_start = Some(new_start);
+        self
+    }
+
+    /// Set the function context written after the hunk's ranges
+    pub fn function_context<C: Into<Cow<'a, T>>>(mut self, function_context: C) -> Self {
+        self.function_context = Some(function_context.into());
+        self
+    }
+
+    /// Add a line which is present in both the old and the new file
+    pub fn context<L: Into<Cow<'a, T>>>(self, line: L) -> Self {
+        self.line(Line::Context(line.into()))
+    }
+
+    /// Add a line which is deleted from the old file
+    pub fn delete<L: Into<Cow<'a, T>>>(self, line: L) -> Self {
+        self.line(Line::Delete(line.into()))
+    }
+
+    /// Add a line which is inserted into the new file
+    pub fn insert<L: Into<Cow<'a, T>>>(self, line: L) -> Self {
+        self.line(Line::Insert(line.into()))
+    }
+
+    /// Add a line
+    pub fn line(mut self, line: Line<'a, T>) -> Self {
+        self.lines.push(line);
+        self
+    }
+
+    /// Add several lines
+    pub fn lines<I: IntoIterator<Item = Line<'a, T>>>(mut self, lines: I) -> Self {
+        self.lines.extend(lines);
+        self
+    }
+}
+
+impl<T: ?Sized, O> fmt::Debug for HunkBuilder<'_, T>
+where
+    T: ToOwned<Owned = O> + fmt::Debug,
+    O: std::borrow::Borrow<T> + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HunkBuilder")
+            .field("old_start", &self.old_start)
+            .field("new_start", &self.new_start)
+            .field("function_context", &self.function_context)
+            .field("lines", &self.lines)
+            .finish()
+    }
+}
+
+impl<'a> HunkBuilder<'a, str> {
+    /// Build the `Hunk`, checking that its lines are valid
+    ///
+    /// Unless it was set, the hunk starts at the same line in the new file as
+    /// it does in the old file.
+    pub fn build(self) -> Result<Hunk<'a, str>, BuildPatchError> {
+        build_hunks(&[self]).map(|mut hunks| hunks.remove(0))
+    }
+}
+
+impl<'a> HunkBuilder<'a, [u8]> {
+    /// Build the `Hunk`, checking that its lines are valid
+    ///
+    /// Unless it was set, the hunk starts at the same line in the new file as
+    /// it does in the old file.
+    pub fn build(self) -> Result<Hunk<'a, [u8]>, BuildPatchError> {
+        build_hunks(&[self]).map(|mut hunks| hunks.remove(0))
+    }
+}
+
+fn build_hunks<'a, T: Text + ?Sized>(
+    builders: &[HunkBuilder<'a, T>],
+) -> Result<Vec<Hunk<'a, T>>, BuildPatchError> {
+    let mut hunks = Vec::with_capacity(builders.len());
+    // The first lines following the previous hunk in the old and new file
+    let mut old_end = 1;
+    let mut new_end = 1;
+    // The last line of the old and new file seen so far which doesn't end with a newline
+    let mut old_eof = None;
+    let mut new_eof = None;
+
+    for (i, builder) in builders.iter().enumerate() {
+        if builder.lines.is_empty() {
+            return Err(BuildPatchError::EmptyHunk { hunk: i });
+        }
+
+        let old_start = builder.old_start;
+        let new_start = builder
+            .new_start
+            .unwrap_or_else(|| (old_start + new_end).saturating_sub(old_end));
+        if old_start == 0 || new_start == 0 {
+            return Err(BuildPatchError::InvalidStart { hunk: i });
+        }
+        if old_start < old_end || new_start < new_end {
+            return Err(BuildPatchError::OverlappingHunk { hunk: i });
+        }
+
+        let mut old_len = 0;
+        let mut new_len = 0;
+        for (j, line) in builder.lines.iter().enumerate() {
+            let (in_old, in_new) = match line {
+                Line::Context(_) => (true, true),
+                Line::Delete(_) => (true, false),
+                Line::Insert(_) => (false, true),
+            };
+            let l = line.inner();
+            // Lines of a file can't follow its last line
+            if let Some((hunk, line)) = old_eof.filter(|_| in_old).or(new_eof.filter(|_| in_new)) {
+                return Err(BuildPatchError::InvalidLine { hunk, line });
+            }
+            let text = l.strip_suffix("\n").unwrap_or(l);
+            if text.find("\n").is_some() {
+                return Err(BuildPatchError::InvalidLine { hunk: i, line: j });
+            }
+            if text.len() == l.len() {
+                if in_old {
+                    old_eof = Some((i, j));
+                }
+                if in_new {
+                    new_eof = Some((i, j));
+                }
+            }
+            old_len += in_old as usize;
+            new_len += in_new as usize;
+        }
+
+        old_end = old_start + old_len;
+        new_end = new_start + new_len;
+
+        // An empty range starts at the line before the hunk rather than its first line
+        let range = |start: usize, len: usize| {
+            HunkRange::new(if len == 0 { start - 1 } else { start }, len)
+        };
+        let (originals, modifieds) = parse::changed_text(&builder.lines);
+        hunks.push(Hunk::new(
+            range(old_start, old_len),
+            range(new_start, new_len),
+            builder.function_context.clone(),
+            builder.lines.clone(),
+            originals,
+            modifieds,
+        ));
+    }
+
+    Ok(hunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BuildPatchError, HunkBuilder, PatchBuilder};
+    use crate::{apply_bytes, create_patch, Line, Patch};
+
+    const ORIGINAL: &str = "\
+Roshar
+Ashyn
+Braize
+Scadrial
+Nalthis
+Sel
+Taldain
+Threnody
+";
+
+    const MODIFIED: &str = "\
+Roshar
+Braize
+Scadrial
+Nalthis
+Sel
+Taldain
+Threnody
+Yolen
+";
+
+    #[test]
+    fn matches_create_patch() {
+        let expected = create_patch(ORIGINAL, MODIFIED);
+        let hunk = &expected.hunks()[0];
+        assert_eq!(expected.hunks().len(), 1);
+
+        let patch = PatchBuilder::new()
+            .original("original")
+            .modified("modified")
+            .hunk(HunkBuilder::new(1).lines(hunk.lines().iter().cloned()))
+            .build()
+            .unwrap();
+        assert_eq!(patch, expected);
+    }
+
+    #[test]
+    fn ranges() {
+        let patch = PatchBuilder::new()
+            .hunk(HunkBuilder::new(2).delete("Ashyn\n"))
+            .hunk(
+                HunkBuilder::new(6)
+                    .insert("Nalthis\n")
+                    .insert("Scadrial\n")
+                    .context("Sel\n"),
+            )
+            .hunk(HunkBuilder::new(9).insert("Yolen\n"))
+            .build()
+            .unwrap();
+        let ranges = patch
+            .hunks()
+            .iter()
+            .map(|hunk| (hunk.old_range().to_string(), hunk.new_range().to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranges,
+            [("2", "1,0"), ("6", "5,3"), ("8,0", "10")]
+                .iter()
+                .map(|(old, new)| (old.to_string(), new.to_string()))
+                .collect::<Vec<_>>()
+        );
+
+        // Creating a file
+        let hunk = HunkBuilder::new(1).insert("Yolen\n").build().unwrap();
+        assert_eq!(hunk.old_range().range(), 0..0);
+        assert_eq!(hunk.new_range().range(), 1..2);
+
+        // Setting the start in the new file shifts the hunks after it
+        let patch = PatchBuilder::new()
+            .hunk(HunkBuilder::new(2).new_start(4).context("Ashyn\n"))
+            .hunk(HunkBuilder::new(3).context("Braize\n"))
+            .build()
+            .unwrap();
+        assert_eq!(patch.hunks()[1].new_range().start(), 5);
+    }
+
+    #[test]
+    fn no_newline_at_eof() {
+        let patch = PatchBuilder::new()
+            .original(&b"a/worlds"[..])
+            .modified(&b"b/worlds"[..])
+            .hunk(
+                HunkBuilder::new(1)
+                    .delete(&b"Roshar"[..])
+                    .insert(&b"Roshar\n"[..])
+                    .insert(&b"Ashyn"[..]),
+            )
+            .build()
+            .unwrap();
+        let expected = "\
+--- a/worlds
++++ b/worlds
+@@ -1 +1,2 @@
+-Roshar
+\\ No newline at end of file
++Roshar
++Ashyn
+\\ No newline at end of file
+";
+        assert_eq!(patch.to_bytes(), expected.as_bytes());
+        assert_eq!(Patch::from_bytes(expected.as_bytes()).unwrap(), patch);
+        assert_eq!(apply_bytes(b"Roshar", &patch).unwrap(), b"Roshar\nAshyn");
+    }
+
+    #[test]
+    fn errors() {
+        let build = |hunks: Vec<HunkBuilder<'static, str>>| {
+            hunks
+                .into_iter()
+                .fold(PatchBuilder::new(), PatchBuilder::hunk)
+                .build()
+                .unwrap_err()
+        };
+
+        assert_eq!(
+            build(vec![
+                HunkBuilder::new(1).context("Roshar\n"),
+                HunkBuilder::new(3)
+            ]),
+            BuildPatchError::EmptyHunk { hunk: 1 }
+        );
+        assert_eq!(
+            build(vec![HunkBuilder::new(0).insert("Roshar\n")]),
+            BuildPatchError::InvalidStart { hunk: 0 }
+        );
+        assert_eq!(
+            build(vec![
+                HunkBuilder::new(1).context("Roshar\n").context("Ashyn\n"),
+                HunkBuilder::new(2).delete("Ashyn\n"),
+            ]),
+            BuildPatchError::OverlappingHunk { hunk: 1 }
+        );
+        assert_eq!(
+            build(vec![
+                HunkBuilder::new(1).insert("Roshar\n"),
+                HunkBuilder::new(2).new_start(1).context("Ashyn\n"),
+            ]),
+            BuildPatchError::OverlappingHunk { hunk: 1 }
+        );
+        assert_eq!(
+            build(vec![HunkBuilder::new(1).context("Roshar\nAshyn\n")]),
+            BuildPatchError::InvalidLine { hunk: 0, line: 0 }
+        );
+        assert_eq!(
+            build(vec![
+                HunkBuilder::new(1).insert("Roshar").delete("Ashyn\n"),
+                HunkBuilder::new(3).line(Line::Insert("Braize\n".into())),
+            ]),
+            BuildPatchError::InvalidLine { hunk: 0, line: 0 }
+        );
+        assert_eq!(
+            build(vec![HunkBuilder::new(1)
+                .delete("Roshar")
+                .context("Ashyn\n")]),
+            BuildPatchError::InvalidLine { hunk: 0, line: 0 }
+        );
+
+        assert_eq!(
+            BuildPatchError::EmptyHunk { hunk: 1 }.to_string(),
+            "error building patch: hunk #2 is empty"
+        );
+        assert_eq!(
+            BuildPatchError::InvalidLine { hunk: 0, line: 2 }.to_string(),
+            "error building patch: line #3 of hunk #1 is malformed"
+        );
+    }
+}
@@ -0,0 +1,567 @@
+//! Combining two patches which are applied one after the other into a single
+//! patch
+
+use super::{hunk_lines_count, parse, Hunk, HunkRange, Line, Patch};
+use crate::utils::Text;
+use std::{borrow::Cow, fmt};
+
+/// An error returned when two patches can't be composed with
+/// [`Patch::compose`]
+///
+/// [`Patch::compose`]: struct.Patch.html#method.compose
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ComposePatchError {
+    /// One of the patches is a binary patch
+    Binary,
+    /// A context or deleted line of the second patch doesn't match the line
+    /// the first patch leaves in its place
+    ///
+    /// The hunk and line are identified by their 0-based index in the second
+    /// patch and within that hunk respectively, while the error message
+    /// numbers them from 1.
+    Conflict { hunk: usize, line: usize },
+    /// The hunks of one of the patches are out of order or overlap
+    InvalidHunks,
+}
+
+impl fmt::Display for ComposePatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComposePatchError::Binary => {
+                write!(f, "error composing patches: binary patches can't be composed")
+            }
+            ComposePatchError::Conflict { hunk, line } => write!(
+                f,
+                "error composing patches: line #{} of hunk #{} of the second patch conflicts with the first patch",
+                line + 1,
+                hunk + 1
+            ),
+            ComposePatchError::InvalidHunks => {
+                write!(f, "error composing patches: hunks not in order or overlap")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ComposePatchError {}
+
+impl<'a> Patch<'a, str> {
+    /// Combines this patch with `other`, a patch for the file this patch
+    /// produces, into a single patch
+    ///
+    /// Applying the composed patch has the same effect as applying this patch
+    /// and then `other`, without needing the intermediate file. Hunks of the
+    /// two patches which touch the same lines of the intermediate file are
+    /// merged and the ranges of the remaining hunks are adjusted. The
+    /// composed patch takes the name of the old file from this patch and the
+    /// name of the new file from `other`, while git headers are left out.
+    ///
+    /// Fails with a [`ComposePatchError`] if the lines `other` expects to
+    /// find don't match the lines this patch leaves in the intermediate
+    /// file, or if either patch is a binary patch.
+    ///
+    /// ```
+    /// use diffy::{apply, create_patch};
+    ///
+    /// let a = "Kaladin\nShallan\nAdolin\n";
+    /// let b = "Kaladin\nVeil\nAdolin\n";
+    /// let c = "Kaladin\nRadiant\nAdolin\nRenarin\n";
+    ///
+    /// let composed = create_patch(a, b).compose(&create_patch(b, c)).unwrap();
+    /// let expected = "\
+    /// --- original
+    /// +++ modified
+    /// @@ -1,3 +1,4 @@
+    ///  Kaladin
+    /// -Shallan
+    /// +Radiant
+    ///  Adolin
+    /// +Renarin
+    /// ";
+    /// assert_eq!(composed.to_string(), expected);
+    /// assert_eq!(apply(a, &composed).unwrap(), c);
+    ///
+    /// // `Veil` is no longer there to be replaced
+    /// let conflicting = create_patch(a, "Kaladin\nRadiant\nAdolin\n");
+    /// assert!(create_patch(a, b).compose(&conflicting).is_err());
+    /// ```
+    ///
+    /// [`ComposePatchError`]: enum.ComposePatchError.html
+    pub fn compose(&self, other: &Patch<'a, str>) -> Result<Patch<'a, str>, ComposePatchError> {
+        compose(self, other)
+    }
+}
+
+impl<'a> Patch<'a, [u8]> {
+    /// Combines this patch with `other`, a patch for the file this patch
+    /// produces, into a single patch
+    ///
+    /// This is the equivalent of [`Patch::compose`] for potentially non-utf8
+    /// patches.
+    ///
+    /// [`Patch::compose`]: struct.Patch.html#method.compose
+    pub fn compose(&self, other: &Patch<'a, [u8]>) -> Result<Patch<'a, [u8]>, ComposePatchError> {
+        compose(self, other)
+    }
+}
+
+fn compose<'a, T: Text + ?Sized>(
+    first: &Patch<'a, T>,
+    second: &Patch<'a, T>,
+) -> Result<Patch<'a, T>, ComposePatchError> {
+    if first.binary.is_some() || second.binary.is_some() {
+        return Err(ComposePatchError::Binary);
+    }
+
+    // Both patches are walked in terms of the lines of the intermediate file, which are the new
+    // lines of the first patch and the old lines of the second
+    let mut first_offset = 0;
+    let mut second_offset = 0;
+    let mut hunks = Vec::new();
+
+    for group in group_hunks(&first.hunks, Side::New, &second.hunks, Side::Old) {
+        let first_ops = Side::New.ops(&group.first, group.start, group.end);
+        let second_ops = Side::Old.ops(&group.second, group.start, group.end);
+        let (first_ops, second_ops) = first_ops
+            .zip(second_ops)
+            .ok_or(ComposePatchError::InvalidHunks)?;
+        let lines = merge_ops(first_ops, second_ops)?;
+
+        let function_context = match (group.first.first(), group.second.first()) {
+            (Some((_, f)), Some((_, s))) if position(s.old_range) < position(f.new_range) => {
+                s.function_context.clone()
+            }
+            (Some((_, f)), _) => f.function_context.clone(),
+            (None, Some((_, s))) => s.function_context.clone(),
+            (None, None) => None,
+        };
+
+        let old_position = (group.start as isize - first_offset) as usize;
+        let new_position = (group.start as isize + second_offset) as usize;
+        first_offset += offset(&group.first);
+        second_offset += offset(&group.second);
+
+        // The second patch may have undone every change of the first
+        if lines.iter().all(|line| matches!(line, Line::Context(_))) {
+            continue;
+        }
+
+        hunks.push(build_hunk(
+            old_position,
+            new_position,
+            function_context,
+            lines,
+        ));
+    }
+
+    Ok(Patch {
+        git: None,
+        original: first.original.clone(),
+        original_timestamp: first.original_timestamp.clone(),
+        modified: second.modified.clone(),
+        modified_timestamp: second.modified_timestamp.clone(),
+        hunks,
+        binary: None,
+        blank_context_space: first.blank_context_space,
+    })
+}
+
+// Returns the 0-based index of the first line of `range`, an empty range starts at the line before
+// the hunk rather than its first line
+pub(super) fn position(range: HunkRange) -> usize {
+    if range.is_empty() {
+        range.start
+    } else {
+        range.start - 1
+    }
+}
+
+// Returns the number of lines the hunks add to the file, or remove from it if negative
+pub(super) fn offset<T: ToOwned + ?Sized>(hunks: &[(usize, &Hunk<'_, T>)]) -> isize {
+    hunks
+        .iter()
+        .map(|(_, hunk)| hunk.new_range.len as isize - hunk.old_range.len as isize)
+        .sum()
+}
+
+// Builds a hunk out of `lines` which starts at the 0-based `old_position` and `new_position`
+pub(super) fn build_hunk<'a, T: Text + ?Sized>(
+    old_position: usize,
+    new_position: usize,
+    function_context: Option<Cow<'a, T>>,
+    lines: Vec<Line<'a, T>>,
+) -> Hunk<'a, T> {
+    let range = |position, len| HunkRange::new(if len == 0 { position } else { position + 1 }, len);
+    let (old_len, new_len) = hunk_lines_count(&lines);
+    let (originals, modifieds) = parse::changed_text(&lines);
+    Hunk::new(
+        range(old_position, old_len),
+        range(new_position, new_len),
+        function_context,
+        lines,
+        originals,
+        modifieds,
+    )
+}
+
+// The hunks of two patches which touch the same lines of the file they are walked in terms of,
+// along with their index in their patch. Every line between `start` and `end` is covered by a hunk
+// of at least one of the patches.
+pub(super) struct Group<'x, 'a, T: ToOwned + ?Sized> {
+    pub(super) start: usize,
+    pub(super) end: usize,
+    pub(super) first: Vec<(usize, &'x Hunk<'a, T>)>,
+    pub(super) second: Vec<(usize, &'x Hunk<'a, T>)>,
+}
+
+// Splits the hunks of two patches into groups of hunks which overlap or are next to each other,
+// where the `first_side` file of the first patch's hunks is the `second_side` file of the second's
+pub(super) fn group_hunks<'x, 'a, T: ToOwned + ?Sized>(
+    first: &'x [Hunk<'a, T>],
+    first_side: Side,
+    second: &'x [Hunk<'a, T>],
+    second_side: Side,
+) -> Vec<Group<'x, 'a, T>> {
+    let mut firsts = first.iter().enumerate().peekable();
+    let mut seconds = second.iter().enumerate().peekable();
+    let mut groups = Vec::new();
+
+    loop {
+        let start = match (firsts.peek(), seconds.peek()) {
+            (Some((_, f)), Some((_, s))) => first_side.position(f).min(second_side.position(s)),
+            (Some((_, f)), None) => first_side.position(f),
+            (None, Some((_, s))) => second_side.position(s),
+            (None, None) => break,
+        };
+
+        // Gather every hunk touching the lines gathered so far
+        let mut group = Group {
+            start,
+            end: start,
+            first: Vec::new(),
+            second: Vec::new(),
+        };
+        loop {
+            let end = group.end;
+            if let Some((i, hunk)) = firsts.next_if(|(_, h)| first_side.position(h) <= end) {
+                group.end = end.max(first_side.position(hunk) + first_side.range(hunk).len);
+                group.first.push((i, hunk));
+            } else if let Some((i, hunk)) = seconds.next_if(|(_, h)| second_side.position(h) <= end)
+            {
+                group.end = end.max(second_side.position(hunk) + second_side.range(hunk).len);
+                group.second.push((i, hunk));
+            } else {
+                break;
+            }
+        }
+        groups.push(group);
+    }
+
+    groups
+}
+
+// Which of its two files the lines of a patch are walked in terms of
+#[derive(Clone, Copy)]
+pub(super) enum Side {
+    Old,
+    New,
+}
+
+impl Side {
+    fn range<T: ToOwned + ?Sized>(self, hunk: &Hunk<'_, T>) -> HunkRange {
+        match self {
+            Side::Old => hunk.old_range,
+            Side::New => hunk.new_range,
+        }
+    }
+
+    fn position<T: ToOwned + ?Sized>(self, hunk: &Hunk<'_, T>) -> usize {
+        position(self.range(hunk))
+    }
+
+    // The lines of `hunks` between `start` and `end`, along with the index of each hunk and line.
+    // Returns `None` if the hunks are out of order or overlap.
+    pub(super) fn ops<'x, 'a, T: ToOwned + ?Sized>(
+        self,
+        hunks: &[(usize, &'x Hunk<'a, T>)],
+        start: usize,
+        end: usize,
+    ) -> Option<Vec<IndexedOp<'x, 'a, T>>> {
+        let mut ops = Vec::new();
+        let mut next = start;
+        for (i, hunk) in hunks {
+            let hunk_start = self.position(hunk);
+            if hunk_start < next {
+                return None;
+            }
+            ops.extend((next..hunk_start).map(|_| (Op::Kept(None), None)));
+            ops.extend(hunk.lines.iter().enumerate().map(|(j, line)| {
+                let op = match (line, self) {
+                    (Line::Context(l), _) => Op::Kept(Some(l)),
+                    (Line::Delete(l), Side::Old) | (Line::Insert(l), Side::New) => Op::Changed(l),
+                    (Line::Delete(l), Side::New) | (Line::Insert(l), Side::Old) => Op::Outside(l),
+                };
+                (op, Some((*i, j)))
+            }));
+            next = hunk_start + self.range(hunk).len;
+        }
+        ops.extend((next..end).map(|_| (Op::Kept(None), None)));
+        Some(ops)
+    }
+}
+
+// A line of a patch in terms of one of its files
+pub(super) enum Op<'x, 'a, T: ToOwned + ?Sized> {
+    // A line which isn't in the file
+    Outside(&'x Cow<'a, T>),
+    // A line of the file which the patch's other file also has. Its text is unknown for lines
+    // between the hunks of the patch.
+    Kept(Option<&'x Cow<'a, T>>),
+    // A line of the file which the patch's other file doesn't have
+    Changed(&'x Cow<'a, T>),
+}
+
+// A line of a patch along with the index of its hunk and of the line within the hunk, which lines
+// between the hunks don't have
+pub(super) type IndexedOp<'x, 'a, T> = (Op<'x, 'a, T>, Option<(usize, usize)>);
+
+impl<'x, 'a, T: ToOwned + ?Sized> Op<'x, 'a, T> {
+    // Returns the text of a line of the file, if it is known
+    pub(super) fn text(&self) -> Option<&'x Cow<'a, T>> {
+        match self {
+            Op::Kept(l) => *l,
+            Op::Changed(l) => Some(l),
+            Op::Outside(_) => unreachable!("the line isn't in the file"),
+        }
+    }
+
+    pub(super) fn is_outside(&self) -> bool {
+        matches!(self, Op::Outside(_))
+    }
+}
+
+// Walks the lines of both patches side by side, producing the lines of the composed hunk
+fn merge_ops<'a, T: Text + ?Sized>(
+    first: Vec<IndexedOp<'_, 'a, T>>,
+    second: Vec<IndexedOp<'_, 'a, T>>,
+) -> Result<Vec<Line<'a, T>>, ComposePatchError> {
+    let mut first = first.into_iter().peekable();
+    let mut second = second.into_iter().peekable();
+    let mut lines = Vec::new();
+
+    loop {
+        if let Some((Op::Outside(l), _)) = first.next_if(|(op, _)| op.is_outside()) {
+            lines.push(Line::Delete(l.clone()));
+            continue;
+        }
+        if let Some((Op::Outside(l), _)) = second.next_if(|(op, _)| op.is_outside()) {
+            lines.push(Line::Insert(l.clone()));
+            continue;
+        }
+
+        // Both patches are now at the same line of the intermediate file
+        let ((f, _), (s, at)) = match (first.next(), second.next()) {
+            (Some(f), Some(s)) => (f, s),
+            (None, None) => break,
+            _ => return Err(ComposePatchError::InvalidHunks),
+        };
+        let l = match (f.text(), s.text(), at) {
+            (Some(a), Some(b), Some((hunk, line))) if a != b => {
+                return Err(ComposePatchError::Conflict { hunk, line });
+            }
+            (Some(l), _, _) | (None, Some(l), _) => l.clone(),
+            (None, None, _) => return Err(ComposePatchError::InvalidHunks),
+        };
+        match (f, s) {
+            (Op::Kept(_), Op::Kept(_)) => lines.push(Line::Context(l)),
+            (Op::Kept(_), Op::Changed(_)) => lines.push(Line::Delete(l)),
+            (Op::Changed(_), Op::Kept(_)) => lines.push(Line::Insert(l)),
+            // A line inserted by the first patch and deleted by the second
+            _ => {}
+        }
+    }
+
+    Ok(tidy_changes(lines))
+}
+
+// Moves the deletions in each group of changed lines ahead of the insertions, turning the lines
+// deleted by one patch and inserted back by the other at either end of the group into context
+pub(super) fn tidy_changes<'a, T: Text + ?Sized>(lines: Vec<Line<'a, T>>) -> Vec<Line<'a, T>> {
+    let mut tidy = Vec::with_capacity(lines.len());
+    let mut deleted = Vec::new();
+    let mut inserted = Vec::new();
+    for line in lines.into_iter().map(Some).chain(Some(None)) {
+        match line {
+            Some(Line::Delete(l)) => deleted.push(l),
+            Some(Line::Insert(l)) => inserted.push(l),
+            context => {
+                let prefix = deleted
+                    .iter()
+                    .zip(&inserted)
+                    .take_while(|(d, i)| d == i)
+                    .count();
+                let suffix = deleted[prefix..]
+                    .iter()
+                    .rev()
+                    .zip(inserted[prefix..].iter().rev())
+                    .take_while(|(d, i)| d == i)
+                    .count();
+                let deleted_end = deleted.len() - suffix;
+                let inserted_end = inserted.len() - suffix;
+
+                let mut deleted = deleted.drain(..);
+                tidy.extend(deleted.by_ref().take(prefix).map(Line::Context));
+                tidy.extend(
+                    deleted
+                        .by_ref()
+                        .take(deleted_end - prefix)
+                        .map(Line::Delete),
+                );
+                tidy.extend(
+                    inserted
+                        .drain(..)
+                        .take(inserted_end)
+                        .skip(prefix)
+                        .map(Line::Insert),
+                );
+                tidy.extend(deleted.map(Line::Context));
+                tidy.extend(context);
+            }
+        }
+    }
+    tidy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ComposePatchError;
+    use crate::{apply, create_binary_patch, create_patch, create_patch_bytes, Patch};
+
+    const A: &str = "\
+Kaladin
+Shallan
+Adolin
+Dalinar
+Navani
+Jasnah
+Renarin
+Lift
+Szeth
+Hoid
+";
+
+    #[track_caller]
+    fn assert_composes(a: &str, b: &str, c: &str) {
+        let composed = create_patch(a, b).compose(&create_patch(b, c)).unwrap();
+        assert_eq!(apply(a, &composed).unwrap(), c);
+
+        let composed = create_patch_bytes(a.as_bytes(), b.as_bytes())
+            .compose(&create_patch_bytes(b.as_bytes(), c.as_bytes()))
+            .unwrap();
+        assert_eq!(
+            crate::apply_bytes(a.as_bytes(), &composed).unwrap(),
+            c.as_bytes()
+        );
+    }
+
+    #[test]
+    fn compose() {
+        let b = "Kaladin\nVeil\nRadiant\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nHoid\n";
+
+        // Hunks far apart from each other
+        assert_composes(A, b, "Kaladin\nVeil\nRadiant\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nHoid\nNightblood\n");
+        assert_composes(A, "Kaladin\nShallan\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nSzeth\nHoid\nNightblood\n", b);
+
+        // Overlapping hunks
+        assert_composes(
+            A,
+            b,
+            "Kaladin\nVeil\nAdolin\nNavani\nJasnah\nRenarin\nLift\nHoid\n",
+        );
+        assert_composes(
+            A,
+            b,
+            "Kaladin\nShallan\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nHoid\n",
+        );
+
+        // The second patch undoes the first
+        let composed = create_patch(A, b).compose(&create_patch(b, A)).unwrap();
+        assert!(composed.hunks().is_empty());
+
+        // Files without a newline at the end, or without any lines
+        assert_composes(A, "Kaladin\nShallan", "Kaladin\nShallan\nAdolin");
+        assert_composes("", A, "Kaladin\n");
+        assert_composes(A, "", b);
+        assert_composes("Kaladin", "Kaladin\n", "Kaladin");
+    }
+
+    #[test]
+    fn ranges() {
+        let b = "Kaladin\nShallan\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nSzeth\nHoid\nNightblood\n";
+        let c = "Syl\nKaladin\nShallan\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nSzeth\nHoid\nNightblood\n";
+        let composed = create_patch(A, b).compose(&create_patch(b, c)).unwrap();
+        let expected = "\
+--- original
++++ modified
+@@ -1,3 +1,4 @@
++Syl
+ Kaladin
+ Shallan
+ Adolin
+@@ -8,3 +9,4 @@
+ Lift
+ Szeth
+ Hoid
++Nightblood
+";
+        assert_eq!(composed.to_string(), expected);
+    }
+
+    #[test]
+    fn conflict() {
+        let b = "Kaladin\nVeil\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nSzeth\nHoid\n";
+        let other = "\
+--- b
++++ c
+@@ -5,2 +5,2 @@
+ Navani
+-Jasnah
++Ivory
+@@ -9 +9 @@
+-Szeth
++Nale
+";
+        let other = Patch::from_str(other).unwrap();
+        let composed = create_patch(A, b).compose(&other).unwrap();
+        assert_eq!(composed.original(), Some("original"));
+        assert_eq!(composed.modified(), Some("c"));
+
+        // Lines that don't match what the first patch left behind
+        let stale = create_patch(A, "Shallan\nAdolin\nDalinar\n");
+        let err = create_patch(A, b).compose(&stale).unwrap_err();
+        assert_eq!(err, ComposePatchError::Conflict { hunk: 0, line: 1 });
+        assert_eq!(
+            err.to_string(),
+            "error composing patches: line #2 of hunk #1 of the second patch conflicts with the first patch"
+        );
+
+        let binary = create_binary_patch(b"\0Kaladin", b"\0Shallan");
+        assert_eq!(binary.compose(&binary), Err(ComposePatchError::Binary));
+
+        // Hunks which the parser accepts but which overlap in the old file
+        let overlapping = "\
+--- a
++++ b
+@@ -2,0 +3 @@
++Syl
+@@ -2 +4 @@
+-Shallan
++Veil
+";
+        let overlapping = Patch::from_str(overlapping).unwrap();
+        assert_eq!(
+            create_patch(A, A).compose(&overlapping),
+            Err(ComposePatchError::InvalidHunks)
+        );
+    }
+}
@@ -0,0 +1,189 @@
+//! Deserializing patches with the `serde` feature, which checks that the ranges of their hunks
+//! match their lines and that the hunks are in order like the parser does
+
+use super::{
+    hunk_lines_count, parse, BinaryPatch, Filename, GitHeader, Hunk, HunkRange, Line, Patch,
+};
+use crate::utils::Text;
+use serde::{de::Error, Deserialize, Deserializer};
+use std::{borrow::Cow, convert::TryFrom};
+
+// The serialized fields of a `Patch`, which are checked before the patch is constructed
+#[derive(Deserialize)]
+#[serde(
+    rename = "Patch",
+    bound(deserialize = "T::Owned: Deserialize<'de>, Hunk<'a, T>: Deserialize<'de>")
+)]
+pub(super) struct PatchFields<'a, T: ToOwned + ?Sized> {
+    git: Option<GitHeader<'a, T>>,
+    original: Option<Filename<'a, T>>,
+    original_timestamp: Option<Cow<'a, T>>,
+    modified: Option<Filename<'a, T>>,
+    modified_timestamp: Option<Cow<'a, T>>,
+    hunks: Vec<Hunk<'a, T>>,
+    binary: Option<BinaryPatch>,
+    #[serde(default)]
+    blank_context_space: bool,
+}
+
+impl<'a, T: ToOwned + ?Sized> TryFrom<PatchFields<'a, T>> for Patch<'a, T> {
+    type Error = &'static str;
+
+    fn try_from(fields: PatchFields<'a, T>) -> Result<Self, Self::Error> {
+        if !parse::verify_hunks_in_order(&fields.hunks) {
+            return Err("Hunks not in order or overlap");
+        }
+
+        Ok(Patch {
+            git: fields.git,
+            original: fields.original,
+            original_timestamp: fields.original_timestamp,
+            modified: fields.modified,
+            modified_timestamp: fields.modified_timestamp,
+            hunks: fields.hunks,
+            binary: fields.binary,
+            blank_context_space: fields.blank_context_space,
+        })
+    }
+}
+
+// The serialized fields of a `Hunk`, leaving out the text derived from its lines
+#[derive(Deserialize)]
+#[serde(rename = "Hunk", bound(deserialize = "T::Owned: Deserialize<'de>"))]
+struct HunkFields<'a, T: ToOwned + ?Sized> {
+    old_range: HunkRange,
+    new_range: HunkRange,
+    function_context: Option<Cow<'a, T>>,
+    lines: Vec<Line<'a, T>>,
+}
+
+impl<'a, T: Text + ?Sized> HunkFields<'a, T> {
+    fn into_hunk<E: Error>(self) -> Result<Hunk<'a, T>, E> {
+        let (old_len, new_len) = hunk_lines_count(&self.lines);
+        if old_len != self.old_range.len() || new_len != self.new_range.len() {
+            return Err(E::custom(format!(
+                "hunk ranges -{} +{} do not match its {} old and {} new lines",
+                self.old_range, self.new_range, old_len, new_len
+            )));
+        }
+
+        let (originals, modifieds) = parse::changed_text(&self.lines);
+        Ok(Hunk::new(
+            self.old_range,
+            self.new_range,
+            self.function_context,
+            self.lines,
+            originals,
+            modifieds,
+        ))
+    }
+}
+
+impl<'de, 'a> Deserialize<'de> for Hunk<'a, str> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        HunkFields::deserialize(deserializer)?.into_hunk()
+    }
+}
+
+impl<'de, 'a> Deserialize<'de> for Hunk<'a, [u8]> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        HunkFields::deserialize(deserializer)?.into_hunk()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{create_binary_patch, create_patch, create_patch_bytes, Patch, PatchSet};
+
+    #[test]
+    fn round_trip() {
+        let original = "Kaladin\nShallan\nAdolin\n";
+        let modified = "Kaladin\nVeil\nAdolin\nRenarin";
+
+        let patch = create_patch(original, modified);
+        let json = serde_json::to_string(&patch).unwrap();
+        let deserialized: Patch<'_, str> = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, patch);
+        assert_eq!(crate::apply(original, &deserialized).unwrap(), modified);
+
+        let patch = create_patch_bytes(original.as_bytes(), modified.as_bytes());
+        let json = serde_json::to_string(&patch).unwrap();
+        let deserialized: Patch<'_, [u8]> = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, patch);
+
+        let patch = create_binary_patch(b"\0Kaladin", b"\0Shallan");
+        let json = serde_json::to_string(&patch).unwrap();
+        let deserialized: Patch<'_, [u8]> = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, patch);
+    }
+
+    #[test]
+    fn git_headers() {
+        let s = "\
+diff --git a/old.txt b/new.txt
+similarity index 90%
+rename from old.txt
+rename to new.txt
+index 2f7a0c1..5d2e4b3 100644
+--- a/old.txt\t2024-01-01 00:00:00.000000000 +0000
++++ b/new.txt\t2024-01-02 00:00:00.000000000 +0000
+@@ -1,2 +1,2 @@ Orders
+ Windrunners
+-Bondsmiths
++Truthwatchers
+";
+        let set = PatchSet::from_str(s).unwrap();
+        let json = serde_json::to_string(set.patches()).unwrap();
+        let patches: Vec<Patch<'_, str>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(patches, set.patches());
+        assert_eq!(patches[0].to_string(), s);
+    }
+
+    #[test]
+    fn invalid_hunk() {
+        let json = r#"{
+            "git": null,
+            "original": "a",
+            "original_timestamp": null,
+            "modified": "b",
+            "modified_timestamp": null,
+            "hunks": [{
+                "old_range": {"start": 1, "len": 2},
+                "new_range": {"start": 1, "len": 1},
+                "function_context": null,
+                "lines": [{"Delete": "Kaladin\n"}, {"Insert": "Shallan\n"}]
+            }],
+            "binary": null
+        }"#;
+        let err = serde_json::from_str::<Patch<'_, str>>(json).unwrap_err();
+        assert!(err.to_string().contains("do not match"));
+
+        let valid = json.replace(r#""len": 2"#, r#""len": 1"#);
+        let patch: Patch<'_, str> = serde_json::from_str(&valid).unwrap();
+        assert_eq!(crate::apply("Kaladin\n", &patch).unwrap(), "Shallan\n");
+    }
+
+    #[test]
+    fn hunks_out_of_order() {
+        let original =
+            "Kaladin\nShallan\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nSzeth\nHoid\n";
+        let modified =
+            "Kaladin\nVeil\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nSzeth\nNale\n";
+        let patch = create_patch(original, modified);
+        assert_eq!(patch.hunks().len(), 2);
+
+        let mut json = serde_json::to_value(&patch).unwrap();
+        json["hunks"].as_array_mut().unwrap().swap(0, 1);
+        let err = serde_json::from_value::<Patch<'_, str>>(json).unwrap_err();
+        assert!(err.to_string().contains("not in order"));
+    }
+
+    #[test]
+    fn oversized_binary_hunk() {
+        let patch = create_binary_patch(b"\0Kaladin", b"\0Shallan");
+        let mut json = serde_json::to_value(&patch).unwrap();
+        json["binary"]["Full"]["forward"]["size"] = serde_json::json!(u64::MAX);
+        let err = serde_json::from_value::<Patch<'_, [u8]>>(json).unwrap_err();
+        assert!(err.to_string().contains("does not match its size"));
+    }
+}
@@ -1,8 +1,9 @@
 use crate::diff::{cleanup, myers};
 use crate::range::{DiffRange, Range};
+use crate::token::{GroupTokenizer, Tokenizer};
 use crate::utils::Classifier;
 
-use super::{Hunk, Line, Patch, NO_NEWLINE_AT_EOF};
+use super::{BinaryPatch, Filename, Hunk, Line, Patch, PatchSet, NO_NEWLINE_AT_EOF};
 use nu_ansi_term::{Color, Style};
 use std::{
     fmt::{Display, Formatter, Result},
@@ -13,6 +14,8 @@ use std::{
 #[derive(Debug)]
 pub struct PatchFormatter {
     with_color: bool,
+    suppress_blank_empty: Option<bool>,
+    tokenizer: Box<dyn Tokenizer>,
 
     context: Style,
     delete: Style,
@@ -27,6 +30,8 @@ impl PatchFormatter {
     pub fn new() -> Self {
         Self {
             with_color: false,
+            suppress_blank_empty: None,
+            tokenizer: Box::new(GroupTokenizer::default()),
 
             context: Style::new(),
             delete: Color::Red.normal(),
@@ -43,6 +48,26 @@ impl PatchFormatter {
         self
     }
 
+    /// Set whether empty context lines are written without the leading space
+    ///
+    /// This mirrors GNU diff's `--suppress-blank-empty`. By default a parsed
+    /// patch is written the same way as its input, with the leading space if
+    /// it had any empty context lines written that way like `git diff` does,
+    /// while a created patch is written without it.
+    pub fn suppress_blank_empty(mut self, suppress_blank_empty: bool) -> Self {
+        self.suppress_blank_empty = Some(suppress_blank_empty);
+        self
+    }
+
+    /// Set the tokenizer used to find the words which changed within a hunk
+    /// when formatting with color
+    ///
+    /// Defaults to `GroupTokenizer::default()`.
+    pub fn with_tokenizer<T: Tokenizer + 'static>(mut self, tokenizer: T) -> Self {
+        self.tokenizer = Box::new(tokenizer);
+        self
+    }
+
     /// Returns a `Display` impl which can be used to print a Patch
     pub fn fmt_patch<'a>(&'a self, patch: &'a Patch<'a, str>) -> impl Display + 'a {
         PatchDisplay { f: self, patch }
@@ -56,28 +81,70 @@ impl PatchFormatter {
         PatchDisplay { f: self, patch }.write_into(w)
     }
 
-    fn fmt_hunk<'a>(&'a self, hunk: &'a Hunk<'a, str>) -> impl Display + 'a {
-        HunkDisplay { f: self, hunk }
+    /// Returns a `Display` impl which can be used to print a PatchSet
+    pub fn fmt_patch_set<'a>(&'a self, patch_set: &'a PatchSet<'a, str>) -> impl Display + 'a {
+        PatchSetDisplay { f: self, patch_set }
     }
 
-    fn write_hunk_into<T: AsRef<[u8]> + ?Sized, W: io::Write>(
+    /// Write a PatchSet, including any text surrounding the individual patches, into `w`
+    pub fn write_patch_set_into<T: ToOwned + AsRef<[u8]> + ?Sized, W: io::Write>(
+        &self,
+        patch_set: &PatchSet<'_, T>,
+        w: W,
+    ) -> io::Result<()> {
+        PatchSetDisplay { f: self, patch_set }.write_into(w)
+    }
+
+    fn fmt_hunk<'a>(
+        &'a self,
+        hunk: &'a Hunk<'a, str>,
+        suppress_blank_empty: bool,
+    ) -> impl Display + 'a {
+        HunkDisplay {
+            f: self,
+            hunk,
+            suppress_blank_empty,
+        }
+    }
+
+    fn write_hunk_into<T: ToOwned + AsRef<[u8]> + ?Sized, W: io::Write>(
         &self,
         hunk: &Hunk<'_, T>,
+        suppress_blank_empty: bool,
         w: W,
     ) -> io::Result<()> {
-        HunkDisplay { f: self, hunk }.write_into(w)
+        HunkDisplay {
+            f: self,
+            hunk,
+            suppress_blank_empty,
+        }
+        .write_into(w)
     }
 
-    fn fmt_line<'a>(&'a self, line: &'a Line<'a, str>) -> impl Display + 'a {
-        LineDisplay { f: self, line }
+    fn fmt_line<'a>(
+        &'a self,
+        line: &'a Line<'a, str>,
+        suppress_blank_empty: bool,
+    ) -> impl Display + 'a {
+        LineDisplay {
+            f: self,
+            line,
+            suppress_blank_empty,
+        }
     }
 
-    fn write_line_into<T: AsRef<[u8]> + ?Sized, W: io::Write>(
+    fn write_line_into<T: ToOwned + AsRef<[u8]> + ?Sized, W: io::Write>(
         &self,
         line: &Line<'_, T>,
+        suppress_blank_empty: bool,
         w: W,
     ) -> io::Result<()> {
-        LineDisplay { f: self, line }.write_into(w)
+        LineDisplay {
+            f: self,
+            line,
+            suppress_blank_empty,
+        }
+        .write_into(w)
     }
 }
 
@@ -87,25 +154,99 @@ impl Default for PatchFormatter {
     }
 }
 
+struct PatchSetDisplay<'a, T: ToOwned + ?Sized> {
+    f: &'a PatchFormatter,
+    patch_set: &'a PatchSet<'a, T>,
+}
+
+impl<T: ToOwned + AsRef<[u8]> + ?Sized> PatchSetDisplay<'_, T> {
+    fn write_into<W: io::Write>(&self, mut w: W) -> io::Result<()> {
+        for (preamble, patch) in self.patch_set.preambles.iter().zip(&self.patch_set.patches) {
+            if let Some(preamble) = preamble.as_deref() {
+                w.write_all(preamble.as_ref())?;
+            }
+            self.f.write_patch_into(patch, &mut w)?;
+        }
+
+        if let Some(trailer) = self.patch_set.trailer() {
+            w.write_all(trailer.as_ref())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for PatchSetDisplay<'_, str> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        for (preamble, patch) in self.patch_set.preambles.iter().zip(&self.patch_set.patches) {
+            if let Some(preamble) = preamble {
+                f.write_str(preamble)?;
+            }
+            write!(f, "{}", self.f.fmt_patch(patch))?;
+        }
+
+        if let Some(trailer) = self.patch_set.trailer() {
+            f.write_str(trailer)?;
+        }
+
+        Ok(())
+    }
+}
+
 struct PatchDisplay<'a, T: ToOwned + ?Sized> {
     f: &'a PatchFormatter,
     patch: &'a Patch<'a, T>,
 }
 
+impl<T: ToOwned + ?Sized> PatchDisplay<'_, T> {
+    fn suppress_blank_empty(&self) -> bool {
+        self.f
+            .suppress_blank_empty
+            .unwrap_or(!self.patch.blank_context_space)
+    }
+
+    fn has_header(&self) -> bool {
+        self.patch.git.is_some() || self.patch.original.is_some() || self.patch.modified.is_some()
+    }
+
+    // The names used in a "Binary files a/x and b/y differ" line, where `None` is written as
+    // "/dev/null"
+    fn binary_marker_filenames(&self) -> (Option<&Filename<'_, T>>, Option<&Filename<'_, T>>) {
+        match &self.patch.git {
+            Some(git) => (
+                Some(&git.original).filter(|_| !git.is_new_file()),
+                Some(&git.modified).filter(|_| !git.is_deleted_file()),
+            ),
+            None => (self.patch.original.as_ref(), self.patch.modified.as_ref()),
+        }
+    }
+}
+
 impl<T: ToOwned + AsRef<[u8]> + ?Sized> PatchDisplay<'_, T> {
     fn write_into<W: io::Write>(&self, mut w: W) -> io::Result<()> {
-        if self.patch.original.is_some() || self.patch.modified.is_some() {
+        if self.has_header() {
             if self.f.with_color {
                 write!(w, "{}", self.f.patch_header.prefix())?;
             }
+            if let Some(git) = &self.patch.git {
+                git.write_into(&mut w)?;
+            }
             if let Some(original) = &self.patch.original {
                 write!(w, "--- ")?;
                 original.write_into(&mut w)?;
+                if let Some(timestamp) = self.patch.original_timestamp() {
+                    write!(w, "\t")?;
+                    w.write_all(timestamp.as_ref())?;
+                }
                 writeln!(w)?;
             }
             if let Some(modified) = &self.patch.modified {
                 write!(w, "+++ ")?;
                 modified.write_into(&mut w)?;
+                if let Some(timestamp) = self.patch.modified_timestamp() {
+                    write!(w, "\t")?;
+                    w.write_all(timestamp.as_ref())?;
+                }
                 writeln!(w)?;
             }
             if self.f.with_color {
@@ -113,8 +254,28 @@ impl<T: ToOwned + AsRef<[u8]> + ?Sized> PatchDisplay<'_, T> {
             }
         }
 
+        match &self.patch.binary {
+            Some(BinaryPatch::Marker) => {
+                let (original, modified) = self.binary_marker_filenames();
+                write!(w, "Binary files ")?;
+                match original {
+                    Some(original) => original.write_into(&mut w)?,
+                    None => write!(w, "/dev/null")?,
+                }
+                write!(w, " and ")?;
+                match modified {
+                    Some(modified) => modified.write_into(&mut w)?,
+                    None => write!(w, "/dev/null")?,
+                }
+                writeln!(w, " differ")?;
+            }
+            Some(binary) => binary.write_into(&mut w)?,
+            None => {}
+        }
+
         for hunk in &self.patch.hunks {
-            self.f.write_hunk_into(hunk, &mut w)?;
+            self.f
+                .write_hunk_into(hunk, self.suppress_blank_empty(), &mut w)?;
         }
 
         Ok(())
@@ -123,35 +284,69 @@ impl<T: ToOwned + AsRef<[u8]> + ?Sized> PatchDisplay<'_, T> {
 
 impl Display for PatchDisplay<'_, str> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        if self.patch.original.is_some() || self.patch.modified.is_some() {
+        if self.has_header() {
             if self.f.with_color {
                 write!(f, "{}", self.f.patch_header.prefix())?;
             }
+            if let Some(git) = &self.patch.git {
+                write!(f, "{}", git)?;
+            }
             if let Some(original) = &self.patch.original {
-                writeln!(f, "--- {}", original)?;
+                write!(f, "--- {}", original)?;
+                if let Some(timestamp) = self.patch.original_timestamp() {
+                    write!(f, "\t{}", timestamp)?;
+                }
+                writeln!(f)?;
             }
             if let Some(modified) = &self.patch.modified {
-                writeln!(f, "+++ {}", modified)?;
+                write!(f, "+++ {}", modified)?;
+                if let Some(timestamp) = self.patch.modified_timestamp() {
+                    write!(f, "\t{}", timestamp)?;
+                }
+                writeln!(f)?;
             }
             if self.f.with_color {
                 write!(f, "{}", self.f.patch_header.suffix())?;
             }
         }
 
+        match &self.patch.binary {
+            Some(BinaryPatch::Marker) => {
+                let (original, modified) = self.binary_marker_filenames();
+                let dev_null = |name: Option<&Filename<'_, str>>| {
+                    name.map_or_else(|| "/dev/null".to_owned(), ToString::to_string)
+                };
+                writeln!(
+                    f,
+                    "Binary files {} and {} differ",
+                    dev_null(original),
+                    dev_null(modified)
+                )?;
+            }
+            Some(binary) => {
+                // The binary data is base85 encoded so it is always valid utf8
+                let mut bytes = Vec::new();
+                binary.write_into(&mut bytes).map_err(|_| std::fmt::Error)?;
+                f.write_str(std::str::from_utf8(&bytes).map_err(|_| std::fmt::Error)?)?;
+            }
+            None => {}
+        }
+
         for hunk in &self.patch.hunks {
-            write!(f, "{}", self.f.fmt_hunk(hunk))?;
+            write!(f, "{}", self.f.fmt_hunk(hunk, self.suppress_blank_empty()))?;
         }
 
         Ok(())
     }
 }
 
-struct HunkDisplay<'a, T: ?Sized> {
+struct HunkDisplay<'a, T: ToOwned + ?Sized> {
     f: &'a PatchFormatter,
     hunk: &'a Hunk<'a, T>,
+    suppress_blank_empty: bool,
 }
 
-impl<T: AsRef<[u8]> + ?Sized> HunkDisplay<'_, T> {
+impl<T: ToOwned + AsRef<[u8]> + ?Sized> HunkDisplay<'_, T> {
     fn write_into<W: io::Write>(&self, mut w: W) -> io::Result<()> {
         if self.f.with_color {
             write!(w, "{}", self.f.hunk_header.prefix())?;
@@ -161,12 +356,11 @@ impl<T: AsRef<[u8]> + ?Sized> HunkDisplay<'_, T> {
             write!(w, "{}", self.f.hunk_header.suffix())?;
         }
 
-        if let Some(ctx) = self.hunk.function_context {
+        if let Some(ctx) = self.hunk.function_context() {
             write!(w, " ")?;
             if self.f.with_color {
                 write!(w, "{}", self.f.function_context.prefix())?;
             }
-            write!(w, " ")?;
             w.write_all(ctx.as_ref())?;
             if self.f.with_color {
                 write!(w, "{}", self.f.function_context.suffix())?;
@@ -175,7 +369,8 @@ impl<T: AsRef<[u8]> + ?Sized> HunkDisplay<'_, T> {
         writeln!(w)?;
 
         for line in &self.hunk.lines {
-            self.f.write_line_into(line, &mut w)?;
+            self.f
+                .write_line_into(line, self.suppress_blank_empty, &mut w)?;
         }
 
         Ok(())
@@ -192,12 +387,12 @@ impl Display for HunkDisplay<'_, str> {
             write!(f, "{}", self.f.hunk_header.suffix())?;
         }
 
-        if let Some(ctx) = self.hunk.function_context {
+        if let Some(ctx) = self.hunk.function_context() {
             write!(f, " ")?;
             if self.f.with_color {
                 write!(f, "{}", self.f.function_context.prefix())?;
             }
-            write!(f, " {}", ctx)?;
+            write!(f, "{}", ctx)?;
             if self.f.with_color {
                 write!(f, "{}", self.f.function_context.suffix())?;
             }
@@ -212,29 +407,31 @@ impl Display for HunkDisplay<'_, str> {
 
         for line in &self.hunk.lines {
             if !self.f.with_color {
-                write!(f, "{}", self.f.fmt_line(line))?;
+                write!(f, "{}", self.f.fmt_line(line, self.suppress_blank_empty))?;
                 continue;
             }
             if let Line::Context(_) = line {
                 is_context = true;
-                write!(f, "{}", self.f.fmt_line(line))?;
+                write!(f, "{}", self.f.fmt_line(line, self.suppress_blank_empty))?;
                 continue;
             }
 
             if is_context {
                 is_context = false;
 
-                let deleted = *original.next().expect("expected to find a deleted string");
+                let deleted: &str = original.next().expect("expected to find a deleted string");
 
-                let inserted = *modified
+                let inserted: &str = modified
                     .next()
                     .expect("expected to find an inserted string");
 
                 let mut classifier = Classifier::default();
-                let (deleted, deleted_ids) = classifier.classify_groups(deleted);
-                let (inserted, inserted_ids) = classifier.classify_groups(inserted);
+                let (deleted, deleted_ids) =
+                    classifier.classify_groups(deleted, &*self.f.tokenizer);
+                let (inserted, inserted_ids) =
+                    classifier.classify_groups(inserted, &*self.f.tokenizer);
                 let solution = {
-                    let mut solution = myers::diff(&deleted_ids, &inserted_ids);
+                    let mut solution = myers::diff(&deleted_ids, &inserted_ids, None);
                     cleanup::compact(&mut solution);
                     solution
                 };
@@ -303,24 +500,26 @@ impl Display for HunkDisplay<'_, str> {
     }
 }
 
-struct LineDisplay<'a, T: ?Sized> {
+struct LineDisplay<'a, T: ToOwned + ?Sized> {
     f: &'a PatchFormatter,
     line: &'a Line<'a, T>,
+    suppress_blank_empty: bool,
 }
 
-impl<T: AsRef<[u8]> + ?Sized> LineDisplay<'_, T> {
+impl<T: ToOwned + AsRef<[u8]> + ?Sized> LineDisplay<'_, T> {
     fn write_into<W: io::Write>(&self, mut w: W) -> io::Result<()> {
-        let (sign, line, style) = match self.line {
-            Line::Context(line) => (' ', line.as_ref(), self.f.context),
-            Line::Delete(line) => ('-', line.as_ref(), self.f.delete),
-            Line::Insert(line) => ('+', line.as_ref(), self.f.insert),
+        let (sign, style) = match self.line {
+            Line::Context(_) => (' ', self.f.context),
+            Line::Delete(_) => ('-', self.f.delete),
+            Line::Insert(_) => ('+', self.f.insert),
         };
+        let line = self.line.inner().as_ref();
 
         if self.f.with_color {
             write!(w, "{}", style.prefix())?;
         }
 
-        if sign == ' ' && line == b"\n" {
+        if sign == ' ' && line == b"\n" && self.suppress_blank_empty {
             w.write_all(line)?;
         } else {
             write!(w, "{}", sign)?;
@@ -342,17 +541,18 @@ impl<T: AsRef<[u8]> + ?Sized> LineDisplay<'_, T> {
 
 impl Display for LineDisplay<'_, str> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        let (sign, line, style) = match self.line {
-            Line::Context(line) => (' ', line, self.f.context),
-            Line::Delete(line) => ('-', line, self.f.delete),
-            Line::Insert(line) => ('+', line, self.f.insert),
+        let (sign, style) = match self.line {
+            Line::Context(_) => (' ', self.f.context),
+            Line::Delete(_) => ('-', self.f.delete),
+            Line::Insert(_) => ('+', self.f.insert),
         };
+        let line = self.line.inner();
 
         if self.f.with_color {
             write!(f, "{}", style.prefix())?;
         }
 
-        if sign == ' ' && *line == "\n" {
+        if sign == ' ' && line == "\n" && self.suppress_blank_empty {
             write!(f, "{}", line)?;
         } else {
             write!(f, "{}{}", sign, line)?;
@@ -0,0 +1,661 @@
+use super::{into_owned, Filename};
+use std::{borrow::Cow, fmt, io};
+
+/// The `diff --git` line and extended header lines of a patch generated by git
+///
+/// ```text
+/// diff --git a/old.txt b/new.txt
+/// similarity index 90%
+/// rename from old.txt
+/// rename to new.txt
+/// index 2f7a0c1..5d2e4b3 100644
+/// ```
+#[derive(PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: serde::Serialize",
+        deserialize = "T::Owned: serde::Deserialize<'de>"
+    ))
+)]
+pub struct GitHeader<'a, T: ToOwned + ?Sized> {
+    pub(super) original: Filename<'a, T>,
+    pub(super) modified: Filename<'a, T>,
+    pub(super) extended_headers: Vec<ExtendedHeader<'a, T>>,
+}
+
+impl<'a, T: ToOwned + ?Sized> GitHeader<'a, T> {
+    /// Return the name of the old file from the `diff --git` line
+    pub fn original(&self) -> &T {
+        &self.original
+    }
+
+    /// Return the name of the new file from the `diff --git` line
+    pub fn modified(&self) -> &T {
+        &self.modified
+    }
+
+    /// Returns the extended header lines in the order they appeared
+    pub fn extended_headers(&self) -> &[ExtendedHeader<'a, T>] {
+        &self.extended_headers
+    }
+
+    /// Returns `true` if the patch creates a new file
+    pub fn is_new_file(&self) -> bool {
+        self.extended_headers
+            .iter()
+            .any(|header| matches!(header, ExtendedHeader::NewFileMode(_)))
+    }
+
+    /// Returns `true` if the patch deletes a file
+    pub fn is_deleted_file(&self) -> bool {
+        self.extended_headers
+            .iter()
+            .any(|header| matches!(header, ExtendedHeader::DeletedFileMode(_)))
+    }
+
+    /// Returns the mode of the old file, if known
+    pub fn old_mode(&self) -> Option<u32> {
+        self.extended_headers
+            .iter()
+            .find_map(|header| match header {
+                ExtendedHeader::OldMode(mode) | ExtendedHeader::DeletedFileMode(mode) => {
+                    Some(*mode)
+                }
+                ExtendedHeader::Index { mode, .. } => *mode,
+                _ => None,
+            })
+    }
+
+    /// Returns the mode of the new file, if known
+    pub fn new_mode(&self) -> Option<u32> {
+        self.extended_headers
+            .iter()
+            .find_map(|header| match header {
+                ExtendedHeader::NewMode(mode) | ExtendedHeader::NewFileMode(mode) => Some(*mode),
+                ExtendedHeader::Index { mode, .. } => *mode,
+                _ => None,
+            })
+    }
+
+    /// Returns the old and new paths if the patch renames a file
+    pub fn rename(&self) -> Option<(&T, &T)> {
+        let from = self
+            .extended_headers
+            .iter()
+            .find_map(|header| match header {
+                ExtendedHeader::RenameFrom(path) => Some(path.as_ref()),
+                _ => None,
+            })?;
+        let to = self
+            .extended_headers
+            .iter()
+            .find_map(|header| match header {
+                ExtendedHeader::RenameTo(path) => Some(path.as_ref()),
+                _ => None,
+            })?;
+        Some((from, to))
+    }
+
+    /// Returns the old and new paths if the patch copies a file
+    pub fn copy(&self) -> Option<(&T, &T)> {
+        let from = self
+            .extended_headers
+            .iter()
+            .find_map(|header| match header {
+                ExtendedHeader::CopyFrom(path) => Some(path.as_ref()),
+                _ => None,
+            })?;
+        let to = self
+            .extended_headers
+            .iter()
+            .find_map(|header| match header {
+                ExtendedHeader::CopyTo(path) => Some(path.as_ref()),
+                _ => None,
+            })?;
+        Some((from, to))
+    }
+}
+
+impl<'a, T: ToOwned + ?Sized> GitHeader<'a, T> {
+    // Returns the header of a patch which undoes this one, see `Patch::reverse`
+    pub(super) fn reverse(&self) -> Self {
+        let mut extended_headers: Vec<_> = self
+            .extended_headers
+            .iter()
+            .map(ExtendedHeader::reverse)
+            .collect();
+        // Keep pairs of headers in the order git writes them, e.g. `old mode` before `new mode`
+        for i in 1..extended_headers.len() {
+            let is_pair = matches!(
+                (&extended_headers[i - 1], &extended_headers[i]),
+                (ExtendedHeader::NewMode(_), ExtendedHeader::OldMode(_))
+                    | (ExtendedHeader::RenameTo(_), ExtendedHeader::RenameFrom(_))
+                    | (ExtendedHeader::CopyTo(_), ExtendedHeader::CopyFrom(_))
+            );
+            if is_pair {
+                extended_headers.swap(i - 1, i);
+            }
+        }
+
+        Self {
+            original: self.modified.clone(),
+            modified: self.original.clone(),
+            extended_headers,
+        }
+    }
+}
+
+impl<T: ToOwned + ?Sized + 'static> GitHeader<'_, T> {
+    pub(super) fn into_owned(self) -> GitHeader<'static, T> {
+        GitHeader {
+            original: self.original.into_owned(),
+            modified: self.modified.into_owned(),
+            extended_headers: self
+                .extended_headers
+                .into_iter()
+                .map(ExtendedHeader::into_owned)
+                .collect(),
+        }
+    }
+}
+
+impl<T: ToOwned + AsRef<[u8]> + ?Sized> GitHeader<'_, T> {
+    pub(super) fn write_into<W: io::Write>(&self, mut w: W) -> io::Result<()> {
+        write!(w, "diff --git ")?;
+        self.original.write_into(&mut w)?;
+        write!(w, " ")?;
+        self.modified.write_into(&mut w)?;
+        writeln!(w)?;
+
+        for header in &self.extended_headers {
+            header.write_into(&mut w)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: ToOwned + ?Sized> Clone for GitHeader<'_, T> {
+    fn clone(&self) -> Self {
+        Self {
+            original: self.original.clone(),
+            modified: self.modified.clone(),
+            extended_headers: self.extended_headers.clone(),
+        }
+    }
+}
+
+impl fmt::Display for GitHeader<'_, str> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "diff --git {} {}", self.original, self.modified)?;
+        for header in &self.extended_headers {
+            write!(f, "{}", header)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: ?Sized, O> fmt::Debug for GitHeader<'_, T>
+where
+    T: ToOwned<Owned = O> + fmt::Debug,
+    O: std::borrow::Borrow<T> + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GitHeader")
+            .field("original", &self.original)
+            .field("modified", &self.modified)
+            .field("extended_headers", &self.extended_headers)
+            .finish()
+    }
+}
+
+/// A single extended header line following a `diff --git` line
+///
+/// File modes are stored as their numeric value, e.g. `0o100644`.
+#[derive(PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: serde::Serialize",
+        deserialize = "T::Owned: serde::Deserialize<'de>"
+    ))
+)]
+pub enum ExtendedHeader<'a, T: ToOwned + ?Sized> {
+    /// `old mode <mode>`
+    OldMode(u32),
+    /// `new mode <mode>`
+    NewMode(u32),
+    /// `deleted file mode <mode>`
+    DeletedFileMode(u32),
+    /// `new file mode <mode>`
+    NewFileMode(u32),
+    /// `rename from <path>`
+    RenameFrom(Cow<'a, T>),
+    /// `rename to <path>`
+    RenameTo(Cow<'a, T>),
+    /// `copy from <path>`
+    CopyFrom(Cow<'a, T>),
+    /// `copy to <path>`
+    CopyTo(Cow<'a, T>),
+    /// `similarity index <number>%`
+    SimilarityIndex(u8),
+    /// `dissimilarity index <number>%`
+    DissimilarityIndex(u8),
+    /// `index <hash>..<hash> <mode>`
+    ///
+    /// The mode is only present if it is the same for both files.
+    Index {
+        original: Cow<'a, T>,
+        modified: Cow<'a, T>,
+        mode: Option<u32>,
+    },
+}
+
+impl<T: ToOwned + ?Sized> ExtendedHeader<'_, T> {
+    fn reverse(&self) -> Self {
+        match self {
+            ExtendedHeader::OldMode(mode) => ExtendedHeader::NewMode(*mode),
+            ExtendedHeader::NewMode(mode) => ExtendedHeader::OldMode(*mode),
+            ExtendedHeader::DeletedFileMode(mode) => ExtendedHeader::NewFileMode(*mode),
+            ExtendedHeader::NewFileMode(mode) => ExtendedHeader::DeletedFileMode(*mode),
+            ExtendedHeader::RenameFrom(path) => ExtendedHeader::RenameTo(path.clone()),
+            ExtendedHeader::RenameTo(path) => ExtendedHeader::RenameFrom(path.clone()),
+            ExtendedHeader::CopyFrom(path) => ExtendedHeader::CopyTo(path.clone()),
+            ExtendedHeader::CopyTo(path) => ExtendedHeader::CopyFrom(path.clone()),
+            ExtendedHeader::Index {
+                original,
+                modified,
+                mode,
+            } => ExtendedHeader::Index {
+                original: modified.clone(),
+                modified: original.clone(),
+                mode: *mode,
+            },
+            header => header.clone(),
+        }
+    }
+}
+
+impl<T: ToOwned + ?Sized + 'static> ExtendedHeader<'_, T> {
+    fn into_owned(self) -> ExtendedHeader<'static, T> {
+        match self {
+            ExtendedHeader::OldMode(mode) => ExtendedHeader::OldMode(mode),
+            ExtendedHeader::NewMode(mode) => ExtendedHeader::NewMode(mode),
+            ExtendedHeader::DeletedFileMode(mode) => ExtendedHeader::DeletedFileMode(mode),
+            ExtendedHeader::NewFileMode(mode) => ExtendedHeader::NewFileMode(mode),
+            ExtendedHeader::RenameFrom(path) => ExtendedHeader::RenameFrom(into_owned(path)),
+            ExtendedHeader::RenameTo(path) => ExtendedHeader::RenameTo(into_owned(path)),
+            ExtendedHeader::CopyFrom(path) => ExtendedHeader::CopyFrom(into_owned(path)),
+            ExtendedHeader::CopyTo(path) => ExtendedHeader::CopyTo(into_owned(path)),
+            ExtendedHeader::SimilarityIndex(n) => ExtendedHeader::SimilarityIndex(n),
+            ExtendedHeader::DissimilarityIndex(n) => ExtendedHeader::DissimilarityIndex(n),
+            ExtendedHeader::Index {
+                original,
+                modified,
+                mode,
+            } => ExtendedHeader::Index {
+                original: into_owned(original),
+                modified: into_owned(modified),
+                mode,
+            },
+        }
+    }
+}
+
+impl<T: ToOwned + AsRef<[u8]> + ?Sized> ExtendedHeader<'_, T> {
+    fn write_into<W: io::Write>(&self, mut w: W) -> io::Result<()> {
+        let (prefix, path) = match self {
+            ExtendedHeader::OldMode(mode) => return writeln!(w, "old mode {:06o}", mode),
+            ExtendedHeader::NewMode(mode) => return writeln!(w, "new mode {:06o}", mode),
+            ExtendedHeader::DeletedFileMode(mode) => {
+                return writeln!(w, "deleted file mode {:06o}", mode)
+            }
+            ExtendedHeader::NewFileMode(mode) => return writeln!(w, "new file mode {:06o}", mode),
+            ExtendedHeader::SimilarityIndex(n) => return writeln!(w, "similarity index {}%", n),
+            ExtendedHeader::DissimilarityIndex(n) => {
+                return writeln!(w, "dissimilarity index {}%", n)
+            }
+            ExtendedHeader::Index {
+                original,
+                modified,
+                mode,
+            } => {
+                write!(w, "index ")?;
+                w.write_all((**original).as_ref())?;
+                write!(w, "..")?;
+                w.write_all((**modified).as_ref())?;
+                if let Some(mode) = mode {
+                    write!(w, " {:06o}", mode)?;
+                }
+                return writeln!(w);
+            }
+            ExtendedHeader::RenameFrom(path) => ("rename from ", path),
+            ExtendedHeader::RenameTo(path) => ("rename to ", path),
+            ExtendedHeader::CopyFrom(path) => ("copy from ", path),
+            ExtendedHeader::CopyTo(path) => ("copy to ", path),
+        };
+
+        write!(w, "{}", prefix)?;
+        Filename(Cow::Borrowed(path.as_ref())).write_into(&mut w)?;
+        writeln!(w)
+    }
+}
+
+impl<T: ToOwned + ?Sized> Clone for ExtendedHeader<'_, T> {
+    fn clone(&self) -> Self {
+        match self {
+            ExtendedHeader::OldMode(mode) => ExtendedHeader::OldMode(*mode),
+            ExtendedHeader::NewMode(mode) => ExtendedHeader::NewMode(*mode),
+            ExtendedHeader::DeletedFileMode(mode) => ExtendedHeader::DeletedFileMode(*mode),
+            ExtendedHeader::NewFileMode(mode) => ExtendedHeader::NewFileMode(*mode),
+            ExtendedHeader::RenameFrom(path) => ExtendedHeader::RenameFrom(path.clone()),
+            ExtendedHeader::RenameTo(path) => ExtendedHeader::RenameTo(path.clone()),
+            ExtendedHeader::CopyFrom(path) => ExtendedHeader::CopyFrom(path.clone()),
+            ExtendedHeader::CopyTo(path) => ExtendedHeader::CopyTo(path.clone()),
+            ExtendedHeader::SimilarityIndex(n) => ExtendedHeader::SimilarityIndex(*n),
+            ExtendedHeader::DissimilarityIndex(n) => ExtendedHeader::DissimilarityIndex(*n),
+            ExtendedHeader::Index {
+                original,
+                modified,
+                mode,
+            } => ExtendedHeader::Index {
+                original: original.clone(),
+                modified: modified.clone(),
+                mode: *mode,
+            },
+        }
+    }
+}
+
+impl fmt::Display for ExtendedHeader<'_, str> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtendedHeader::OldMode(mode) => writeln!(f, "old mode {:06o}", mode),
+            ExtendedHeader::NewMode(mode) => writeln!(f, "new mode {:06o}", mode),
+            ExtendedHeader::DeletedFileMode(mode) => writeln!(f, "deleted file mode {:06o}", mode),
+            ExtendedHeader::NewFileMode(mode) => writeln!(f, "new file mode {:06o}", mode),
+            ExtendedHeader::RenameFrom(path) => {
+                writeln!(f, "rename from {}", Filename(Cow::Borrowed(&**path)))
+            }
+            ExtendedHeader::RenameTo(path) => {
+                writeln!(f, "rename to {}", Filename(Cow::Borrowed(&**path)))
+            }
+            ExtendedHeader::CopyFrom(path) => {
+                writeln!(f, "copy from {}", Filename(Cow::Borrowed(&**path)))
+            }
+            ExtendedHeader::CopyTo(path) => {
+                writeln!(f, "copy to {}", Filename(Cow::Borrowed(&**path)))
+            }
+            ExtendedHeader::SimilarityIndex(n) => writeln!(f, "similarity index {}%", n),
+            ExtendedHeader::DissimilarityIndex(n) => writeln!(f, "dissimilarity index {}%", n),
+            ExtendedHeader::Index {
+                original,
+                modified,
+                mode,
+            } => {
+                write!(f, "index {}..{}", original, modified)?;
+                if let Some(mode) = mode {
+                    write!(f, " {:06o}", mode)?;
+                }
+                writeln!(f)
+            }
+        }
+    }
+}
+
+impl<T: ?Sized, O> fmt::Debug for ExtendedHeader<'_, T>
+where
+    T: ToOwned<Owned = O> + fmt::Debug,
+    O: std::borrow::Borrow<T> + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtendedHeader::OldMode(mode) => f.debug_tuple("OldMode").field(mode).finish(),
+            ExtendedHeader::NewMode(mode) => f.debug_tuple("NewMode").field(mode).finish(),
+            ExtendedHeader::DeletedFileMode(mode) => {
+                f.debug_tuple("DeletedFileMode").field(mode).finish()
+            }
+            ExtendedHeader::NewFileMode(mode) => f.debug_tuple("NewFileMode").field(mode).finish(),
+            ExtendedHeader::RenameFrom(path) => f.debug_tuple("RenameFrom").field(path).finish(),
+            ExtendedHeader::RenameTo(path) => f.debug_tuple("RenameTo").field(path).finish(),
+            ExtendedHeader::CopyFrom(path) => f.debug_tuple("CopyFrom").field(path).finish(),
+            ExtendedHeader::CopyTo(path) => f.debug_tuple("CopyTo").field(path).finish(),
+            ExtendedHeader::SimilarityIndex(n) => {
+                f.debug_tuple("SimilarityIndex").field(n).finish()
+            }
+            ExtendedHeader::DissimilarityIndex(n) => {
+                f.debug_tuple("DissimilarityIndex").field(n).finish()
+            }
+            ExtendedHeader::Index {
+                original,
+                modified,
+                mode,
+            } => f
+                .debug_struct("Index")
+                .field("original", original)
+                .field("modified", modified)
+                .field("mode", mode)
+                .finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ExtendedHeader, Patch, PatchSet};
+
+    #[test]
+    fn rename_only() {
+        let s = "\
+diff --git a/old.txt b/new.txt
+similarity index 100%
+rename from old.txt
+rename to new.txt
+";
+        let patch = Patch::from_str(s).unwrap();
+        let git = patch.git_header().unwrap();
+        assert_eq!(git.original(), "a/old.txt");
+        assert_eq!(git.modified(), "b/new.txt");
+        assert_eq!(git.rename(), Some(("old.txt", "new.txt")));
+        assert_eq!(
+            git.extended_headers()[0],
+            ExtendedHeader::SimilarityIndex(100)
+        );
+        assert_eq!(patch.original(), None);
+        assert!(patch.hunks().is_empty());
+        assert_eq!(patch.to_string(), s);
+        assert_eq!(
+            Patch::from_bytes(s.as_bytes()).unwrap().to_bytes(),
+            s.as_bytes()
+        );
+    }
+
+    #[test]
+    fn mode_only() {
+        let s = "\
+diff --git a/run.sh b/run.sh
+old mode 100644
+new mode 100755
+";
+        let patch = Patch::from_str(s).unwrap();
+        let git = patch.git_header().unwrap();
+        assert_eq!(git.old_mode(), Some(0o100644));
+        assert_eq!(git.new_mode(), Some(0o100755));
+        assert_eq!(patch.to_string(), s);
+    }
+
+    #[test]
+    fn renamed_and_modified() {
+        let s = "\
+diff --git a/src/old.rs b/src/new.rs
+old mode 100644
+new mode 100755
+similarity index 90%
+rename from src/old.rs
+rename to src/new.rs
+index 2f7a0c1..5d2e4b3
+--- a/src/old.rs
++++ b/src/new.rs
+@@ -1 +1 @@
+-fn main() {}
++fn main() { }
+";
+        let patch = Patch::from_str(s).unwrap();
+        assert_eq!(patch.original(), Some("a/src/old.rs"));
+        assert_eq!(
+            patch.git_header().unwrap().extended_headers()[5],
+            ExtendedHeader::Index {
+                original: "2f7a0c1".into(),
+                modified: "5d2e4b3".into(),
+                mode: None,
+            }
+        );
+        assert_eq!(patch.to_string(), s);
+    }
+
+    #[test]
+    fn reverse() {
+        let s = "\
+diff --git a/src/old.rs b/src/new.rs
+old mode 100644
+new mode 100755
+similarity index 90%
+rename from src/old.rs
+rename to src/new.rs
+index 2f7a0c1..5d2e4b3
+--- a/src/old.rs
++++ b/src/new.rs
+@@ -1,2 +1,2 @@
+-fn main() {}
++fn main() { }
+ // end
+";
+        let expected = "\
+diff --git b/src/new.rs a/src/old.rs
+old mode 100755
+new mode 100644
+similarity index 90%
+rename from src/new.rs
+rename to src/old.rs
+index 5d2e4b3..2f7a0c1
+--- b/src/new.rs
++++ a/src/old.rs
+@@ -1,2 +1,2 @@
+-fn main() { }
++fn main() {}
+ // end
+";
+        let patch = Patch::from_str(s).unwrap();
+        let reversed = patch.reverse();
+        assert_eq!(reversed.to_string(), expected);
+        assert_eq!(reversed.reverse(), patch);
+
+        let s = "\
+diff --git a/new b/new
+new file mode 100644
+index 0000000..3333333
+--- /dev/null
++++ b/new
+@@ -0,0 +1 @@
++new
+";
+        let reversed = Patch::from_str(s).unwrap().reverse();
+        assert!(reversed.git_header().unwrap().is_deleted_file());
+        assert_eq!(reversed.modified(), Some("/dev/null"));
+        assert_eq!(crate::apply("new\n", &reversed).unwrap(), "");
+    }
+
+    #[test]
+    fn new_copied_and_deleted_files() {
+        let s = "\
+diff --git a/new b/new
+new file mode 100644
+index 0000000..3333333
+--- /dev/null
++++ b/new
+@@ -0,0 +1 @@
++new
+diff --git a/new b/copy
+similarity index 100%
+copy from new
+copy to copy
+diff --git a/gone b/gone
+deleted file mode 120000
+index 4444444..0000000
+--- a/gone
++++ /dev/null
+@@ -1 +0,0 @@
+-gone
+\\ No newline at end of file
+diff --git a/same b/same
+index 5555555..6666666 100644
+--- a/same
++++ b/same
+@@ -1 +1 @@
+-a
++b
+";
+        let set = PatchSet::from_str(s).unwrap();
+        let headers = set
+            .patches()
+            .iter()
+            .map(|patch| patch.git_header().unwrap())
+            .collect::<Vec<_>>();
+        assert!(headers[0].is_new_file());
+        assert_eq!(headers[0].new_mode(), Some(0o100644));
+        assert_eq!(headers[1].copy(), Some(("new", "copy")));
+        assert!(headers[2].is_deleted_file());
+        assert_eq!(headers[2].old_mode(), Some(0o120000));
+        assert_eq!(headers[3].old_mode(), Some(0o100644));
+        assert_eq!(headers[3].new_mode(), Some(0o100644));
+        assert_eq!(set.to_string(), s);
+    }
+
+    #[test]
+    fn filenames_with_spaces_and_quotes() {
+        let s = "\
+diff --git a/my file b/my file
+old mode 100644
+new mode 100755
+diff --git a/x b/y b/x b/z
+similarity index 100%
+rename from x b/y
+rename to x b/z
+diff --git \"a/tab\\there\" b/plain
+similarity index 100%
+rename from \"tab\\there\"
+rename to plain
+diff --git a/plain \"b/new\\nline\"
+similarity index 100%
+rename from plain
+rename to \"new\\nline\"
+";
+        let set = PatchSet::from_str(s).unwrap();
+        let headers = set
+            .patches()
+            .iter()
+            .map(|patch| patch.git_header().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(headers[0].original(), "a/my file");
+        assert_eq!(headers[0].modified(), "b/my file");
+        assert_eq!(headers[1].original(), "a/x");
+        assert_eq!(headers[1].modified(), "b/y b/x b/z");
+        assert_eq!(headers[2].original(), "a/tab\there");
+        assert_eq!(headers[2].rename(), Some(("tab\there", "plain")));
+        assert_eq!(headers[3].modified(), "b/new\nline");
+        assert_eq!(set.to_string(), s);
+
+        let set = PatchSet::from_bytes(s.as_bytes()).unwrap();
+        assert_eq!(set.to_bytes(), s.as_bytes());
+    }
+
+    #[test]
+    fn invalid_extended_headers() {
+        Patch::from_str("diff --git a/x b/x\nold mode 1006z4\n").unwrap_err();
+        Patch::from_str("diff --git a/x b/x\nsimilarity index 101%\n").unwrap_err();
+        Patch::from_str("diff --git a/x b/x\nindex 1234567\n").unwrap_err();
+    }
+}
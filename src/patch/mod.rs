@@ -1,20 +1,59 @@
+mod binary;
+mod builder;
+mod compose;
+#[cfg(feature = "serde")]
+mod deserialize;
 mod format;
-
+mod git;
+mod parse;
+mod rebase;
+mod select;
+mod set;
+
+pub use binary::{BinaryHunk, BinaryHunkKind, BinaryPatch};
+pub use builder::{BuildPatchError, HunkBuilder, PatchBuilder};
+pub use compose::ComposePatchError;
 pub use format::PatchFormatter;
+pub use git::{ExtendedHeader, GitHeader};
+pub use parse::ParsePatchError;
+pub use rebase::RebasePatchError;
+pub use set::PatchSet;
 
 use std::{borrow::Cow, fmt, ops};
 
 const NO_NEWLINE_AT_EOF: &str = "\\ No newline at end of file";
 
 /// Representation of all the differences between two files
+///
+/// With the `serde` feature enabled, patches of both `str` and `[u8]` can be
+/// serialized and deserialized. Deserialized patches own their text.
 #[derive(PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: serde::Serialize",
+        deserialize = "T::Owned: serde::Deserialize<'de>, Hunk<'a, T>: serde::Deserialize<'de>"
+    ))
+)]
+#[cfg_attr(feature = "serde", serde(try_from = "deserialize::PatchFields<'a, T>"))]
 pub struct Patch<'a, T: ToOwned + ?Sized> {
+    git: Option<GitHeader<'a, T>>,
     // TODO GNU patch is able to parse patches without filename headers.
     // This should be changed to an `Option` type to reflect this instead of setting this to ""
     // when they're missing
     original: Option<Filename<'a, T>>,
+    // Any text following the filename on the '---' line, e.g. a timestamp
+    original_timestamp: Option<Cow<'a, T>>,
     modified: Option<Filename<'a, T>>,
+    // Any text following the filename on the '+++' line, e.g. a timestamp
+    modified_timestamp: Option<Cow<'a, T>>,
     hunks: Vec<Hunk<'a, T>>,
+    binary: Option<BinaryPatch>,
+    // Whether empty context lines are written with a leading space, as `git diff` does, rather
+    // than as an empty line
+    #[cfg_attr(feature = "serde", serde(default))]
+    blank_context_space: bool,
 }
 
 impl<'a, T: ToOwned + ?Sized> Patch<'a, T> {
@@ -30,9 +69,41 @@ impl<'a, T: ToOwned + ?Sized> Patch<'a, T> {
         let original = original.map(|o| Filename(o.into()));
         let modified = modified.map(|m| Filename(m.into()));
         Self {
+            git: None,
             original,
+            original_timestamp: None,
             modified,
+            modified_timestamp: None,
             hunks,
+            binary: None,
+            blank_context_space: false,
+        }
+    }
+
+    /// Returns the `diff --git` line and extended headers, if the patch has them
+    pub fn git_header(&self) -> Option<&GitHeader<'a, T>> {
+        self.git.as_ref()
+    }
+
+    pub(crate) fn new_binary<O, M>(original: O, modified: M, binary: BinaryPatch) -> Self
+    where
+        O: Into<Cow<'a, T>>,
+        M: Into<Cow<'a, T>>,
+    {
+        let git = GitHeader {
+            original: Filename(original.into()),
+            modified: Filename(modified.into()),
+            extended_headers: Vec::new(),
+        };
+        Self {
+            git: Some(git),
+            original: None,
+            original_timestamp: None,
+            modified: None,
+            modified_timestamp: None,
+            hunks: Vec::new(),
+            binary: Some(binary),
+            blank_context_space: false,
         }
     }
 
@@ -46,10 +117,146 @@ impl<'a, T: ToOwned + ?Sized> Patch<'a, T> {
         self.modified.as_ref().map(AsRef::as_ref)
     }
 
+    /// Return the text following the name of the old file in the patch header
+    ///
+    /// Tools like `diff -u` separate a timestamp from the filename with a tab,
+    /// e.g. `--- a/file.txt\t2002-02-21 23:30:39.942229878 -0800`.
+    pub fn original_timestamp(&self) -> Option<&T> {
+        self.original_timestamp.as_deref()
+    }
+
+    /// Return the text following the name of the new file in the patch header
+    pub fn modified_timestamp(&self) -> Option<&T> {
+        self.modified_timestamp.as_deref()
+    }
+
     /// Returns the hunks in the patch
-    pub fn hunks(&self) -> &[Hunk<'_, T>] {
+    pub fn hunks(&self) -> &[Hunk<'a, T>] {
         &self.hunks
     }
+
+    /// Returns the binary portion of the patch, if it modifies a binary file
+    pub fn binary(&self) -> Option<&BinaryPatch> {
+        self.binary.as_ref()
+    }
+
+    // Returns a patch of the same files which only holds `hunks`, e.g. the rejected hunks of a
+    // patch which couldn't be fully applied
+    pub(crate) fn with_hunks(&self, hunks: Vec<Hunk<'a, T>>) -> Self {
+        Self {
+            git: None,
+            original: self.original.clone(),
+            original_timestamp: self.original_timestamp.clone(),
+            modified: self.modified.clone(),
+            modified_timestamp: self.modified_timestamp.clone(),
+            hunks,
+            binary: None,
+            blank_context_space: self.blank_context_space,
+        }
+    }
+
+    /// Returns a patch which undoes this one, turning the new file back into
+    /// the old file
+    ///
+    /// The filenames, timestamps, git headers and hunk ranges of the old and
+    /// new files are swapped and deleted lines become inserted lines and vice
+    /// versa. Within each group of changed lines the deletions are kept ahead
+    /// of the insertions. A binary patch can only be reversed if it includes
+    /// the hunk turning the new file back into the old file, otherwise the
+    /// reversed patch holds a `BinaryPatch::Marker`.
+    ///
+    /// ```
+    /// use diffy::{apply, create_patch};
+    ///
+    /// let original = "The Way of Kings\nWords of Radiance\n";
+    /// let modified = "The Way of Kings\nOathbringer\n";
+    ///
+    /// let reversed = create_patch(original, modified).reverse();
+    /// assert_eq!(reversed.original(), Some("modified"));
+    /// assert_eq!(apply(modified, &reversed).unwrap(), original);
+    /// ```
+    pub fn reverse(&self) -> Patch<'a, T> {
+        Self {
+            git: self.git.as_ref().map(GitHeader::reverse),
+            original: self.modified.clone(),
+            original_timestamp: self.modified_timestamp.clone(),
+            modified: self.original.clone(),
+            modified_timestamp: self.original_timestamp.clone(),
+            hunks: self.hunks.iter().map(Hunk::reverse).collect(),
+            binary: self.binary.as_ref().map(BinaryPatch::reversed),
+            blank_context_space: self.blank_context_space,
+        }
+    }
+}
+
+impl<T: ToOwned + ?Sized + 'static> Patch<'_, T> {
+    /// Converts the patch into one which owns all of its text rather than
+    /// borrowing it from the input it was parsed or created from
+    ///
+    /// ```
+    /// use diffy::{create_patch, Patch};
+    ///
+    /// fn read_patch() -> Patch<'static, str> {
+    ///     let original = String::from("Kholinar\n");
+    ///     let modified = String::from("Urithiru\n");
+    ///     create_patch(&original, &modified).into_owned()
+    /// }
+    ///
+    /// let patch = read_patch();
+    /// assert_eq!(patch.hunks()[0].lines().len(), 2);
+    /// ```
+    pub fn into_owned(self) -> Patch<'static, T> {
+        Patch {
+            git: self.git.map(GitHeader::into_owned),
+            original: self.original.map(Filename::into_owned),
+            original_timestamp: self.original_timestamp.map(into_owned),
+            modified: self.modified.map(Filename::into_owned),
+            modified_timestamp: self.modified_timestamp.map(into_owned),
+            hunks: self.hunks.into_iter().map(Hunk::into_owned).collect(),
+            binary: self.binary,
+            blank_context_space: self.blank_context_space,
+        }
+    }
+}
+
+// Converts a `Cow` into one which owns its contents
+fn into_owned<T: ToOwned + ?Sized + 'static>(cow: Cow<'_, T>) -> Cow<'static, T> {
+    Cow::Owned(cow.into_owned())
+}
+
+impl<'a> Patch<'a, str> {
+    /// Parse a `Patch` from a string
+    ///
+    /// ```
+    /// use diffy::Patch;
+    ///
+    /// let s = "\
+    /// --- a/ideals
+    /// +++ b/ideals
+    /// @@ -1,4 +1,6 @@
+    ///  First:
+    ///      Life before death,
+    ///      strength before weakness,
+    ///      journey before destination.
+    /// +Second:
+    /// +    I will protect those who cannot protect themselves.
+    /// ";
+    ///
+    /// let patch = Patch::from_str(s).unwrap();
+    /// assert_eq!(patch.original(), Some("a/ideals"));
+    /// assert_eq!(patch.hunks().len(), 1);
+    /// ```
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &'a str) -> Result<Patch<'a, str>, ParsePatchError> {
+        parse::parse(s)
+    }
+}
+
+impl<'a> Patch<'a, [u8]> {
+    /// Parse a `Patch` from bytes
+    pub fn from_bytes(s: &'a [u8]) -> Result<Patch<'a, [u8]>, ParsePatchError> {
+        parse::parse_bytes(s)
+    }
 }
 
 impl<T: AsRef<[u8]> + ToOwned + ?Sized> Patch<'_, T> {
@@ -69,9 +276,14 @@ impl<T: AsRef<[u8]> + ToOwned + ?Sized> Patch<'_, T> {
 impl<T: ToOwned + ?Sized> Clone for Patch<'_, T> {
     fn clone(&self) -> Self {
         Self {
+            git: self.git.clone(),
             original: self.original.clone(),
+            original_timestamp: self.original_timestamp.clone(),
             modified: self.modified.clone(),
+            modified_timestamp: self.modified_timestamp.clone(),
             hunks: self.hunks.clone(),
+            binary: self.binary.clone(),
+            blank_context_space: self.blank_context_space,
         }
     }
 }
@@ -89,18 +301,53 @@ where
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Patch")
+            .field("git", &self.git)
             .field("original", &self.original)
+            .field("original_timestamp", &self.original_timestamp)
             .field("modified", &self.modified)
+            .field("modified_timestamp", &self.modified_timestamp)
             .field("hunks", &self.hunks)
+            .field("binary", &self.binary)
             .finish()
     }
 }
 
 #[derive(PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(
+        transparent,
+        bound(
+            serialize = "T: serde::Serialize",
+            deserialize = "T::Owned: serde::Deserialize<'de>"
+        )
+    )
+)]
 struct Filename<'a, T: ToOwned + ?Sized>(Cow<'a, T>);
 
+impl<T: ToOwned + ?Sized + 'static> Filename<'_, T> {
+    fn into_owned(self) -> Filename<'static, T> {
+        Filename(into_owned(self.0))
+    }
+}
+
 const ESCAPED_CHARS: &[char] = &['\n', '\t', '\0', '\r', '\"', '\\'];
-const ESCAPED_CHARS_BYTES: &[u8] = &[b'\n', b'\t', b'\0', b'\r', b'\"', b'\\'];
+const ESCAPED_CHARS_BYTES: &[u8] = b"\n\t\0\r\"\\";
+
+// Returns the C-style escape sequence used to represent one of the `ESCAPED_CHARS` inside of a
+// quoted filename
+fn escape_sequence(b: u8) -> &'static str {
+    match b {
+        b'\n' => "\\n",
+        b'\t' => "\\t",
+        b'\0' => "\\000",
+        b'\r' => "\\r",
+        b'\"' => "\\\"",
+        b'\\' => "\\\\",
+        _ => unreachable!("{:?} doesn't need to be escaped", b as char),
+    }
+}
 
 impl Filename<'_, str> {
     fn needs_to_be_escaped(&self) -> bool {
@@ -122,9 +369,10 @@ impl<T: ToOwned + AsRef<[u8]> + ?Sized> Filename<'_, T> {
             w.write_all(b"\"")?;
             for b in self.0.as_ref().as_ref() {
                 if ESCAPED_CHARS_BYTES.contains(b) {
-                    w.write_all(b"\\")?;
+                    w.write_all(escape_sequence(*b).as_bytes())?;
+                } else {
+                    w.write_all(&[*b])?;
                 }
-                w.write_all(&[*b])?;
             }
             w.write_all(b"\"")?;
         } else {
@@ -162,9 +410,10 @@ impl fmt::Display for Filename<'_, str> {
             f.write_char('\"')?;
             for c in self.0.chars() {
                 if ESCAPED_CHARS.contains(&c) {
-                    f.write_char('\\')?;
+                    f.write_str(escape_sequence(c as u8))?;
+                } else {
+                    f.write_char(c)?;
                 }
-                f.write_char(c)?;
             }
             f.write_char('\"')?;
         } else {
@@ -186,19 +435,26 @@ where
 }
 
 /// Represents a group of differing lines between two files
-#[derive(Debug, PartialEq, Eq)]
-pub struct Hunk<'a, T: ?Sized> {
+#[derive(PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(bound(serialize = "T: serde::Serialize")))]
+pub struct Hunk<'a, T: ToOwned + ?Sized> {
     old_range: HunkRange,
     new_range: HunkRange,
 
-    function_context: Option<&'a T>,
+    function_context: Option<Cow<'a, T>>,
 
     lines: Vec<Line<'a, T>>,
-    originals: Vec<&'a T>,
-    modifieds: Vec<&'a T>,
+    // The old and new text of each group of changed lines in the hunk. Patches created from a
+    // diff borrow these from the original texts while parsed patches need to stitch them together
+    // from the individual lines.
+    #[cfg_attr(feature = "serde", serde(skip_serializing))]
+    originals: Vec<Cow<'a, T>>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing))]
+    modifieds: Vec<Cow<'a, T>>,
 }
 
-fn hunk_lines_count<T: ?Sized>(lines: &[Line<'_, T>]) -> (usize, usize) {
+fn hunk_lines_count<T: ToOwned + ?Sized>(lines: &[Line<'_, T>]) -> (usize, usize) {
     lines.iter().fold((0, 0), |count, line| match line {
         Line::Context(_) => (count.0 + 1, count.1 + 1),
         Line::Delete(_) => (count.0 + 1, count.1),
@@ -206,14 +462,14 @@ fn hunk_lines_count<T: ?Sized>(lines: &[Line<'_, T>]) -> (usize, usize) {
     })
 }
 
-impl<'a, T: ?Sized> Hunk<'a, T> {
+impl<'a, T: ToOwned + ?Sized> Hunk<'a, T> {
     pub(crate) fn new(
         old_range: HunkRange,
         new_range: HunkRange,
-        function_context: Option<&'a T>,
+        function_context: Option<Cow<'a, T>>,
         lines: Vec<Line<'a, T>>,
-        originals: Vec<&'a T>,
-        modifieds: Vec<&'a T>,
+        originals: Vec<Cow<'a, T>>,
+        modifieds: Vec<Cow<'a, T>>,
     ) -> Self {
         let (old_count, new_count) = hunk_lines_count(&lines);
 
@@ -242,21 +498,62 @@ impl<'a, T: ?Sized> Hunk<'a, T> {
 
     /// Returns the function context (if any) for the hunk
     pub fn function_context(&self) -> Option<&T> {
-        self.function_context
+        self.function_context.as_deref()
     }
 
     /// Returns the lines in the hunk
     pub fn lines(&self) -> &[Line<'a, T>] {
         &self.lines
     }
+
+    // Returns a hunk which undoes this one, see `Patch::reverse`
+    fn reverse(&self) -> Self {
+        let mut lines = Vec::with_capacity(self.lines.len());
+        let mut inserted = Vec::new();
+        for line in &self.lines {
+            match line {
+                Line::Context(l) => {
+                    lines.append(&mut inserted);
+                    lines.push(Line::Context(l.clone()));
+                }
+                Line::Delete(l) => inserted.push(Line::Insert(l.clone())),
+                Line::Insert(l) => lines.push(Line::Delete(l.clone())),
+            }
+        }
+        lines.append(&mut inserted);
+
+        Self {
+            old_range: self.new_range,
+            new_range: self.old_range,
+            function_context: self.function_context.clone(),
+            lines,
+            originals: self.modifieds.clone(),
+            modifieds: self.originals.clone(),
+        }
+    }
+}
+
+impl<T: ToOwned + ?Sized + 'static> Hunk<'_, T> {
+    /// Converts the hunk into one which owns all of its text, see
+    /// [`Patch::into_owned`]
+    pub fn into_owned(self) -> Hunk<'static, T> {
+        Hunk {
+            old_range: self.old_range,
+            new_range: self.new_range,
+            function_context: self.function_context.map(into_owned),
+            lines: self.lines.into_iter().map(Line::into_owned).collect(),
+            originals: self.originals.into_iter().map(into_owned).collect(),
+            modifieds: self.modifieds.into_iter().map(into_owned).collect(),
+        }
+    }
 }
 
-impl<T: ?Sized> Clone for Hunk<'_, T> {
+impl<T: ToOwned + ?Sized> Clone for Hunk<'_, T> {
     fn clone(&self) -> Self {
         Self {
             old_range: self.old_range,
             new_range: self.new_range,
-            function_context: self.function_context,
+            function_context: self.function_context.clone(),
             lines: self.lines.clone(),
             originals: self.originals.clone(),
             modifieds: self.modifieds.clone(),
@@ -264,8 +561,26 @@ impl<T: ?Sized> Clone for Hunk<'_, T> {
     }
 }
 
+impl<T: ?Sized, O> fmt::Debug for Hunk<'_, T>
+where
+    T: ToOwned<Owned = O> + fmt::Debug,
+    O: std::borrow::Borrow<T> + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hunk")
+            .field("old_range", &self.old_range)
+            .field("new_range", &self.new_range)
+            .field("function_context", &self.function_context)
+            .field("lines", &self.lines)
+            .field("originals", &self.originals)
+            .field("modifieds", &self.modifieds)
+            .finish()
+    }
+}
+
 /// The range of lines in a file for a particular `Hunk`.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HunkRange {
     /// The starting line number of a hunk
     start: usize,
@@ -318,20 +633,66 @@ impl fmt::Display for HunkRange {
 ///
 /// A `Line` contains the terminating newline character `\n` unless it is the final
 /// line in the file and the file does not end with a newline character.
-#[derive(Debug, PartialEq, Eq)]
-pub enum Line<'a, T: ?Sized> {
+#[derive(PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: serde::Serialize",
+        deserialize = "T::Owned: serde::Deserialize<'de>"
+    ))
+)]
+pub enum Line<'a, T: ToOwned + ?Sized> {
     /// A line providing context in the diff which is present in both the old and new file
-    Context(&'a T),
+    Context(Cow<'a, T>),
     /// A line deleted from the old file
-    Delete(&'a T),
+    Delete(Cow<'a, T>),
     /// A line inserted to the new file
-    Insert(&'a T),
+    Insert(Cow<'a, T>),
+}
+
+impl<T: ToOwned + ?Sized> Line<'_, T> {
+    /// Returns the text of the line
+    pub fn inner(&self) -> &T {
+        match self {
+            Line::Context(line) | Line::Delete(line) | Line::Insert(line) => line,
+        }
+    }
 }
 
-impl<T: ?Sized> Copy for Line<'_, T> {}
+impl<T: ToOwned + ?Sized + 'static> Line<'_, T> {
+    /// Converts the line into one which owns its text, see
+    /// [`Patch::into_owned`]
+    pub fn into_owned(self) -> Line<'static, T> {
+        match self {
+            Line::Context(line) => Line::Context(into_owned(line)),
+            Line::Delete(line) => Line::Delete(into_owned(line)),
+            Line::Insert(line) => Line::Insert(into_owned(line)),
+        }
+    }
+}
 
-impl<T: ?Sized> Clone for Line<'_, T> {
+impl<T: ToOwned + ?Sized> Clone for Line<'_, T> {
     fn clone(&self) -> Self {
-        *self
+        match self {
+            Line::Context(line) => Line::Context(line.clone()),
+            Line::Delete(line) => Line::Delete(line.clone()),
+            Line::Insert(line) => Line::Insert(line.clone()),
+        }
+    }
+}
+
+impl<T: ?Sized, O> fmt::Debug for Line<'_, T>
+where
+    T: ToOwned<Owned = O> + fmt::Debug,
+    O: std::borrow::Borrow<T> + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, line) = match self {
+            Line::Context(line) => ("Context", line),
+            Line::Delete(line) => ("Delete", line),
+            Line::Insert(line) => ("Insert", line),
+        };
+        f.debug_tuple(name).field(line).finish()
     }
 }
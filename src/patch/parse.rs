@@ -0,0 +1,1036 @@
+//! Parse a Patch
+
+use super::{
+    binary, BinaryHunk, BinaryHunkKind, BinaryPatch, ExtendedHeader, Filename, GitHeader, Hunk,
+    HunkRange, Line, Patch, PatchSet, ESCAPED_CHARS_BYTES, NO_NEWLINE_AT_EOF,
+};
+use crate::utils::Text;
+use std::{borrow::Cow, fmt};
+
+type Result<T, E = ParsePatchError> = std::result::Result<T, E>;
+
+/// An error returned when parsing a `Patch` using [`Patch::from_str`] fails
+///
+/// [`Patch::from_str`]: struct.Patch.html#method.from_str
+#[derive(Debug)]
+pub struct ParsePatchError(Cow<'static, str>);
+
+impl ParsePatchError {
+    fn new<E: Into<Cow<'static, str>>>(e: E) -> Self {
+        Self(e.into())
+    }
+}
+
+impl fmt::Display for ParsePatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error parsing patch: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParsePatchError {}
+
+struct Parser<'a, T: Text + ?Sized> {
+    input: &'a T,
+    lines: Vec<&'a T>,
+    // Index of the next line to be consumed
+    pos: usize,
+    // Byte offset of the next line to be consumed
+    offset: usize,
+    // Whether an empty context line of the patch being parsed was written with a leading space,
+    // as `git diff` does
+    saw_blank_context_space: bool,
+}
+
+impl<'a, T: Text + ?Sized> Parser<'a, T> {
+    fn new(input: &'a T) -> Self {
+        Self {
+            input,
+            lines: input.lines().collect(),
+            pos: 0,
+            offset: 0,
+            saw_blank_context_space: false,
+        }
+    }
+
+    fn peek(&self) -> Option<&'a T> {
+        self.peek_nth(0)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<&'a T> {
+        self.lines.get(self.pos + n).copied()
+    }
+
+    fn next(&mut self) -> Result<&'a T> {
+        let line = self
+            .peek()
+            .ok_or_else(|| ParsePatchError::new("unexpected EOF"))?;
+        self.pos += 1;
+        self.offset += line.len();
+        Ok(line)
+    }
+
+    fn offset(&self) -> usize {
+        self.offset
+    }
+
+    // Returns the input starting at `start` up to, but not including, the next line to be consumed
+    fn consumed_since(&self, start: usize) -> Option<&'a T> {
+        let (consumed, _) = self.input.split_at(self.offset);
+        let (_, text) = consumed.split_at(start);
+        if text.is_empty() {
+            None
+        } else {
+            Some(text)
+        }
+    }
+}
+
+// Converts a filename, which is always parsed as bytes, into the text type of the patch
+type ConvertFilename<'a, T> = fn(Cow<'a, [u8]>) -> Cow<'a, T>;
+
+pub fn parse(input: &str) -> Result<Patch<'_, str>> {
+    let mut parser = Parser::new(input);
+    skip_header_preamble(&mut parser)?;
+    let patch = patch(&mut parser, convert_cow_to_str)?;
+    expect_eof(&mut parser)?;
+
+    Ok(patch)
+}
+
+pub fn parse_bytes(input: &[u8]) -> Result<Patch<'_, [u8]>> {
+    let mut parser = Parser::new(input);
+    skip_header_preamble(&mut parser)?;
+    let patch = patch(&mut parser, |filename| filename)?;
+    expect_eof(&mut parser)?;
+
+    Ok(patch)
+}
+
+pub fn parse_set(input: &str) -> Result<PatchSet<'_, str>> {
+    patch_set(&mut Parser::new(input), convert_cow_to_str)
+}
+
+pub fn parse_set_bytes(input: &[u8]) -> Result<PatchSet<'_, [u8]>> {
+    patch_set(&mut Parser::new(input), |filename| filename)
+}
+
+// This is only used when the type originated as a utf8 string
+fn convert_cow_to_str(cow: Cow<'_, [u8]>) -> Cow<'_, str> {
+    match cow {
+        Cow::Borrowed(b) => std::str::from_utf8(b).unwrap().into(),
+        Cow::Owned(o) => String::from_utf8(o).unwrap().into(),
+    }
+}
+
+fn expect_eof<T: Text + ?Sized>(parser: &mut Parser<'_, T>) -> Result<()> {
+    match parser.peek() {
+        Some(_) => Err(ParsePatchError::new("unexpected line after hunks")),
+        None => Ok(()),
+    }
+}
+
+fn patch_set<'a, T: Text + ?Sized>(
+    parser: &mut Parser<'a, T>,
+    convert: ConvertFilename<'a, T>,
+) -> Result<PatchSet<'a, T>> {
+    let mut patches = Vec::new();
+    let mut preambles = Vec::new();
+
+    loop {
+        // Everything up until the start of the next patch is preserved as-is
+        let start = parser.offset();
+        while parser.peek().is_some() && !at_patch_start(parser) {
+            parser.next()?;
+        }
+        let preamble = parser.consumed_since(start);
+
+        if parser.peek().is_none() {
+            return Ok(PatchSet {
+                patches,
+                preambles,
+                trailer: preamble.map(Cow::Borrowed),
+            });
+        }
+
+        preambles.push(preamble.map(Cow::Borrowed));
+        patches.push(patch(parser, convert)?);
+    }
+}
+
+// Checks if the next line starts a new patch, either with a "diff --git" line, a "---" line
+// immediately followed by a "+++" line or, for patches without filename headers, with a hunk
+fn at_patch_start<T: Text + ?Sized>(parser: &Parser<'_, T>) -> bool {
+    match parser.peek() {
+        Some(line) if line.starts_with("diff --git ") || line.starts_with("@@ ") => true,
+        Some(line) if line.starts_with("--- ") => parser
+            .peek_nth(1)
+            .map_or(false, |line| line.starts_with("+++ ")),
+        _ => false,
+    }
+}
+
+fn patch<'a, T: Text + ?Sized>(
+    parser: &mut Parser<'a, T>,
+    convert: ConvertFilename<'a, T>,
+) -> Result<Patch<'a, T>> {
+    let git = match parser.peek() {
+        Some(line) if line.starts_with("diff --git ") => Some(git_header(parser, convert)?),
+        _ => None,
+    };
+    let (original, modified) = patch_header(parser)?;
+    let binary = binary_patch(parser)?;
+    parser.saw_blank_context_space = false;
+    let hunks = if binary.is_none() {
+        hunks(parser)?
+    } else {
+        Vec::new()
+    };
+
+    let (original, original_timestamp) = match original {
+        Some((filename, timestamp)) => (
+            Some(Filename(convert(filename))),
+            timestamp.map(Cow::Borrowed),
+        ),
+        None => (None, None),
+    };
+    let (modified, modified_timestamp) = match modified {
+        Some((filename, timestamp)) => (
+            Some(Filename(convert(filename))),
+            timestamp.map(Cow::Borrowed),
+        ),
+        None => (None, None),
+    };
+
+    Ok(Patch {
+        git,
+        original,
+        original_timestamp,
+        modified,
+        modified_timestamp,
+        hunks,
+        binary,
+        blank_context_space: parser.saw_blank_context_space,
+    })
+}
+
+// A filename parsed from a "---" or "+++" line along with any text following it
+type FilenameHeader<'a, T> = (Cow<'a, [u8]>, Option<&'a T>);
+
+#[allow(clippy::type_complexity)]
+fn patch_header<'a, T: Text + ?Sized>(
+    parser: &mut Parser<'a, T>,
+) -> Result<(Option<FilenameHeader<'a, T>>, Option<FilenameHeader<'a, T>>)> {
+    let mut filename1 = None;
+    let mut filename2 = None;
+
+    while let Some(line) = parser.peek() {
+        if line.starts_with("--- ") {
+            if filename1.is_some() {
+                return Err(ParsePatchError::new("multiple '---' lines"));
+            }
+            filename1 = Some(parse_filename("--- ", parser.next()?)?);
+        } else if line.starts_with("+++ ") {
+            if filename2.is_some() {
+                return Err(ParsePatchError::new("multiple '+++' lines"));
+            }
+            filename2 = Some(parse_filename("+++ ", parser.next()?)?);
+        } else {
+            break;
+        }
+    }
+
+    Ok((filename1, filename2))
+}
+
+// Skip to the first "diff --git" line, filename header ("--- " or "+++ ") or hunk line,
+// skipping any preamble lines like a commit message
+fn skip_header_preamble<T: Text + ?Sized>(parser: &mut Parser<'_, T>) -> Result<()> {
+    while let Some(line) = parser.peek() {
+        if line.starts_with("diff --git ")
+            | line.starts_with("--- ")
+            | line.starts_with("+++ ")
+            | line.starts_with("@@ ")
+        {
+            break;
+        }
+        parser.next()?;
+    }
+
+    Ok(())
+}
+
+fn git_header<'a, T: Text + ?Sized>(
+    parser: &mut Parser<'a, T>,
+    convert: ConvertFilename<'a, T>,
+) -> Result<GitHeader<'a, T>> {
+    let line = parser
+        .next()?
+        .strip_prefix("diff --git ")
+        .and_then(|line| line.strip_suffix("\n"))
+        .ok_or_else(|| ParsePatchError::new("unable to parse 'diff --git' line"))?;
+    let (original, modified) = split_git_filenames(line)
+        .ok_or_else(|| ParsePatchError::new("unable to parse filenames in 'diff --git' line"))?;
+
+    let mut extended_headers = Vec::new();
+    while let Some(line) = parser.peek() {
+        match extended_header(line, convert)? {
+            Some(header) => extended_headers.push(header),
+            None => break,
+        }
+        parser.next()?;
+    }
+
+    Ok(GitHeader {
+        original: Filename(convert(decode_filename(original)?)),
+        modified: Filename(convert(decode_filename(modified)?)),
+        extended_headers,
+    })
+}
+
+// Splits the two filenames on a "diff --git" line. Since git doesn't quote filenames containing
+// spaces this isn't always possible to do unambiguously, so like git this prefers splitting the
+// line such that both names are the same once their "a/" and "b/" prefixes are removed.
+fn split_git_filenames<T: Text + ?Sized>(line: &T) -> Option<(&T, &T)> {
+    let bytes = line.as_bytes();
+
+    let ndx = if bytes.first() == Some(&b'"') {
+        let mut end = 1;
+        while end < bytes.len() && bytes[end] != b'"' {
+            end += if bytes[end] == b'\\' { 2 } else { 1 };
+        }
+        end + 1
+    } else if bytes.last() == Some(&b'"') {
+        line.find(" \"")?
+    } else {
+        let mid = bytes.len() / 2;
+        let strip_prefix = |name: &[u8]| {
+            name.iter()
+                .position(|&b| b == b'/')
+                .map(|ndx| name[ndx..].to_vec())
+        };
+        if bytes.len() % 2 == 1
+            && bytes[mid] == b' '
+            && strip_prefix(&bytes[..mid]).is_some()
+            && strip_prefix(&bytes[..mid]) == strip_prefix(&bytes[mid + 1..])
+        {
+            mid
+        } else {
+            line.find(" b/").or_else(|| line.find(" "))?
+        }
+    };
+
+    if bytes.get(ndx) != Some(&b' ') {
+        return None;
+    }
+    let (original, modified) = line.split_at(ndx);
+    Some((original, modified.strip_prefix(" ")?))
+}
+
+fn extended_header<'a, T: Text + ?Sized>(
+    line: &'a T,
+    convert: ConvertFilename<'a, T>,
+) -> Result<Option<ExtendedHeader<'a, T>>> {
+    let line = match line.strip_suffix("\n") {
+        Some(line) => line,
+        None => return Ok(None),
+    };
+
+    let header = if let Some(mode) = line.strip_prefix("old mode ") {
+        ExtendedHeader::OldMode(parse_mode(mode)?)
+    } else if let Some(mode) = line.strip_prefix("new mode ") {
+        ExtendedHeader::NewMode(parse_mode(mode)?)
+    } else if let Some(mode) = line.strip_prefix("deleted file mode ") {
+        ExtendedHeader::DeletedFileMode(parse_mode(mode)?)
+    } else if let Some(mode) = line.strip_prefix("new file mode ") {
+        ExtendedHeader::NewFileMode(parse_mode(mode)?)
+    } else if let Some(path) = line.strip_prefix("rename from ") {
+        ExtendedHeader::RenameFrom(convert(decode_filename(path)?))
+    } else if let Some(path) = line.strip_prefix("rename to ") {
+        ExtendedHeader::RenameTo(convert(decode_filename(path)?))
+    } else if let Some(path) = line.strip_prefix("copy from ") {
+        ExtendedHeader::CopyFrom(convert(decode_filename(path)?))
+    } else if let Some(path) = line.strip_prefix("copy to ") {
+        ExtendedHeader::CopyTo(convert(decode_filename(path)?))
+    } else if let Some(n) = line.strip_prefix("similarity index ") {
+        ExtendedHeader::SimilarityIndex(parse_percentage(n)?)
+    } else if let Some(n) = line.strip_prefix("dissimilarity index ") {
+        ExtendedHeader::DissimilarityIndex(parse_percentage(n)?)
+    } else if let Some(index) = line.strip_prefix("index ") {
+        let (hashes, mode) = match index.split_at_exclusive(" ") {
+            Some((hashes, mode)) => (hashes, Some(parse_mode(mode)?)),
+            None => (index, None),
+        };
+        let (original, modified) = hashes
+            .split_at_exclusive("..")
+            .ok_or_else(|| ParsePatchError::new("invalid 'index' line"))?;
+        ExtendedHeader::Index {
+            original: Cow::Borrowed(original),
+            modified: Cow::Borrowed(modified),
+            mode,
+        }
+    } else {
+        return Ok(None);
+    };
+
+    Ok(Some(header))
+}
+
+fn decode_filename<T: Text + ?Sized>(path: &T) -> Result<Cow<'_, [u8]>> {
+    if let Some(quoted) = is_quoted(path) {
+        escaped_filename(quoted)
+    } else {
+        unescaped_filename(path)
+    }
+}
+
+fn parse_mode<T: Text + ?Sized>(mode: &T) -> Result<u32> {
+    mode.as_str()
+        .and_then(|mode| u32::from_str_radix(mode, 8).ok())
+        .ok_or_else(|| ParsePatchError::new("invalid file mode"))
+}
+
+fn parse_percentage<T: Text + ?Sized>(n: &T) -> Result<u8> {
+    n.strip_suffix("%")
+        .and_then(|n| n.parse())
+        .filter(|&n| n <= 100)
+        .ok_or_else(|| ParsePatchError::new("invalid percentage"))
+}
+
+fn parse_filename<'a, T: Text + ?Sized>(
+    prefix: &str,
+    line: &'a T,
+) -> Result<FilenameHeader<'a, T>> {
+    let line = line
+        .strip_prefix(prefix)
+        .ok_or_else(|| ParsePatchError::new("unable to parse filename"))?;
+    let line = line
+        .strip_suffix("\n")
+        .ok_or_else(|| ParsePatchError::new("filename unterminated"))?;
+
+    // Anything following a tab (e.g. a timestamp) isn't part of the filename
+    let (filename, timestamp) = match line.split_at_exclusive("\t") {
+        Some((filename, timestamp)) => (filename, Some(timestamp)),
+        None => (line, None),
+    };
+
+    Ok((decode_filename(filename)?, timestamp))
+}
+
+fn is_quoted<T: Text + ?Sized>(s: &T) -> Option<&T> {
+    s.strip_prefix("\"").and_then(|s| s.strip_suffix("\""))
+}
+
+fn unescaped_filename<T: Text + ?Sized>(filename: &T) -> Result<Cow<'_, [u8]>> {
+    let bytes = filename.as_bytes();
+
+    if bytes.iter().any(|b| ESCAPED_CHARS_BYTES.contains(b)) {
+        return Err(ParsePatchError::new("invalid char in unquoted filename"));
+    }
+
+    Ok(bytes.into())
+}
+
+// Decodes the C-style escape sequences in a quoted filename. In addition to the sequences
+// produced by `Filename::write_into` this also handles the octal escapes git uses when quoting
+// non-ascii characters (e.g. "\303\244").
+fn escaped_filename<T: Text + ?Sized>(escaped: &T) -> Result<Cow<'_, [u8]>> {
+    let mut filename = Vec::new();
+
+    let mut chars = escaped.as_bytes().iter().copied().peekable();
+    while let Some(c) = chars.next() {
+        if c == b'\\' {
+            let ch = match chars
+                .next()
+                .ok_or_else(|| ParsePatchError::new("expected escaped character"))?
+            {
+                b'n' => b'\n',
+                b't' => b'\t',
+                b'r' => b'\r',
+                b'a' => b'\x07',
+                b'b' => b'\x08',
+                b'f' => b'\x0c',
+                b'v' => b'\x0b',
+                b'\"' => b'\"',
+                b'\\' => b'\\',
+                d @ b'0'..=b'7' => {
+                    let mut value = u32::from(d - b'0');
+                    for _ in 0..2 {
+                        match chars.peek() {
+                            Some(&d @ b'0'..=b'7') => {
+                                value = value * 8 + u32::from(d - b'0');
+                                chars.next();
+                            }
+                            _ => break,
+                        }
+                    }
+                    if value > 0xff {
+                        return Err(ParsePatchError::new("invalid octal escape"));
+                    }
+                    value as u8
+                }
+                _ => return Err(ParsePatchError::new("invalid escaped character")),
+            };
+            filename.push(ch);
+        } else if ESCAPED_CHARS_BYTES.contains(&c) {
+            return Err(ParsePatchError::new("invalid unescaped character"));
+        } else {
+            filename.push(c);
+        }
+    }
+
+    Ok(filename.into())
+}
+
+fn binary_patch<T: Text + ?Sized>(parser: &mut Parser<'_, T>) -> Result<Option<BinaryPatch>> {
+    match parser.peek() {
+        Some(line) if line.starts_with("Binary files ") && line.ends_with(" differ\n") => {
+            parser.next()?;
+            Ok(Some(BinaryPatch::Marker))
+        }
+        Some(line) if line.as_bytes() == b"GIT binary patch\n" => {
+            parser.next()?;
+            let forward = binary_hunk(parser)?;
+            let reverse = match parser.peek() {
+                Some(line) if line.starts_with("literal ") || line.starts_with("delta ") => {
+                    Some(binary_hunk(parser)?)
+                }
+                _ => None,
+            };
+            Ok(Some(BinaryPatch::Full { forward, reverse }))
+        }
+        _ => Ok(None),
+    }
+}
+
+// Parses a "literal" or "delta" line followed by lines of base85 encoded data and a blank line
+fn binary_hunk<T: Text + ?Sized>(parser: &mut Parser<'_, T>) -> Result<BinaryHunk> {
+    let line = parser.next()?;
+    let (kind, size) = if let Some(size) = line.strip_prefix("literal ") {
+        (BinaryHunkKind::Literal, size)
+    } else if let Some(size) = line.strip_prefix("delta ") {
+        (BinaryHunkKind::Delta, size)
+    } else {
+        return Err(ParsePatchError::new("expected 'literal' or 'delta' line"));
+    };
+    let size = size
+        .strip_suffix("\n")
+        .and_then(|size| size.parse())
+        .ok_or_else(|| ParsePatchError::new("invalid binary hunk size"))?;
+
+    let mut deflated = Vec::new();
+    while let Some(line) = parser.peek() {
+        parser.next()?;
+        if line.as_bytes() == b"\n" {
+            break;
+        }
+        line.strip_suffix("\n")
+            .and_then(|line| binary::decode_line(line.as_bytes(), &mut deflated))
+            .ok_or_else(|| ParsePatchError::new("invalid binary hunk data"))?;
+    }
+
+    BinaryHunk::from_deflated(kind, size, deflated)
+        .ok_or_else(|| ParsePatchError::new("binary hunk data does not match its size"))
+}
+
+pub(super) fn verify_hunks_in_order<T: ToOwned + ?Sized>(hunks: &[Hunk<'_, T>]) -> bool {
+    for hunk in hunks.windows(2) {
+        if hunk[0].old_range.end() > hunk[1].old_range.start()
+            || hunk[0].new_range.end() > hunk[1].new_range.start()
+        {
+            return false;
+        }
+    }
+    true
+}
+
+fn hunks<'a, T: Text + ?Sized>(parser: &mut Parser<'a, T>) -> Result<Vec<Hunk<'a, T>>> {
+    let mut hunks = Vec::new();
+    while let Some(line) = parser.peek() {
+        if !line.starts_with("@@ ") {
+            break;
+        }
+        hunks.push(hunk(parser)?);
+    }
+
+    // check and verify that the Hunks are in sorted order and don't overlap
+    if !verify_hunks_in_order(&hunks) {
+        return Err(ParsePatchError::new("Hunks not in order or overlap"));
+    }
+
+    Ok(hunks)
+}
+
+fn hunk<'a, T: Text + ?Sized>(parser: &mut Parser<'a, T>) -> Result<Hunk<'a, T>> {
+    let (range1, range2, function_context) = hunk_header(parser.next()?)?;
+    let lines = hunk_lines(parser, range1.len, range2.len)?;
+    let (originals, modifieds) = changed_text(&lines);
+
+    Ok(Hunk::new(
+        range1,
+        range2,
+        function_context.map(Cow::Borrowed),
+        lines,
+        originals,
+        modifieds,
+    ))
+}
+
+fn hunk_header<T: Text + ?Sized>(input: &T) -> Result<(HunkRange, HunkRange, Option<&T>)> {
+    let input = input
+        .strip_prefix("@@ ")
+        .ok_or_else(|| ParsePatchError::new("unable to parse hunk header"))?;
+
+    let (ranges, function_context) = input
+        .split_at_exclusive(" @@")
+        .ok_or_else(|| ParsePatchError::new("hunk header unterminated"))?;
+    let function_context = function_context
+        .strip_suffix("\n")
+        .unwrap_or(function_context);
+    let function_context = function_context
+        .strip_prefix(" ")
+        .filter(|context| !context.is_empty());
+
+    let (range1, range2) = ranges
+        .split_at_exclusive(" ")
+        .ok_or_else(|| ParsePatchError::new("unable to parse hunk header"))?;
+    let range1 = range(
+        range1
+            .strip_prefix("-")
+            .ok_or_else(|| ParsePatchError::new("unable to parse hunk header"))?,
+    )?;
+    let range2 = range(
+        range2
+            .strip_prefix("+")
+            .ok_or_else(|| ParsePatchError::new("unable to parse hunk header"))?,
+    )?;
+    Ok((range1, range2, function_context))
+}
+
+fn range<T: Text + ?Sized>(s: &T) -> Result<HunkRange> {
+    let (start, len) = if let Some((start, len)) = s.split_at_exclusive(",") {
+        (
+            start
+                .parse()
+                .ok_or_else(|| ParsePatchError::new("can't parse range"))?,
+            len.parse()
+                .ok_or_else(|| ParsePatchError::new("can't parse range"))?,
+        )
+    } else {
+        (
+            s.parse()
+                .ok_or_else(|| ParsePatchError::new("can't parse range"))?,
+            1,
+        )
+    };
+
+    Ok(HunkRange::new(start, len))
+}
+
+// The constructor of one of the kinds of `Line`
+type NewLine<'a, T> = fn(Cow<'a, T>) -> Line<'a, T>;
+
+// Reads the body of a hunk. The number of lines read is driven by the counts in the hunk header
+// (just like `git apply` and GNU patch do) so that any text following the hunk, like the start of
+// the next patch in a patch series, is left alone.
+fn hunk_lines<'a, T: Text + ?Sized>(
+    parser: &mut Parser<'a, T>,
+    len1: usize,
+    len2: usize,
+) -> Result<Vec<Line<'a, T>>> {
+    let mut lines: Vec<Line<'a, T>> = Vec::new();
+    // The text of the last line in `lines`
+    let mut last_text = None;
+    let (mut count1, mut count2) = (0, 0);
+    let mut no_newline_context = false;
+    let mut no_newline_delete = false;
+    let mut no_newline_insert = false;
+
+    loop {
+        let line = match parser.peek() {
+            Some(line) => line,
+            None if count1 == len1 && count2 == len2 => break,
+            None => return Err(ParsePatchError::new("Hunk header does not match hunk")),
+        };
+
+        let is_no_newline_marker = line.starts_with(NO_NEWLINE_AT_EOF);
+        let (new_line, text): (NewLine<'a, T>, &'a T) = if is_no_newline_marker {
+            let (last_line, last_text) = lines.pop().zip(last_text).ok_or_else(|| {
+                ParsePatchError::new("unexpected 'No newline at end of file' line")
+            })?;
+            let new_line: NewLine<'a, T> = match last_line {
+                Line::Context(_) => {
+                    no_newline_context = true;
+                    Line::Context
+                }
+                Line::Delete(_) => {
+                    no_newline_delete = true;
+                    Line::Delete
+                }
+                Line::Insert(_) => {
+                    no_newline_insert = true;
+                    Line::Insert
+                }
+            };
+            (new_line, strip_newline(last_text)?)
+        } else if count1 == len1 && count2 == len2 {
+            break;
+        } else if no_newline_context {
+            return Err(ParsePatchError::new("expected end of hunk"));
+        } else if let Some(line) = line.strip_prefix(" ") {
+            if line.len() == 1 && line.starts_with("\n") {
+                parser.saw_blank_context_space = true;
+            }
+            (Line::Context, line)
+        } else if line.starts_with("\n") {
+            (Line::Context, line)
+        } else if let Some(line) = line.strip_prefix("-") {
+            if no_newline_delete {
+                return Err(ParsePatchError::new("expected no more deleted lines"));
+            }
+            (Line::Delete, line)
+        } else if let Some(line) = line.strip_prefix("+") {
+            if no_newline_insert {
+                return Err(ParsePatchError::new("expected no more inserted lines"));
+            }
+            (Line::Insert, line)
+        } else {
+            return Err(ParsePatchError::new("unexpected line in hunk body"));
+        };
+        let line = new_line(Cow::Borrowed(text));
+
+        // A 'No newline at end of file' line replaces the line it applies to, which has already
+        // been counted
+        if !is_no_newline_marker {
+            match line {
+                Line::Context(_) => {
+                    count1 += 1;
+                    count2 += 1;
+                }
+                Line::Delete(_) => count1 += 1,
+                Line::Insert(_) => count2 += 1,
+            }
+        }
+        if count1 > len1 || count2 > len2 {
+            return Err(ParsePatchError::new("Hunk header does not match hunk"));
+        }
+
+        lines.push(line);
+        last_text = Some(text);
+        parser.next()?;
+    }
+
+    Ok(lines)
+}
+
+fn strip_newline<T: Text + ?Sized>(s: &T) -> Result<&T> {
+    if let Some(stripped) = s.strip_suffix("\n") {
+        Ok(stripped)
+    } else {
+        Err(ParsePatchError::new("missing newline"))
+    }
+}
+
+// Builds the old and new text of each group of consecutive changed lines in a hunk
+#[allow(clippy::type_complexity)]
+pub(super) fn changed_text<'a, T: Text + ?Sized>(
+    lines: &[Line<'a, T>],
+) -> (Vec<Cow<'a, T>>, Vec<Cow<'a, T>>) {
+    let mut originals = Vec::new();
+    let mut modifieds = Vec::new();
+
+    for group in lines.split(|line| matches!(line, Line::Context(_))) {
+        if group.is_empty() {
+            continue;
+        }
+
+        let (deleted, inserted): (Vec<_>, Vec<_>) = group
+            .iter()
+            .partition(|line| matches!(line, Line::Delete(_)));
+        // Only text made up of lines which are all borrowed can itself be borrowed
+        let text = |lines: Vec<&Line<'a, T>>| -> Cow<'a, T> {
+            let borrowed: Option<Vec<&'a T>> = lines
+                .iter()
+                .map(|line| match line {
+                    Line::Context(Cow::Borrowed(l))
+                    | Line::Delete(Cow::Borrowed(l))
+                    | Line::Insert(Cow::Borrowed(l)) => Some(*l),
+                    _ => None,
+                })
+                .collect();
+            match borrowed {
+                Some(pieces) => T::concat(&pieces),
+                None => {
+                    let pieces: Vec<&T> = lines.iter().map(|line| line.inner()).collect();
+                    Cow::Owned(T::concat(&pieces).into_owned())
+                }
+            }
+        };
+        originals.push(text(deleted));
+        modifieds.push(text(inserted));
+    }
+
+    (originals, modifieds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PatchFormatter;
+
+    #[test]
+    fn test_escaped_filenames() {
+        // No escaped characters
+        let s = "\
+--- original
++++ modified
+@@ -1,0 +1,1 @@
++Oathbringer
+";
+        parse(s).unwrap();
+        parse_bytes(s.as_ref()).unwrap();
+
+        // unescaped characters fail parsing
+        let s = "\
+--- ori\"ginal
++++ modified
+@@ -1,0 +1,1 @@
++Oathbringer
+";
+        parse(s).unwrap_err();
+        parse_bytes(s.as_ref()).unwrap_err();
+
+        // quoted with invalid escaped characters
+        let s = "\
+--- \"ori\\\"g\\zinal\"
++++ modified
+@@ -1,0 +1,1 @@
++Oathbringer
+";
+        parse(s).unwrap_err();
+        parse_bytes(s.as_ref()).unwrap_err();
+
+        // quoted with escaped characters
+        let s = r#"--- "ori\"g\tin\\a\nl"
++++ "mo\000\t\r\ndi\\fied"
+@@ -1,0 +1,1 @@
++Oathbringer
+"#;
+        let p = parse(s).unwrap();
+        assert_eq!(p.original(), Some("ori\"g\tin\\a\nl"));
+        assert_eq!(p.modified(), Some("mo\0\t\r\ndi\\fied"));
+        let b = parse_bytes(s.as_ref()).unwrap();
+        assert_eq!(b.original(), Some(&b"ori\"g\tin\\a\nl"[..]));
+        assert_eq!(b.modified(), Some(&b"mo\0\t\r\ndi\\fied"[..]));
+
+        // octal escapes produced by git for non-ascii filenames
+        let s = r#"--- "a/\303\244pfel"
++++ "b/\303\244pfel"
+"#;
+        let p = parse(s).unwrap();
+        assert_eq!(p.original(), Some("a/äpfel"));
+        assert_eq!(p.modified(), Some("b/äpfel"));
+    }
+
+    #[test]
+    fn test_filename_round_trip() {
+        let s = r#"--- "ori\"g\tin\\a\nl"
++++ "mo\000\t\r\ndi\\fied"
+@@ -1,0 +1 @@
++Oathbringer
+"#;
+        assert_eq!(parse(s).unwrap().to_string(), s);
+        assert_eq!(parse_bytes(s.as_ref()).unwrap().to_bytes(), s.as_bytes());
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let s = "\
+--- a/file.txt\t2002-02-21 23:30:39.942229878 -0800
++++ b/file.txt\t2002-02-21 23:30:50.442260588 -0800
+@@ -1 +1 @@
+-old
++new
+";
+        let p = parse(s).unwrap();
+        assert_eq!(p.original(), Some("a/file.txt"));
+        assert_eq!(p.modified(), Some("b/file.txt"));
+        assert_eq!(
+            p.original_timestamp(),
+            Some("2002-02-21 23:30:39.942229878 -0800")
+        );
+        assert_eq!(
+            p.modified_timestamp(),
+            Some("2002-02-21 23:30:50.442260588 -0800")
+        );
+        assert_eq!(p.to_string(), s);
+    }
+
+    #[test]
+    fn blank_context_lines() {
+        let git = "\
+--- a/file.txt
++++ b/file.txt
+@@ -1,3 +1,3 @@
+ Kaladin
+ 
+-Shallan
++Jasnah
+";
+        let patch = parse(git).unwrap();
+        assert_eq!(patch.to_string(), git);
+        assert_eq!(
+            parse_bytes(git.as_ref()).unwrap().to_bytes(),
+            git.as_bytes()
+        );
+
+        let gnu = git.replace(" \n-", "\n-");
+        let patch = parse(&gnu).unwrap();
+        assert_eq!(patch.to_string(), gnu);
+        assert_eq!(
+            PatchFormatter::new()
+                .suppress_blank_empty(false)
+                .fmt_patch(&patch)
+                .to_string(),
+            git
+        );
+    }
+
+    #[test]
+    fn test_missing_filename_header() {
+        // Missing Both '---' and '+++' lines
+        let patch = r#"
+@@ -1,11 +1,12 @@
+ diff --git a/src/detection/mod.rs b/src/detection/mod.rs
+ index 78f0f0f..2e4e1b6 100644
+ --- a/src/detection/mod.rs
+ +++ b/src/detection/mod.rs
+ @@ -1,11 +1,12 @@
+  mod alpha;
+  mod beta;
+ +mod gamma;
+  mod delta;
+  mod epsilon;
+  mod zeta;
++mod eta;
+"#;
+
+        parse(patch).unwrap();
+
+        // Missing '---'
+        let s = "\
++++ modified
+@@ -1,0 +1,1 @@
++Oathbringer
+";
+        parse(s).unwrap();
+
+        // Missing '+++'
+        let s = "\
+--- original
+@@ -1,0 +1,1 @@
++Oathbringer
+";
+        parse(s).unwrap();
+
+        // Headers out of order
+        let s = "\
++++ modified
+--- original
+@@ -1,0 +1,1 @@
++Oathbringer
+";
+        parse(s).unwrap();
+
+        // multiple headers should fail to parse
+        let s = "\
+--- original
+--- modified
+@@ -1,0 +1,1 @@
++Oathbringer
+";
+        parse(s).unwrap_err();
+    }
+
+    #[test]
+    fn adjacent_hunks_correctly_parse() {
+        let s = "\
+--- original
++++ modified
+@@ -110,7 +110,7 @@
+ --
+
+ I am afraid, however, that all I have known - that my story - will be forgotten.
+ I am afraid for the world that is to come.
+-Afraid that my plans will fail. Afraid of a doom worse than the Deepness.
++Afraid that Alendi will fail. Afraid of a doom brought by the Deepness.
+
+ Alendi was never the Hero of Ages.
+@@ -117,8 +117,8 @@
+ At best, I have amplified his virtues, creating a Hero where there was none.
+
+ At worst, I fear that all we believe may have been corrupted.
+
+-Alendi must not reach the Well of Ascension. He must not take the power for himself.
++Rashek must not reach the Well of Ascension. He must not take the power for himself.
+
+ --
+ Kwaan
+";
+        let patch = parse(s).unwrap();
+        assert_eq!(patch.hunks().len(), 2);
+        assert_eq!(patch.to_string(), s);
+    }
+
+    #[test]
+    fn hunk_header_counts_are_checked() {
+        let s = "\
+@@ -1,2 +1,2 @@
+-old
++new
+";
+        parse(s).unwrap_err();
+
+        let s = "\
+@@ -1 +1 @@
+-old
++new
+ extra
+";
+        parse(s).unwrap_err();
+    }
+
+    #[test]
+    fn function_context() {
+        let s = "\
+--- a/lib.rs
++++ b/lib.rs
+@@ -1,3 +1,3 @@ fn main() {
+     let a = 1;
+-    let b = 2;
++    let b = 3;
+     println!(\"{}\", a + b);
+";
+        let patch = parse(s).unwrap();
+        assert_eq!(patch.hunks()[0].function_context(), Some("fn main() {"));
+        assert_eq!(patch.to_string(), s);
+    }
+
+    #[test]
+    fn originals_and_modifieds() {
+        let s = "\
+@@ -1,4 +1,4 @@
+-one
+-two
++uno
+ three
+-four
++cuatro
++cinco
+\\ No newline at end of file
+";
+        let patch = parse(s).unwrap();
+        let hunk = &patch.hunks()[0];
+        assert_eq!(hunk.originals, ["one\ntwo\n", "four\n"]);
+        assert_eq!(hunk.modifieds, ["uno\n", "cuatro\ncinco"]);
+    }
+}
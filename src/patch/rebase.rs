@@ -0,0 +1,405 @@
+//! Moving a patch onto a file which has been changed by another patch
+
+use super::{
+    compose::{build_hunk, group_hunks, offset, tidy_changes, IndexedOp, Op, Side},
+    Line, Patch,
+};
+use crate::utils::Text;
+use std::fmt;
+
+/// An error returned when a patch can't be rebased with [`Patch::rebase`]
+///
+/// [`Patch::rebase`]: struct.Patch.html#method.rebase
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RebasePatchError {
+    /// One of the patches is a binary patch
+    Binary,
+    /// Hunks of the patch change the same lines as hunks of the upstream
+    /// patch
+    ///
+    /// Each pair holds the 0-based index of a hunk of the patch and of a hunk
+    /// of the upstream patch it overlaps, sorted by the former. The error
+    /// message numbers them from 1.
+    Conflict { overlaps: Vec<(usize, usize)> },
+    /// The hunks of one of the patches are out of order or overlap
+    InvalidHunks,
+}
+
+impl fmt::Display for RebasePatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RebasePatchError::Binary => {
+                write!(f, "error rebasing patch: binary patches can't be rebased")
+            }
+            RebasePatchError::Conflict { overlaps } => {
+                write!(f, "error rebasing patch: ")?;
+                for (i, (hunk, upstream)) in overlaps.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(
+                        f,
+                        "hunk #{} overlaps upstream hunk #{}",
+                        hunk + 1,
+                        upstream + 1
+                    )?;
+                }
+                Ok(())
+            }
+            RebasePatchError::InvalidHunks => {
+                write!(f, "error rebasing patch: hunks not in order or overlap")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RebasePatchError {}
+
+impl<'a> Patch<'a, str> {
+    /// Moves this patch onto the file produced by `upstream`, another patch
+    /// for the same old file
+    ///
+    /// The hunks of the rebased patch are shifted by the lines `upstream`
+    /// adds and removes before them, and the context lines which `upstream`
+    /// changes are updated, so the rebased patch applies to the new file of
+    /// `upstream`. It takes the filenames of this patch while git headers are
+    /// left out.
+    ///
+    /// Fails with a [`RebasePatchError`] listing the overlapping hunks if the
+    /// two patches change the same lines, insert lines at the same place or
+    /// insert lines next to a line the other patch changes, or if either
+    /// patch is a binary patch.
+    ///
+    /// ```
+    /// use diffy::{apply, create_patch, RebasePatchError};
+    ///
+    /// let base = "Kaladin\nShallan\nAdolin\nDalinar\n";
+    /// let ours = "Kaladin\nShallan\nAdolin\nBlackthorn\n";
+    /// let upstream = "Syl\nKaladin\nVeil\nAdolin\nDalinar\n";
+    ///
+    /// let patch = create_patch(base, ours);
+    /// let rebased = patch.rebase(&create_patch(base, upstream)).unwrap();
+    /// assert_eq!(
+    ///     apply(upstream, &rebased).unwrap(),
+    ///     "Syl\nKaladin\nVeil\nAdolin\nBlackthorn\n"
+    /// );
+    ///
+    /// let conflicting = create_patch(base, "Kaladin\nShallan\nAdolin\nBondsmith\n");
+    /// assert_eq!(
+    ///     patch.rebase(&conflicting),
+    ///     Err(RebasePatchError::Conflict { overlaps: vec![(0, 0)] })
+    /// );
+    /// ```
+    ///
+    /// [`RebasePatchError`]: enum.RebasePatchError.html
+    pub fn rebase(&self, upstream: &Patch<'a, str>) -> Result<Patch<'a, str>, RebasePatchError> {
+        rebase(self, upstream)
+    }
+}
+
+impl<'a> Patch<'a, [u8]> {
+    /// Moves this patch onto the file produced by `upstream`, another patch
+    /// for the same old file
+    ///
+    /// This is the equivalent of [`Patch::rebase`] for potentially non-utf8
+    /// patches.
+    ///
+    /// [`Patch::rebase`]: struct.Patch.html#method.rebase
+    pub fn rebase(&self, upstream: &Patch<'a, [u8]>) -> Result<Patch<'a, [u8]>, RebasePatchError> {
+        rebase(self, upstream)
+    }
+}
+
+fn rebase<'a, T: Text + ?Sized>(
+    patch: &Patch<'a, T>,
+    upstream: &Patch<'a, T>,
+) -> Result<Patch<'a, T>, RebasePatchError> {
+    if patch.binary.is_some() || upstream.binary.is_some() {
+        return Err(RebasePatchError::Binary);
+    }
+
+    // Both patches are walked in terms of the lines of the base file they share
+    let mut patch_offset = 0;
+    let mut upstream_offset = 0;
+    let mut hunks = Vec::new();
+    let mut overlaps = Vec::new();
+
+    for group in group_hunks(&patch.hunks, Side::Old, &upstream.hunks, Side::Old) {
+        let patch_ops = Side::Old.ops(&group.first, group.start, group.end);
+        let upstream_ops = Side::Old.ops(&group.second, group.start, group.end);
+        let (patch_ops, upstream_ops) = patch_ops
+            .zip(upstream_ops)
+            .ok_or(RebasePatchError::InvalidHunks)?;
+        let lines = rebase_ops(patch_ops, upstream_ops, &mut overlaps)?;
+
+        let function_context = group
+            .first
+            .first()
+            .and_then(|(_, hunk)| hunk.function_context.clone());
+
+        let old_position = (group.start as isize + upstream_offset) as usize;
+        let new_position = (group.start as isize + upstream_offset + patch_offset) as usize;
+        patch_offset += offset(&group.first);
+        upstream_offset += offset(&group.second);
+
+        // Groups without any of the patch's hunks only hold upstream's lines as context
+        if lines.iter().all(|line| matches!(line, Line::Context(_))) {
+            continue;
+        }
+
+        hunks.push(build_hunk(
+            old_position,
+            new_position,
+            function_context,
+            lines,
+        ));
+    }
+
+    if !overlaps.is_empty() {
+        overlaps.sort_unstable();
+        overlaps.dedup();
+        return Err(RebasePatchError::Conflict { overlaps });
+    }
+
+    Ok(patch.with_hunks(hunks))
+}
+
+// Walks the lines of both patches side by side, producing the lines of the rebased hunk and
+// recording the hunks which change the same lines
+fn rebase_ops<'a, T: Text + ?Sized>(
+    patch: Vec<IndexedOp<'_, 'a, T>>,
+    upstream: Vec<IndexedOp<'_, 'a, T>>,
+    overlaps: &mut Vec<(usize, usize)>,
+) -> Result<Vec<Line<'a, T>>, RebasePatchError> {
+    let mut patch = patch.into_iter().peekable();
+    let mut upstream = upstream.into_iter().peekable();
+    let mut lines = Vec::new();
+    // The hunks which changed the previous line of the base file
+    let mut patch_changed = None;
+    let mut upstream_changed = None;
+
+    let hunk = |at: Option<(usize, usize)>| at.map(|(hunk, _)| hunk);
+    let changed = |op: Option<&IndexedOp<'_, 'a, T>>| match op {
+        Some((Op::Changed(_), at)) => hunk(*at),
+        _ => None,
+    };
+
+    loop {
+        // Lines inserted at the same place, or next to a line the other patch changes, could go
+        // either way
+        let mut patch_inserted = None;
+        while let Some((Op::Outside(l), at)) = patch.next_if(|(op, _)| op.is_outside()) {
+            lines.push(Line::Insert(l.clone()));
+            patch_inserted = hunk(at);
+        }
+        let mut upstream_inserted = None;
+        while let Some((Op::Outside(l), at)) = upstream.next_if(|(op, _)| op.is_outside()) {
+            lines.push(Line::Context(l.clone()));
+            upstream_inserted = hunk(at);
+        }
+        let upstream_next = changed(upstream.peek());
+        let patch_next = changed(patch.peek());
+        if let Some(p) = patch_inserted {
+            if let Some(u) = upstream_inserted.or(upstream_changed).or(upstream_next) {
+                overlaps.push((p, u));
+            }
+        }
+        if let Some(u) = upstream_inserted {
+            if let Some(p) = patch_changed.or(patch_next) {
+                overlaps.push((p, u));
+            }
+        }
+
+        // Both patches are now at the same line of the base file
+        let ((p, p_at), (u, u_at)) = match (patch.next(), upstream.next()) {
+            (Some(p), Some(u)) => (p, u),
+            (None, None) => break,
+            _ => return Err(RebasePatchError::InvalidHunks),
+        };
+        let l = match (p.text(), u.text()) {
+            (Some(a), Some(b)) if a != b => {
+                // The patches don't agree on what the base file holds
+                overlaps.extend(hunk(p_at).zip(hunk(u_at)));
+                a.clone()
+            }
+            (Some(l), _) | (None, Some(l)) => l.clone(),
+            (None, None) => return Err(RebasePatchError::InvalidHunks),
+        };
+        patch_changed = None;
+        upstream_changed = None;
+        match (p, u) {
+            (Op::Kept(_), Op::Kept(_)) => lines.push(Line::Context(l)),
+            // A line upstream deleted, which is no longer there to be context
+            (Op::Kept(_), _) => upstream_changed = hunk(u_at),
+            (_, Op::Kept(_)) => {
+                lines.push(Line::Delete(l));
+                patch_changed = hunk(p_at);
+            }
+            // A line deleted by both patches
+            _ => overlaps.extend(hunk(p_at).zip(hunk(u_at))),
+        }
+    }
+
+    Ok(tidy_changes(lines))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RebasePatchError;
+    use crate::{apply, create_binary_patch, create_patch, create_patch_bytes, Patch};
+
+    const BASE: &str = "\
+Kaladin
+Shallan
+Adolin
+Dalinar
+Navani
+Jasnah
+Renarin
+Lift
+Szeth
+Hoid
+";
+
+    #[track_caller]
+    fn assert_rebases(ours: &str, upstream: &str, expected: &str) {
+        let patch = create_patch(BASE, ours);
+        let rebased = patch.rebase(&create_patch(BASE, upstream)).unwrap();
+        assert_eq!(apply(upstream, &rebased).unwrap(), expected);
+
+        let patch = create_patch_bytes(BASE.as_bytes(), ours.as_bytes());
+        let rebased = patch
+            .rebase(&create_patch_bytes(BASE.as_bytes(), upstream.as_bytes()))
+            .unwrap();
+        assert_eq!(
+            crate::apply_bytes(upstream.as_bytes(), &rebased).unwrap(),
+            expected.as_bytes()
+        );
+    }
+
+    #[test]
+    fn rebase() {
+        let ours = "Kaladin\nShallan\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nNale\nHoid\n";
+
+        // Upstream changes far away from ours
+        assert_rebases(
+            ours,
+            "Syl\nKaladin\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nSzeth\nHoid\n",
+            "Syl\nKaladin\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nNale\nHoid\n",
+        );
+
+        // Upstream changes the context of our hunk
+        assert_rebases(
+            ours,
+            "Kaladin\nShallan\nAdolin\nDalinar\nNavani\nIvory\nRenarin\nLift\nSzeth\nHoid\nNightblood\n",
+            "Kaladin\nShallan\nAdolin\nDalinar\nNavani\nIvory\nRenarin\nLift\nNale\nHoid\nNightblood\n",
+        );
+        assert_rebases(
+            ours,
+            "Kaladin\nShallan\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nSzeth\nHoid\n",
+            "Kaladin\nShallan\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nNale\nHoid\n",
+        );
+
+        // Nothing to rebase across
+        assert_rebases(ours, BASE, ours);
+        assert_rebases(BASE, ours, ours);
+    }
+
+    #[test]
+    fn ranges() {
+        let patch = create_patch(BASE, "Kaladin\nShallan\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nSzeth\nHoid\nNightblood\n");
+        let upstream = create_patch(BASE, "Syl\nPattern\nKaladin\nShallan\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nSzeth\nHoid\n");
+        let rebased = patch.rebase(&upstream).unwrap();
+        let expected = "\
+--- original
++++ modified
+@@ -10,3 +10,4 @@
+ Lift
+ Szeth
+ Hoid
++Nightblood
+";
+        assert_eq!(rebased.to_string(), expected);
+    }
+
+    #[test]
+    fn conflict() {
+        let patch = "\
+--- a/radiants
++++ b/radiants
+@@ -1,3 +1,3 @@
+ Kaladin
+-Shallan
++Veil
+ Adolin
+@@ -8,3 +8,3 @@
+ Lift
+-Szeth
++Nale
+ Hoid
+";
+        let patch = Patch::from_str(patch).unwrap();
+
+        // Both patches change the same line
+        let upstream = create_patch(
+            BASE,
+            "Kaladin\nRadiant\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nSzeth\nHoid\n",
+        );
+        assert_eq!(
+            patch.rebase(&upstream),
+            Err(RebasePatchError::Conflict {
+                overlaps: vec![(0, 0)]
+            })
+        );
+
+        // Upstream inserts a line right after a line we change
+        let upstream = create_patch(BASE, "Kaladin\nShallan\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nSzeth\nIshar\nHoid\n");
+        let err = patch.rebase(&upstream).unwrap_err();
+        assert_eq!(
+            err,
+            RebasePatchError::Conflict {
+                overlaps: vec![(1, 0)]
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "error rebasing patch: hunk #2 overlaps upstream hunk #1"
+        );
+
+        // Both patches insert lines at the same place
+        let ours = create_patch(
+            BASE,
+            "Syl\nKaladin\nShallan\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nSzeth\nHoid\n",
+        );
+        let upstream = create_patch(BASE, "Pattern\nKaladin\nShallan\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nSzeth\nHoid\n");
+        assert_eq!(
+            ours.rebase(&upstream),
+            Err(RebasePatchError::Conflict {
+                overlaps: vec![(0, 0)]
+            })
+        );
+
+        let binary = create_binary_patch(b"\0Kaladin", b"\0Shallan");
+        assert_eq!(binary.rebase(&binary), Err(RebasePatchError::Binary));
+
+        // Hunks which the parser accepts but which overlap in the old file
+        let overlapping = "\
+--- a
++++ b
+@@ -2,0 +3 @@
++Syl
+@@ -2 +4 @@
+-Shallan
++Veil
+";
+        let overlapping = Patch::from_str(overlapping).unwrap();
+        assert_eq!(
+            overlapping.rebase(&create_patch(BASE, BASE)),
+            Err(RebasePatchError::InvalidHunks)
+        );
+        assert_eq!(
+            create_patch(BASE, BASE).rebase(&overlapping),
+            Err(RebasePatchError::InvalidHunks)
+        );
+    }
+}
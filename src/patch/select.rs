@@ -0,0 +1,364 @@
+//! Building a patch out of a subset of the changes of another patch, like
+//! `git add --patch`
+
+use super::{hunk_lines_count, parse, Hunk, HunkRange, Line, Patch};
+use crate::utils::Text;
+use std::borrow::Cow;
+
+impl<'a, T: ToOwned + ?Sized> Patch<'a, T> {
+    /// Returns a patch with only the hunks for which `keep` returns `true`
+    ///
+    /// `keep` is called with the index of each hunk. The ranges of the new
+    /// file are adjusted for the hunks which were left out, so the resulting
+    /// patch applies to the same old file as this one.
+    ///
+    /// ```
+    /// use diffy::{apply, Patch};
+    ///
+    /// let s = "\
+    /// --- a/orders
+    /// +++ b/orders
+    /// @@ -1,2 +1,3 @@
+    ///  Windrunners
+    /// +Skybreakers
+    ///  Dustbringers
+    /// @@ -5,2 +6,2 @@
+    ///  Stonewards
+    /// -Bondsmiths
+    /// +Truthwatchers
+    /// ";
+    /// let patch = Patch::from_str(s).unwrap();
+    /// let base_image = "Windrunners\nDustbringers\nEdgedancers\nLightweavers\nStonewards\nBondsmiths\n";
+    ///
+    /// let second = patch.select_hunks(|i, _| i == 1);
+    /// assert_eq!(second.hunks()[0].new_range().start(), 5);
+    /// assert_eq!(
+    ///     apply(base_image, &second).unwrap(),
+    ///     "Windrunners\nDustbringers\nEdgedancers\nLightweavers\nStonewards\nTruthwatchers\n"
+    /// );
+    /// ```
+    pub fn select_hunks<F>(&self, mut keep: F) -> Patch<'a, T>
+    where
+        F: FnMut(usize, &Hunk<'a, T>) -> bool,
+    {
+        let hunks = self
+            .hunks
+            .iter()
+            .enumerate()
+            .filter(|(i, hunk)| keep(*i, hunk))
+            .map(|(_, hunk)| hunk.clone())
+            .collect();
+        self.with_selected_hunks(hunks)
+    }
+
+    // Returns a patch of the same files holding `hunks`, which are a subset of this patch's hunks
+    // with their old ranges untouched, fixing up the start of their new ranges
+    fn with_selected_hunks(&self, mut hunks: Vec<Hunk<'a, T>>) -> Patch<'a, T> {
+        let mut offset = 0;
+        for hunk in &mut hunks {
+            // An empty range starts at the line before the hunk rather than its first line
+            let old = hunk.old_range;
+            let position = if old.is_empty() {
+                old.start
+            } else {
+                old.start - 1
+            };
+            let position = (position as isize + offset) as usize;
+            let len = hunk.new_range.len;
+            hunk.new_range = HunkRange::new(if len == 0 { position } else { position + 1 }, len);
+            offset += len as isize - old.len as isize;
+        }
+
+        Patch {
+            git: self.git.clone(),
+            original: self.original.clone(),
+            original_timestamp: self.original_timestamp.clone(),
+            modified: self.modified.clone(),
+            modified_timestamp: self.modified_timestamp.clone(),
+            hunks,
+            binary: self.binary.clone(),
+            blank_context_space: self.blank_context_space,
+        }
+    }
+}
+
+impl<'a> Patch<'a, str> {
+    /// Returns a patch with only the changed lines for which `keep` returns
+    /// `true`
+    ///
+    /// `keep` is called with the index of the hunk, the index of the line
+    /// within the hunk and the line itself for each deleted and inserted
+    /// line. A deleted line which isn't kept becomes a context line, while an
+    /// inserted line which isn't kept is dropped, the same way lines are
+    /// rejected when editing a hunk with `git add --patch`. Hunks left
+    /// without any changes are dropped and the ranges of the remaining hunks
+    /// are recomputed, so the resulting patch applies to the same old file as
+    /// this one. If the last line of the old file has no newline at the end
+    /// and lines are inserted after it, rejecting its deletion changes it to
+    /// the same line with a newline instead, so the insertions aren't joined
+    /// onto it.
+    ///
+    /// ```
+    /// use diffy::{apply, Line, Patch};
+    ///
+    /// let s = "\
+    /// --- a/ideals
+    /// +++ b/ideals
+    /// @@ -1,3 +1,3 @@
+    ///  Life before death.
+    /// -Strength before weakness.
+    /// +Strength before weakness!
+    /// -Journey before destination.
+    /// +Journey before destination!
+    /// ";
+    /// let patch = Patch::from_str(s).unwrap();
+    ///
+    /// // Only take the changes to the second line
+    /// let selected = patch.select_lines(|_, _, line| match line {
+    ///     Line::Delete(l) | Line::Insert(l) => l.starts_with("Strength"),
+    ///     Line::Context(_) => true,
+    /// });
+    ///
+    /// let expected = "\
+    /// --- a/ideals
+    /// +++ b/ideals
+    /// @@ -1,3 +1,3 @@
+    ///  Life before death.
+    /// -Strength before weakness.
+    /// +Strength before weakness!
+    ///  Journey before destination.
+    /// ";
+    /// assert_eq!(selected.to_string(), expected);
+    ///
+    /// let base_image = "Life before death.\nStrength before weakness.\nJourney before destination.\n";
+    /// assert_eq!(
+    ///     apply(base_image, &selected).unwrap(),
+    ///     "Life before death.\nStrength before weakness!\nJourney before destination.\n"
+    /// );
+    /// ```
+    pub fn select_lines<F>(&self, keep: F) -> Patch<'a, str>
+    where
+        F: FnMut(usize, usize, &Line<'a, str>) -> bool,
+    {
+        self.with_selected_hunks(select_lines(&self.hunks, keep))
+    }
+}
+
+impl<'a> Patch<'a, [u8]> {
+    /// Returns a patch with only the changed lines for which `keep` returns
+    /// `true`
+    ///
+    /// This is the equivalent of [`Patch::select_lines`] for potentially
+    /// non-utf8 patches.
+    ///
+    /// [`Patch::select_lines`]: struct.Patch.html#method.select_lines
+    pub fn select_lines<F>(&self, keep: F) -> Patch<'a, [u8]>
+    where
+        F: FnMut(usize, usize, &Line<'a, [u8]>) -> bool,
+    {
+        self.with_selected_hunks(select_lines(&self.hunks, keep))
+    }
+}
+
+// Builds the hunks holding the selected lines, which still need their new ranges fixed up
+fn select_lines<'a, T: Text + ?Sized, F>(hunks: &[Hunk<'a, T>], mut keep: F) -> Vec<Hunk<'a, T>>
+where
+    F: FnMut(usize, usize, &Line<'a, T>) -> bool,
+{
+    let mut selected = Vec::new();
+    for (i, hunk) in hunks.iter().enumerate() {
+        let mut lines = Vec::with_capacity(hunk.lines.len());
+        let mut changed = false;
+        for (j, line) in hunk.lines.iter().enumerate() {
+            match line {
+                Line::Context(_) => lines.push(line.clone()),
+                Line::Delete(l) => {
+                    if keep(i, j, line) {
+                        changed = true;
+                        lines.push(line.clone());
+                    } else {
+                        lines.push(Line::Context(l.clone()));
+                    }
+                }
+                Line::Insert(_) => {
+                    if keep(i, j, line) {
+                        changed = true;
+                        lines.push(line.clone());
+                    }
+                }
+            }
+        }
+        if !changed {
+            continue;
+        }
+        keep_last_line(&mut lines);
+
+        let (_, new_len) = hunk_lines_count(&lines);
+        let (originals, modifieds) = parse::changed_text(&lines);
+        selected.push(Hunk::new(
+            hunk.old_range,
+            HunkRange::new(hunk.new_range.start, new_len),
+            hunk.function_context.clone(),
+            lines,
+            originals,
+            modifieds,
+        ));
+    }
+    selected
+}
+
+// A rejected deletion of the last line of a file without a newline at the end can't stay as
+// context when inserted lines follow it, since they would be joined onto it. The line is replaced by
+// the same line ending with a newline instead.
+fn keep_last_line<T: Text + ?Sized>(lines: &mut Vec<Line<'_, T>>) {
+    let last = lines
+        .iter()
+        .position(|line| matches!(line, Line::Context(l) if !l.ends_with("\n")));
+    if let Some(i) = last {
+        if lines[i + 1..]
+            .iter()
+            .any(|line| matches!(line, Line::Insert(_)))
+        {
+            if let Line::Context(line) = lines[i].clone() {
+                let with_newline = Cow::Owned(line.with_newline());
+                lines[i] = Line::Delete(line);
+                lines.insert(i + 1, Line::Insert(with_newline));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{apply, apply_bytes, create_patch, create_patch_bytes, Line, Patch};
+
+    const ORIGINAL: &str = "\
+Kaladin
+Shallan
+Adolin
+Dalinar
+Navani
+Jasnah
+Renarin
+Lift
+Szeth
+Hoid
+";
+
+    const MODIFIED: &str = "\
+Kaladin
+Veil
+Radiant
+Adolin
+Dalinar
+Navani
+Jasnah
+Renarin
+Lift
+Hoid
+Nightblood
+";
+
+    #[test]
+    fn select_hunks() {
+        let patch = create_patch(ORIGINAL, MODIFIED);
+        assert_eq!(patch.hunks().len(), 2);
+
+        let none = patch.select_hunks(|_, _| false);
+        assert!(none.hunks().is_empty());
+        assert_eq!(apply(ORIGINAL, &none).unwrap(), ORIGINAL);
+
+        let all = patch.select_hunks(|_, _| true);
+        assert_eq!(all, patch);
+
+        let second = patch.select_hunks(|i, _| i == 1);
+        assert_eq!(second.hunks()[0].old_range().start(), 6);
+        assert_eq!(second.hunks()[0].new_range().start(), 6);
+        assert_eq!(
+            apply(ORIGINAL, &second).unwrap(),
+            "Kaladin\nShallan\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nHoid\nNightblood\n"
+        );
+    }
+
+    #[test]
+    fn select_lines() {
+        let patch = create_patch(ORIGINAL, MODIFIED);
+
+        let all = patch.select_lines(|_, _, _| true);
+        assert_eq!(all.to_string(), patch.to_string());
+
+        // Rejecting every line of the first hunk drops it
+        let second = patch.select_lines(|i, _, _| i == 1);
+        assert_eq!(second, patch.select_hunks(|i, _| i == 1));
+
+        // Only take the insertions
+        let inserted = patch.select_lines(|_, _, line| matches!(line, Line::Insert(_)));
+        let expected = "\
+--- original
++++ modified
+@@ -1,5 +1,7 @@
+ Kaladin
+ Shallan
++Veil
++Radiant
+ Adolin
+ Dalinar
+ Navani
+@@ -6,5 +8,6 @@
+ Jasnah
+ Renarin
+ Lift
+ Szeth
+ Hoid
++Nightblood
+";
+        assert_eq!(inserted.to_string(), expected);
+        assert_eq!(
+            apply(ORIGINAL, &inserted).unwrap(),
+            "Kaladin\nShallan\nVeil\nRadiant\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nSzeth\nHoid\nNightblood\n"
+        );
+
+        // Only take the deletions
+        let deleted = patch.select_lines(|_, _, line| matches!(line, Line::Delete(_)));
+        assert_eq!(deleted.hunks()[1].new_range().start(), 5);
+        assert_eq!(
+            apply(ORIGINAL, &deleted).unwrap(),
+            "Kaladin\nAdolin\nDalinar\nNavani\nJasnah\nRenarin\nLift\nHoid\n"
+        );
+
+        let bytes = patch.to_bytes();
+        let patch = Patch::from_bytes(&bytes).unwrap();
+        let deleted = patch
+            .select_lines(|_, _, line| matches!(line, Line::Delete(_)))
+            .to_bytes();
+        assert_eq!(Patch::from_bytes(&deleted).unwrap().hunks().len(), 2);
+    }
+
+    #[test]
+    fn select_lines_without_newline() {
+        let patch = create_patch("a\nb", "a\nB\n");
+        let inserted = patch.select_lines(|_, _, line| matches!(line, Line::Insert(_)));
+        let expected = "\
+--- original
++++ modified
+@@ -1,2 +1,3 @@
+ a
+-b
+\\ No newline at end of file
++b
++B
+";
+        assert_eq!(inserted.to_string(), expected);
+        assert_eq!(Patch::from_str(expected).unwrap(), inserted);
+        assert_eq!(apply("a\nb", &inserted).unwrap(), "a\nb\nB\n");
+
+        // The deletion alone leaves the line as it is
+        let deleted = patch.select_lines(|_, _, line| matches!(line, Line::Delete(_)));
+        assert_eq!(apply("a\nb", &deleted).unwrap(), "a\n");
+
+        let patch = create_patch_bytes(b"a\nb", b"a\nB\n");
+        let inserted = patch.select_lines(|_, _, line| matches!(line, Line::Insert(_)));
+        assert_eq!(Patch::from_bytes(&inserted.to_bytes()).unwrap(), inserted);
+        assert_eq!(apply_bytes(b"a\nb", &inserted).unwrap(), b"a\nb\nB\n");
+    }
+}
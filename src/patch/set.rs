@@ -0,0 +1,345 @@
+use super::{into_owned, parse, ParsePatchError, Patch, PatchFormatter};
+use std::{borrow::Cow, fmt};
+
+/// A collection of `Patch`es which modify multiple files
+///
+/// This is the kind of patch produced by `git diff`, `git format-patch` or
+/// `diff -ruN`. Any text surrounding the individual patches (e.g. a commit
+/// message, `diff --git` lines or a signature) is preserved so that a parsed
+/// `PatchSet` can be written back out unchanged.
+#[derive(PartialEq, Eq)]
+pub struct PatchSet<'a, T: ToOwned + ?Sized> {
+    pub(super) patches: Vec<Patch<'a, T>>,
+    // The text preceding each patch
+    pub(super) preambles: Vec<Option<Cow<'a, T>>>,
+    // The text following the last patch
+    pub(super) trailer: Option<Cow<'a, T>>,
+}
+
+impl<'a, T: ToOwned + ?Sized> PatchSet<'a, T> {
+    /// Construct a `PatchSet` from a list of patches
+    pub fn new(patches: Vec<Patch<'a, T>>) -> Self {
+        let preambles = patches.iter().map(|_| None).collect();
+        Self {
+            patches,
+            preambles,
+            trailer: None,
+        }
+    }
+
+    /// Returns the patches in the set
+    pub fn patches(&self) -> &[Patch<'a, T>] {
+        &self.patches
+    }
+
+    /// Consumes the set, returning the patches it contains
+    pub fn into_patches(self) -> Vec<Patch<'a, T>> {
+        self.patches
+    }
+
+    /// Returns the text preceding the patch at `index`, if any
+    pub fn preamble(&self, index: usize) -> Option<&T> {
+        self.preambles.get(index)?.as_deref()
+    }
+
+    /// Returns the text following the last patch, if any
+    pub fn trailer(&self) -> Option<&T> {
+        self.trailer.as_deref()
+    }
+}
+
+impl<T: ToOwned + ?Sized + 'static> PatchSet<'_, T> {
+    /// Converts the set into one which owns all of its text, see
+    /// [`Patch::into_owned`]
+    pub fn into_owned(self) -> PatchSet<'static, T> {
+        PatchSet {
+            patches: self.patches.into_iter().map(Patch::into_owned).collect(),
+            preambles: self
+                .preambles
+                .into_iter()
+                .map(|preamble| preamble.map(into_owned))
+                .collect(),
+            trailer: self.trailer.map(into_owned),
+        }
+    }
+}
+
+impl<'a> PatchSet<'a, str> {
+    /// Parse a `PatchSet` from a string
+    ///
+    /// ```
+    /// use diffy::PatchSet;
+    ///
+    /// let s = "\
+    /// diff --git a/alpha b/alpha
+    /// --- a/alpha
+    /// +++ b/alpha
+    /// @@ -1 +1 @@
+    /// -Kaladin
+    /// +Shallan
+    /// diff --git a/beta b/beta
+    /// --- a/beta
+    /// +++ b/beta
+    /// @@ -1 +1 @@
+    /// -Dalinar
+    /// +Jasnah
+    /// ";
+    ///
+    /// let set = PatchSet::from_str(s).unwrap();
+    /// assert_eq!(set.patches().len(), 2);
+    /// assert_eq!(set.patches()[1].modified(), Some("b/beta"));
+    /// assert_eq!(set.patches()[1].git_header().unwrap().modified(), "b/beta");
+    /// assert_eq!(set.to_string(), s);
+    /// ```
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &'a str) -> Result<PatchSet<'a, str>, ParsePatchError> {
+        parse::parse_set(s)
+    }
+}
+
+impl<'a> PatchSet<'a, [u8]> {
+    /// Parse a `PatchSet` from bytes
+    pub fn from_bytes(s: &'a [u8]) -> Result<PatchSet<'a, [u8]>, ParsePatchError> {
+        parse::parse_set_bytes(s)
+    }
+}
+
+impl<T: AsRef<[u8]> + ToOwned + ?Sized> PatchSet<'_, T> {
+    /// Convert a `PatchSet` into bytes
+    ///
+    /// This is the equivalent of the `to_string` function but for
+    /// potentially non-utf8 patches.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        PatchFormatter::new()
+            .write_patch_set_into(self, &mut bytes)
+            .unwrap();
+        bytes
+    }
+}
+
+impl<T: ToOwned + ?Sized> Clone for PatchSet<'_, T> {
+    fn clone(&self) -> Self {
+        Self {
+            patches: self.patches.clone(),
+            preambles: self.preambles.clone(),
+            trailer: self.trailer.clone(),
+        }
+    }
+}
+
+impl fmt::Display for PatchSet<'_, str> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", PatchFormatter::new().fmt_patch_set(self))
+    }
+}
+
+impl<T: ?Sized, O> fmt::Debug for PatchSet<'_, T>
+where
+    T: ToOwned<Owned = O> + fmt::Debug,
+    O: std::borrow::Borrow<T> + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PatchSet")
+            .field("patches", &self.patches)
+            .field("preambles", &self.preambles)
+            .field("trailer", &self.trailer)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_patch_round_trip() {
+        let s = "\
+From 2d0a8ba1c2a1f2e1d8f1d4b8ad1bf0b4a6b1c0de Mon Sep 17 00:00:00 2001
+From: Navani Kholin <navani@kholinar.example>
+Date: Mon, 1 Jan 2024 00:00:00 +0000
+Subject: [PATCH] Update the records
+
+---
+ alpha | 3 ++-
+ beta  | 1 +
+ 2 files changed, 3 insertions(+), 1 deletion(-)
+
+diff --git a/alpha b/alpha
+index 1111111..2222222 100644
+--- a/alpha
++++ b/alpha
+@@ -1,3 +1,4 @@ fn main() {
+ Kaladin
+ 
+-Shallan
++Jasnah
++Dalinar
+diff --git a/beta b/beta
+new file mode 100644
+index 0000000..3333333
+--- /dev/null
++++ b/beta
+@@ -0,0 +1 @@
++Szeth
+\\ No newline at end of file
+-- 
+2.43.0
+
+";
+        let set = PatchSet::from_str(s).unwrap();
+        assert_eq!(set.patches().len(), 2);
+        assert!(set.preamble(0).unwrap().starts_with("From 2d0a8ba"));
+        assert_eq!(set.preamble(1), None);
+        assert!(set.patches()[1].git_header().unwrap().is_new_file());
+        assert_eq!(set.trailer(), Some("-- \n2.43.0\n\n"));
+        assert_eq!(set.patches()[1].original(), Some("/dev/null"));
+        assert_eq!(set.to_string(), s);
+
+        let set = PatchSet::from_bytes(s.as_bytes()).unwrap();
+        assert_eq!(set.to_bytes(), s.as_bytes());
+    }
+
+    #[test]
+    fn recursive_diff_round_trip() {
+        let s = "\
+Only in old: gamma
+diff -ruN old/alpha new/alpha
+--- old/alpha\t2024-01-01 00:00:00.000000000 +0000
++++ new/alpha\t2024-01-02 00:00:00.000000000 +0000
+@@ -1,3 +1,3 @@
+ Kaladin
+
+-Shallan
++Jasnah
+diff -ruN old/beta new/beta
+--- old/beta\t1970-01-01 00:00:00.000000000 +0000
++++ new/beta\t2024-01-02 00:00:00.000000000 +0000
+@@ -0,0 +1 @@
++Szeth
+";
+        let set = PatchSet::from_str(s).unwrap();
+        assert_eq!(set.patches().len(), 2);
+        assert_eq!(
+            set.preamble(0),
+            Some("Only in old: gamma\ndiff -ruN old/alpha new/alpha\n")
+        );
+        assert_eq!(set.trailer(), None);
+        assert_eq!(
+            set.patches()[1].original_timestamp(),
+            Some("1970-01-01 00:00:00.000000000 +0000")
+        );
+        assert_eq!(set.to_string(), s);
+    }
+
+    #[test]
+    fn patches_without_preambles() {
+        let s = "\
+--- a
++++ a
+@@ -1 +1 @@
+-1
++2
+--- b
++++ b
+@@ -1 +1 @@
+-3
++4
+";
+        let set = PatchSet::from_str(s).unwrap();
+        assert_eq!(set.patches().len(), 2);
+        assert_eq!(set.preamble(0), None);
+        assert_eq!(set.preamble(1), None);
+        assert_eq!(set.to_string(), s);
+
+        let set = PatchSet::new(set.into_patches());
+        assert_eq!(set.to_string(), s);
+    }
+
+    #[test]
+    fn mixed_blank_context_lines() {
+        let s = "\
+diff --git a/alpha b/alpha
+--- a/alpha
++++ b/alpha
+@@ -1,3 +1,3 @@
+ Kaladin
+ 
+-Shallan
++Jasnah
+diff -u old/beta new/beta
+--- old/beta
++++ new/beta
+@@ -1,3 +1,3 @@
+ Dalinar
+
+-Navani
++Adolin
+";
+        let set = PatchSet::from_str(s).unwrap();
+        assert_eq!(set.to_string(), s);
+        assert_eq!(set.clone().into_owned().to_string(), s);
+
+        let set = PatchSet::from_bytes(s.as_bytes()).unwrap();
+        assert_eq!(set.to_bytes(), s.as_bytes());
+    }
+
+    #[test]
+    fn into_owned() {
+        fn parse(s: &str) -> PatchSet<'static, str> {
+            // Make sure nothing borrows from the original text
+            let s = s.to_owned();
+            PatchSet::from_str(&s).unwrap().into_owned()
+        }
+
+        let s = "\
+Subject: [PATCH] Reorder the orders
+
+diff --git a/orders b/orders
+index 1111111..2222222 100644
+--- a/orders\t2024-01-01 00:00:00.000000000 +0000
++++ b/orders\t2024-01-02 00:00:00.000000000 +0000
+@@ -1,2 +1,2 @@ Knights Radiant
+-Windrunners
++Skybreakers
+ Dustbringers
+\\ No newline at end of file
+-- 
+2.43.0
+";
+        let set = parse(s);
+        assert_eq!(set.to_string(), s);
+        assert_eq!(set, PatchSet::from_str(s).unwrap());
+
+        let patch = &set.patches()[0];
+        assert_eq!(patch.hunks()[0].function_context(), Some("Knights Radiant"));
+        assert_eq!(
+            crate::apply("Windrunners\nDustbringers", patch).unwrap(),
+            "Skybreakers\nDustbringers"
+        );
+    }
+
+    #[test]
+    fn empty() {
+        let set = PatchSet::from_str("").unwrap();
+        assert!(set.patches().is_empty());
+        assert_eq!(set.to_string(), "");
+
+        let set = PatchSet::from_str("nothing to see here\n").unwrap();
+        assert!(set.patches().is_empty());
+        assert_eq!(set.trailer(), Some("nothing to see here\n"));
+        assert_eq!(set.to_string(), "nothing to see here\n");
+    }
+
+    #[test]
+    fn invalid_patch() {
+        let s = "\
+--- a
++++ a
+@@ -1,2 +1 @@
+-1
++2
+";
+        PatchSet::from_str(s).unwrap_err();
+    }
+}
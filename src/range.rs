@@ -33,7 +33,6 @@ impl<'a, T: ?Sized> Range<'a, T> {
         self.offset
     }
 
-    #[allow(dead_code)]
     pub fn range(&self) -> ops::Range<usize> {
         self.offset..self.offset + self.len
     }
@@ -0,0 +1,5 @@
+//! Splitting text into tokens for finding the words which changed within a line
+
+pub use crate::utils::token::{
+    AlphaNumeric, GroupTokenizer, Grouping, Number, Tokenizer, WhitespaceGroup,
+};
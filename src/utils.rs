@@ -1,6 +1,8 @@
 //! Common utilities
 
+use crate::diff::Whitespace;
 use std::{
+    borrow::Cow,
     collections::{hash_map::Entry, HashMap},
     hash::Hash,
 };
@@ -12,9 +14,22 @@ pub mod token;
 pub struct Classifier<'a, T: ?Sized> {
     next_id: u64,
     unique_ids: HashMap<&'a T, u64>,
+    whitespace: Whitespace,
+    // Lines with whitespace normalized according to `whitespace`, used instead of `unique_ids`
+    // when whitespace isn't compared exactly
+    normalized_ids: HashMap<Vec<u8>, u64>,
+    buf: Vec<u8>,
 }
 
 impl<'a, T: ?Sized + Eq + Hash> Classifier<'a, T> {
+    /// Construct a `Classifier` which treats whitespace in lines according to `whitespace`
+    pub fn with_whitespace(whitespace: Whitespace) -> Self {
+        Self {
+            whitespace,
+            ..Self::default()
+        }
+    }
+
     fn classify(&mut self, record: &'a T) -> u64 {
         match self.unique_ids.entry(record) {
             Entry::Occupied(o) => *o.get(),
@@ -34,7 +49,7 @@ impl<'a, T: ?Sized + Text> Classifier<'a, T> {
         let mut pos = 0;
         token::LineIter::new(text)
             .map(|line| {
-                (line, self.classify(line), {
+                (line, self.classify_line(line), {
                     let p = pos;
                     pos += line.len();
                     p
@@ -42,11 +57,98 @@ impl<'a, T: ?Sized + Text> Classifier<'a, T> {
             })
             .unzip_n()
     }
+
+    fn classify_line(&mut self, line: &'a T) -> u64 {
+        if self.whitespace == Whitespace::Exact {
+            return self.classify(line);
+        }
+
+        normalize_whitespace(line.as_bytes(), self.whitespace, &mut self.buf);
+        if let Some(&id) = self.normalized_ids.get(&self.buf[..]) {
+            return id;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.normalized_ids.insert(self.buf.clone(), id);
+        id
+    }
+}
+
+// Writes `line` into `out` with its whitespace normalized such that two lines are considered
+// equal under `whitespace` exactly when their normalized forms are equal
+fn normalize_whitespace(line: &[u8], whitespace: Whitespace, out: &mut Vec<u8>) {
+    out.clear();
+    let trimmed = match line.iter().rposition(|b| !b.is_ascii_whitespace()) {
+        Some(last) => &line[..=last],
+        None => &[],
+    };
+    match whitespace {
+        Whitespace::Exact => out.extend_from_slice(line),
+        Whitespace::IgnoreAtEol => out.extend_from_slice(trimmed),
+        Whitespace::IgnoreChange => {
+            let mut in_whitespace = false;
+            for &b in trimmed {
+                if b.is_ascii_whitespace() {
+                    in_whitespace = true;
+                } else {
+                    if in_whitespace {
+                        out.push(b' ');
+                        in_whitespace = false;
+                    }
+                    out.push(b);
+                }
+            }
+        }
+        Whitespace::IgnoreAll => out.extend(trimmed.iter().filter(|b| !b.is_ascii_whitespace())),
+    }
+}
+
+/// Returns whether `a` and `b` are equal under `whitespace`
+///
+/// This gives the same answer as comparing the lines written by
+/// `normalize_whitespace`, without allocating.
+pub fn whitespace_eq(a: &[u8], b: &[u8], whitespace: Whitespace) -> bool {
+    fn trim_end(line: &[u8]) -> &[u8] {
+        match line.iter().rposition(|b| !b.is_ascii_whitespace()) {
+            Some(last) => &line[..=last],
+            None => &[],
+        }
+    }
+    fn words(line: &[u8]) -> impl Iterator<Item = &[u8]> {
+        line.split(u8::is_ascii_whitespace)
+            .filter(|word| !word.is_empty())
+    }
+    fn has_leading_whitespace(line: &[u8]) -> bool {
+        line.first().map_or(false, u8::is_ascii_whitespace)
+    }
+
+    let (a_trimmed, b_trimmed) = (trim_end(a), trim_end(b));
+    match whitespace {
+        Whitespace::Exact => a == b,
+        Whitespace::IgnoreAtEol => a_trimmed == b_trimmed,
+        Whitespace::IgnoreChange => {
+            has_leading_whitespace(a_trimmed) == has_leading_whitespace(b_trimmed)
+                && words(a_trimmed).eq(words(b_trimmed))
+        }
+        Whitespace::IgnoreAll => a_trimmed
+            .iter()
+            .filter(|b| !b.is_ascii_whitespace())
+            .eq(b_trimmed.iter().filter(|b| !b.is_ascii_whitespace())),
+    }
+}
+
+/// Returns whether `line` consists only of whitespace
+pub fn is_blank(line: &[u8]) -> bool {
+    line.iter().all(u8::is_ascii_whitespace)
 }
 
 impl<'a> Classifier<'a, str> {
-    pub fn classify_groups(&mut self, text: &'a str) -> (Vec<&'a str>, Vec<u64>) {
-        token::GroupIter::new(text)
+    pub fn classify_groups(
+        &mut self,
+        text: &'a str,
+        tokenizer: &dyn token::Tokenizer,
+    ) -> (Vec<&'a str>, Vec<u64>) {
+        token::GroupIter::new(text, tokenizer)
             .map(|group| (group, self.classify(group)))
             .unzip()
     }
@@ -57,13 +159,16 @@ impl<T: Eq + Hash + ?Sized> Default for Classifier<'_, T> {
         Self {
             next_id: 0,
             unique_ids: HashMap::default(),
+            whitespace: Whitespace::default(),
+            normalized_ids: HashMap::default(),
+            buf: Vec::new(),
         }
     }
 }
 
 /// A helper trait for processing text like `str` and `[u8]`
 /// Useful for abstracting over those types for parsing as well as breaking input into lines
-pub trait Text: Eq + Hash {
+pub trait Text: Eq + Hash + ToOwned {
     fn is_empty(&self) -> bool;
     fn len(&self) -> usize;
     fn starts_with(&self, prefix: &str) -> bool;
@@ -75,7 +180,13 @@ pub trait Text: Eq + Hash {
     fn split_at(&self, mid: usize) -> (&Self, &Self);
     fn as_str(&self) -> Option<&str>;
     fn as_bytes(&self) -> &[u8];
-    fn lines(&self) -> token::LineIter<Self>;
+    fn lines(&self) -> token::LineIter<'_, '_, Self>;
+
+    /// Joins `pieces` together, only allocating when there is more than one piece
+    fn concat<'a>(pieces: &[&'a Self]) -> Cow<'a, Self>;
+
+    /// Returns a copy of the text with a newline appended
+    fn with_newline(&self) -> Self::Owned;
 
     fn parse<T: std::str::FromStr>(&self) -> Option<T> {
         self.as_str().and_then(|s| s.parse().ok())
@@ -128,9 +239,21 @@ impl Text for str {
         self.as_bytes()
     }
 
-    fn lines(&self) -> token::LineIter<Self> {
+    fn lines(&self) -> token::LineIter<'_, '_, Self> {
         token::LineIter::new(self)
     }
+
+    fn concat<'a>(pieces: &[&'a Self]) -> Cow<'a, Self> {
+        match pieces {
+            [] => Cow::Borrowed(""),
+            [piece] => Cow::Borrowed(piece),
+            pieces => Cow::Owned(pieces.concat()),
+        }
+    }
+
+    fn with_newline(&self) -> String {
+        format!("{}\n", self)
+    }
 }
 
 impl Text for [u8] {
@@ -178,9 +301,21 @@ impl Text for [u8] {
         self
     }
 
-    fn lines(&self) -> token::LineIter<Self> {
+    fn lines(&self) -> token::LineIter<'_, '_, Self> {
         token::LineIter::new(self)
     }
+
+    fn concat<'a>(pieces: &[&'a Self]) -> Cow<'a, Self> {
+        match pieces {
+            [] => Cow::Borrowed(&[]),
+            [piece] => Cow::Borrowed(piece),
+            pieces => Cow::Owned(pieces.concat()),
+        }
+    }
+
+    fn with_newline(&self) -> Vec<u8> {
+        [self, b"\n"].concat()
+    }
 }
 
 fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
@@ -216,3 +351,45 @@ fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
 fn find_byte(haystack: &[u8], byte: u8) -> Option<usize> {
     haystack.iter().position(|&b| b == byte)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whitespace_eq_matches_normalize_whitespace() {
+        let lines: &[&[u8]] = &[
+            b"",
+            b"\n",
+            b"  \n",
+            b"a b\n",
+            b"a  b\r\n",
+            b" a b\n",
+            b"\ta\tb",
+            b"ab\n",
+            b"a b \n",
+        ];
+        let (mut a_buf, mut b_buf) = (Vec::new(), Vec::new());
+        for &whitespace in &[
+            Whitespace::Exact,
+            Whitespace::IgnoreAtEol,
+            Whitespace::IgnoreChange,
+            Whitespace::IgnoreAll,
+        ] {
+            for a in lines {
+                for b in lines {
+                    normalize_whitespace(a, whitespace, &mut a_buf);
+                    normalize_whitespace(b, whitespace, &mut b_buf);
+                    assert_eq!(
+                        whitespace_eq(a, b, whitespace),
+                        a_buf == b_buf,
+                        "{:?} {:?} {:?}",
+                        whitespace,
+                        a,
+                        b
+                    );
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,8 @@
 mod groups;
 
+pub use groups::{AlphaNumeric, Grouping, Number, WhitespaceGroup};
+use std::fmt;
+
 pub struct TokenIter<'a, 'f, T: ?Sized>(&'a T, &'f dyn Fn(&'a T) -> Option<usize>);
 
 impl<'a, 'f, T: ?Sized> TokenIter<'a, 'f, T> {
@@ -28,51 +31,127 @@ impl<'a, 'f, T: super::Text + ?Sized> Iterator for TokenIter<'a, 'f, T> {
     }
 }
 
-/// Iterator over the lines of a string, including the `\n` character.
-pub struct GroupIter<'a, 'f>(TokenIter<'a, 'f, str>);
+/// Splits text into the tokens compared when finding the words which changed
+/// within a line
+///
+/// This is used by [`PatchFormatter::with_color`] to highlight changed words
+/// and by [`MergeOptions`] to resolve conflicts which only touch different
+/// words of the same lines.
+///
+/// ```
+/// use diffy::token::Tokenizer;
+///
+/// /// Splits `camelCase` identifiers into their words
+/// #[derive(Debug)]
+/// struct CamelCase;
+///
+/// impl Tokenizer for CamelCase {
+///     fn token_len(&self, text: &str) -> usize {
+///         let mut chars = text.char_indices();
+///         let (_, first) = chars.next().unwrap();
+///         if !first.is_alphabetic() {
+///             return first.len_utf8();
+///         }
+///         chars
+///             .find(|&(_, c)| !c.is_lowercase())
+///             .map_or(text.len(), |(i, _)| i)
+///     }
+/// }
+///
+/// assert_eq!(CamelCase.token_len("camelCase"), 5);
+/// ```
+///
+/// [`PatchFormatter::with_color`]: crate::PatchFormatter::with_color
+/// [`MergeOptions`]: crate::MergeOptions
+pub trait Tokenizer: fmt::Debug + Send + Sync {
+    /// Returns the length in bytes of the token at the start of `text`
+    ///
+    /// `text` is never empty. The returned length must be greater than zero
+    /// and fall on a char boundary of `text`, otherwise the first character of
+    /// `text` is used as the token.
+    fn token_len(&self, text: &str) -> usize;
+}
+
+/// A [`Tokenizer`] which groups characters using a list of [`Grouping`]s
+///
+/// At each position the groupings are tried in order and the first one which
+/// matches determines the token. Characters which no grouping matches are
+/// tokens on their own. The default tokenizer uses [`Number`],
+/// [`AlphaNumeric`] and [`WhitespaceGroup`].
+///
+/// ```
+/// use diffy::token::{GroupTokenizer, Grouping, Tokenizer};
+///
+/// /// Groups runs of `:` so that `::` is a single token
+/// #[derive(Debug)]
+/// struct Colons;
+///
+/// impl Grouping for Colons {
+///     fn belongs(&self, c: char) -> bool {
+///         c == ':'
+///     }
+/// }
+///
+/// let tokenizer = GroupTokenizer::default().with_group(Colons);
+/// assert_eq!(tokenizer.token_len("::new"), 2);
+/// assert_eq!(GroupTokenizer::default().token_len("::new"), 1);
+/// ```
+#[derive(Debug)]
+pub struct GroupTokenizer {
+    groups: Vec<Box<dyn Grouping>>,
+}
+
+impl GroupTokenizer {
+    /// Construct a tokenizer with no groupings, which splits text into
+    /// individual characters
+    pub fn new() -> Self {
+        Self { groups: Vec::new() }
+    }
+
+    /// Add a grouping, which is tried after the ones added before it
+    pub fn with_group<G: Grouping + 'static>(mut self, group: G) -> Self {
+        self.groups.push(Box::new(group));
+        self
+    }
+}
+
+impl Default for GroupTokenizer {
+    fn default() -> Self {
+        Self::new()
+            .with_group(Number)
+            .with_group(AlphaNumeric)
+            .with_group(WhitespaceGroup)
+    }
+}
+
+impl Tokenizer for GroupTokenizer {
+    fn token_len(&self, s: &str) -> usize {
+        let c = match s.chars().next() {
+            Some(c) => c,
+            None => return 0,
+        };
+
+        for grouper in &self.groups {
+            if !grouper.start(c) {
+                continue;
+            }
+            let end = s.find(|c: char| !grouper.belongs(c)).unwrap_or(s.len());
+            // Shorten the group to end on a character which is allowed to end it
+            if let Some((pos, c)) = s[..end].char_indices().rev().find(|&(_, c)| grouper.end(c)) {
+                return pos + c.len_utf8();
+            }
+        }
+        // By default, characters don't group at all
+        c.len_utf8()
+    }
+}
+
+/// Iterator over the tokens of a string produced by a `Tokenizer`
+pub struct GroupIter<'a, 'f>(&'a str, &'f dyn Tokenizer);
 
 impl<'a, 'f> GroupIter<'a, 'f> {
-    pub fn new(text: &'a str) -> Self {
-        Self(TokenIter::<'a, 'f, str>::new(
-            text,
-            &|s: &'a str| -> Option<usize> {
-                if let Some(c) = s.chars().nth(0) {
-                    // The order of possible groups to match in order of preference
-                    let groups: &[Box<&dyn groups::Grouping>] = &[
-                        Box::new(&groups::Number {}),
-                        Box::new(&groups::AlphaNumeric {}),
-                        Box::new(&groups::Whitespace {}),
-                    ];
-
-                    for grouper in groups.iter() {
-                        if !grouper.start(c) {
-                            continue;
-                        }
-                        let mut pos = match s.find(|c: char| !grouper.belongs(c)) {
-                            None => s.len(),
-                            Some(pos) => pos,
-                        };
-                        loop {
-                            loop {
-                                pos -= 1;
-                                if s.is_char_boundary(pos) {
-                                    break;
-                                }
-                            }
-                            match s[pos..].chars().next() {
-                                // Oops, should be looking for the byte before
-                                Some(c) if grouper.end(c) => return Some(pos + c.len_utf8()),
-                                Some(_) => continue,
-                                _ => break,
-                            }
-                        }
-                    }
-                    // By default, characters don't group at all
-                    return Some(c.len_utf8());
-                }
-                None
-            },
-        ))
+    pub fn new(text: &'a str, tokenizer: &'f dyn Tokenizer) -> Self {
+        Self(text, tokenizer)
     }
 }
 
@@ -80,7 +159,17 @@ impl<'a, 'f> Iterator for GroupIter<'a, 'f> {
     type Item = &'a str;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.0.next()
+        let c = self.0.chars().next()?;
+        let len = self.1.token_len(self.0);
+        let len = if len > 0 && self.0.is_char_boundary(len) {
+            len
+        } else {
+            c.len_utf8()
+        };
+
+        let (token, remaining) = self.0.split_at(len);
+        self.0 = remaining;
+        Some(token)
     }
 }
 
@@ -91,12 +180,7 @@ impl<'a, 'f, T: super::Text + ?Sized> LineIter<'a, 'f, T> {
     pub fn new(text: &'a T) -> Self {
         Self(TokenIter::<'a, 'f, T>::new(
             text,
-            &|s: &'a T| -> Option<usize> {
-                match s.find("\n") {
-                    Some(ndx) => Some(ndx + 1),
-                    None => None,
-                }
-            },
+            &|s: &'a T| -> Option<usize> { s.find("\n").map(|ndx| ndx + 1) },
         ))
     }
 }
@@ -121,8 +205,10 @@ mod tests {
             line three
         "};
 
-        let answer: Vec<_> =
-            TokenIter::new(lines, &|s: &str| -> Option<usize> { s.find("\n") }).collect();
+        let answer: Vec<_> = TokenIter::new(lines, &|s: &str| -> Option<usize> {
+            s.find("\n").map(|i| i + 1)
+        })
+        .collect();
         assert_eq!(vec!["line one\n", "line two\r\n", "line three\n"], answer);
     }
 
@@ -130,7 +216,7 @@ mod tests {
     fn test_words() {
         assert_eq!(
             vec![" ", "one", " ", "two", "     ", "three", "\n"],
-            GroupIter::new(" one two     three\n").collect::<Vec<_>>()
+            GroupIter::new(" one two     three\n", &GroupTokenizer::default()).collect::<Vec<_>>()
         );
     }
 
@@ -138,7 +224,7 @@ mod tests {
     fn test_number() {
         assert_eq!(
             vec!["$", "1000000.00", "."],
-            GroupIter::new("$1000000.00.").collect::<Vec<_>>()
+            GroupIter::new("$1000000.00.", &GroupTokenizer::default()).collect::<Vec<_>>()
         );
     }
 
@@ -146,7 +232,57 @@ mod tests {
     fn test_alnum() {
         assert_eq!(
             vec!["_alpha_numeric"],
-            GroupIter::new("_alpha_numeric").collect::<Vec<_>>()
+            GroupIter::new("_alpha_numeric", &GroupTokenizer::default()).collect::<Vec<_>>()
+        );
+    }
+
+    #[derive(Debug)]
+    struct Arrow;
+
+    impl Grouping for Arrow {
+        fn start(&self, c: char) -> bool {
+            c == '-'
+        }
+        fn belongs(&self, c: char) -> bool {
+            c == '-' || c == '>'
+        }
+        fn end(&self, c: char) -> bool {
+            c == '>'
+        }
+    }
+
+    #[test]
+    fn test_custom_grouping() {
+        let tokenizer = GroupTokenizer::new().with_group(Arrow);
+        assert_eq!(
+            vec!["a", "-", "-", ">", "b", "-", "c"],
+            GroupIter::new("a-->b-c", &GroupTokenizer::default()).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["a", "-->", "b", "-", "c"],
+            GroupIter::new("a-->b-c", &tokenizer).collect::<Vec<_>>()
+        );
+    }
+
+    #[derive(Debug)]
+    struct Broken;
+
+    impl Tokenizer for Broken {
+        fn token_len(&self, text: &str) -> usize {
+            if text.starts_with('a') {
+                0
+            } else {
+                2
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_token_len() {
+        // Lengths which are zero or don't fall on a char boundary fall back to a single char
+        assert_eq!(
+            vec!["a", "bc", "\u{2603}", "d"],
+            GroupIter::new("abc\u{2603}d", &Broken).collect::<Vec<_>>()
         );
     }
 }
@@ -1,15 +1,27 @@
+use std::fmt;
 use unicode_categories::UnicodeCategories;
 
-pub trait Grouping {
+/// A class of characters which are grouped into a single token by a
+/// [`GroupTokenizer`](super::GroupTokenizer)
+///
+/// A group starts with a character matching `start`, extends over the
+/// following characters matching `belongs` and is then shortened to end on
+/// the last of those characters matching `end`.
+pub trait Grouping: fmt::Debug + Send + Sync {
+    /// Returns whether a group can start with `c`
     fn start(&self, c: char) -> bool {
         self.belongs(c)
     }
+    /// Returns whether `c` can be part of a group
     fn belongs(&self, c: char) -> bool;
+    /// Returns whether a group can end with `c`
     fn end(&self, c: char) -> bool {
         self.belongs(c)
     }
 }
 
+/// Groups numbers, including any decimal points within them
+#[derive(Debug)]
 pub struct Number;
 
 impl Grouping for Number {
@@ -24,6 +36,8 @@ impl Grouping for Number {
     }
 }
 
+/// Groups runs of alphanumeric characters and connectors like `_`
+#[derive(Debug)]
 pub struct AlphaNumeric;
 
 impl Grouping for AlphaNumeric {
@@ -32,9 +46,11 @@ impl Grouping for AlphaNumeric {
     }
 }
 
-pub struct Whitespace;
+/// Groups runs of whitespace
+#[derive(Debug)]
+pub struct WhitespaceGroup;
 
-impl Grouping for Whitespace {
+impl Grouping for WhitespaceGroup {
     fn belongs(&self, c: char) -> bool {
         c.is_whitespace()
     }